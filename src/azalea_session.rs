@@ -0,0 +1,89 @@
+use azalea::prelude::*;
+use azalea::container::{ContainerClientExt, ContainerHandleRef};
+use azalea::inventory::ItemStack;
+use azalea::inventory::components::{CustomName, Lore, MapId};
+use azalea::inventory::operations::ThrowClick;
+use crate::session::{GameSession, SlotItem, Slots, Window};
+use crate::text::flatten;
+
+/// Extract lore text from an item
+/// In Minecraft 1.21.1+, lore is stored in components, not NBT
+fn extract_lore(item: &ItemStack) -> Vec<String> {
+    // Check if item is present and has lore component
+    if let Some(lore_component) = item.get_component::<Lore>() {
+        // Convert each FormattedText line to a plain string
+        lore_component.lines.iter()
+            .map(flatten)
+            .collect()
+    } else {
+        vec![]
+    }
+}
+
+/// Copy what the trading flows need out of an azalea item stack
+fn slot_item(item: &ItemStack) -> Option<SlotItem> {
+    let ItemStack::Present(data) = item else {
+        return None;
+    };
+    Some(SlotItem {
+        kind: data.kind.to_string(),
+        count: data.count,
+        name: item.get_component::<CustomName>().map(|custom| flatten(&custom.name)),
+        lore: extract_lore(item),
+        map_id: item.get_component::<MapId>().map(|id| id.id),
+    })
+}
+
+fn snapshot(menu: azalea::inventory::Menu) -> Slots {
+    menu.slots().iter().map(slot_item).collect()
+}
+
+impl Window for ContainerHandleRef {
+    fn id(&self) -> i32 {
+        ContainerHandleRef::id(self)
+    }
+
+    fn contents(&self) -> Option<Slots> {
+        self.menu().map(snapshot)
+    }
+
+    fn left_click(&self, slot: usize) {
+        ContainerHandleRef::left_click(self, slot);
+    }
+
+    fn right_click(&self, slot: usize) {
+        ContainerHandleRef::right_click(self, slot);
+    }
+
+    fn shift_click(&self, slot: usize) {
+        ContainerHandleRef::shift_click(self, slot);
+    }
+
+    fn throw_stack(&self, slot: usize) {
+        self.click(ThrowClick::All { slot: slot as u16 });
+    }
+
+    fn close(&self) {
+        ContainerHandleRef::close(self);
+    }
+}
+
+impl GameSession for Client {
+    type Inventory = ContainerHandleRef;
+
+    fn chat(&self, message: &str) {
+        Client::chat(self, message);
+    }
+
+    fn username(&self) -> String {
+        Client::username(self).to_string()
+    }
+
+    fn current_window(&self) -> ContainerHandleRef {
+        self.get_inventory()
+    }
+
+    fn select_hotbar_slot(&self, slot: u8) {
+        self.set_selected_hotbar_slot(slot);
+    }
+}
@@ -0,0 +1,104 @@
+use crate::price_parser::Money;
+
+/// Outcome of checking a purchase against the known balance
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BalanceCheck {
+    /// Enough money left over after the purchase
+    Affordable,
+    /// No `/bal` response seen yet; the purchase is allowed
+    Unknown,
+    /// Buying would leave less than the reserve
+    BelowReserve { balance: Money, remaining: Money },
+}
+
+/// Best-known in-game balance
+///
+/// Seeded from `/bal` and kept current between checks by applying our own purchases
+/// and sales, so a stale reading only drifts by what other players pay us through
+/// channels we don't see.
+#[derive(Debug, Default)]
+pub struct BalanceTracker {
+    balance: Option<Money>,
+    awaiting_response: bool,
+}
+
+impl BalanceTracker {
+    pub fn balance(&self) -> Option<Money> {
+        self.balance
+    }
+
+    pub fn is_awaiting_response(&self) -> bool {
+        self.awaiting_response
+    }
+
+    pub fn set_awaiting_response(&mut self, awaiting: bool) {
+        self.awaiting_response = awaiting;
+    }
+
+    /// Replace the balance with a fresh reading from the server
+    pub fn set(&mut self, balance: Money) {
+        self.balance = Some(balance);
+        self.awaiting_response = false;
+    }
+
+    pub fn record_purchase(&mut self, price: Money) {
+        if let Some(balance) = self.balance.as_mut() {
+            *balance = balance.saturating_sub(price);
+        }
+    }
+
+    pub fn record_sale(&mut self, price: Money) {
+        if let Some(balance) = self.balance.as_mut() {
+            *balance = balance.saturating_add(price);
+        }
+    }
+
+    /// Would buying at `price` keep at least `reserve` in the bank?
+    pub fn check_purchase(&self, price: Money, reserve: Money) -> BalanceCheck {
+        let Some(balance) = self.balance else {
+            return BalanceCheck::Unknown;
+        };
+        match balance.checked_sub(price) {
+            Some(remaining) if remaining >= reserve => BalanceCheck::Affordable,
+            remaining => BalanceCheck::BelowReserve {
+                balance,
+                remaining: remaining.unwrap_or(Money::ZERO),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_purchase_respects_reserve() {
+        let mut tracker = BalanceTracker::default();
+        assert_eq!(tracker.check_purchase(Money::new(2000), Money::new(5000)), BalanceCheck::Unknown);
+
+        tracker.set(Money::new(8000));
+        assert_eq!(tracker.check_purchase(Money::new(2000), Money::new(5000)), BalanceCheck::Affordable);
+        assert_eq!(
+            tracker.check_purchase(Money::new(4000), Money::new(5000)),
+            BalanceCheck::BelowReserve { balance: Money::new(8000), remaining: Money::new(4000) }
+        );
+        assert_eq!(
+            tracker.check_purchase(Money::new(9000), Money::ZERO),
+            BalanceCheck::BelowReserve { balance: Money::new(8000), remaining: Money::ZERO }
+        );
+    }
+
+    #[test]
+    fn test_local_updates_between_checks() {
+        let mut tracker = BalanceTracker::default();
+        tracker.record_purchase(Money::new(1000));
+        assert_eq!(tracker.balance(), None);
+
+        tracker.set(Money::new(5000));
+        tracker.record_purchase(Money::new(2000));
+        tracker.record_sale(Money::new(9900));
+        tracker.record_purchase(Money::new(20000));
+        assert_eq!(tracker.balance(), Some(Money::ZERO));
+    }
+}
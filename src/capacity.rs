@@ -0,0 +1,142 @@
+use std::fmt;
+use std::ops::Range;
+use crate::config::FullInventoryPolicy;
+
+/// Player inventory menu slots that hold items: main inventory (9-35) and hotbar (36-44).
+/// 0-8 are the crafting grid and armour, 45 the offhand.
+pub const ITEM_SLOTS: Range<usize> = 9..45;
+
+/// What occupies one inventory slot, as far as capacity planning cares
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotContent {
+    Empty,
+    Map { count: u32 },
+    Other,
+}
+
+/// Slot usage of the main inventory and hotbar
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InventoryModel {
+    pub free_slots: u32,
+    pub map_stacks: u32,
+    pub maps: u32,
+    pub other_stacks: u32,
+}
+
+impl InventoryModel {
+    /// Build the model from a player inventory menu, indexed as the menu is
+    pub fn from_slots(slots: &[SlotContent]) -> Self {
+        let end = ITEM_SLOTS.end.min(slots.len());
+        let start = ITEM_SLOTS.start.min(end);
+        slots[start..end].iter().fold(Self::default(), |mut model, slot| {
+            match slot {
+                SlotContent::Empty => model.free_slots += 1,
+                SlotContent::Map { count } => {
+                    model.map_stacks += 1;
+                    model.maps += count;
+                }
+                SlotContent::Other => model.other_stacks += 1,
+            }
+            model
+        })
+    }
+
+    /// Every map has its own id, so a bought map never stacks onto ours and needs an empty slot
+    pub fn fits_purchase(&self) -> bool {
+        self.free_slots > 0
+    }
+}
+
+impl fmt::Display for InventoryModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} free slot(s), {} map(s) in {} stack(s), {} other stack(s)",
+            self.free_slots, self.maps, self.map_stacks, self.other_stacks
+        )
+    }
+}
+
+/// What to do about capacity before the next purchase
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CapacityPlan {
+    Buy,
+    Skip,
+    ListFirst,
+    StoreFirst,
+}
+
+/// Decide whether the next purchase fits, and if not how to make room per `policy`
+pub fn plan_purchase(model: &InventoryModel, policy: FullInventoryPolicy) -> CapacityPlan {
+    if model.fits_purchase() {
+        return CapacityPlan::Buy;
+    }
+    // Listing or storing only helps if there are maps to move out of the way
+    match policy {
+        FullInventoryPolicy::List if model.map_stacks > 0 => CapacityPlan::ListFirst,
+        FullInventoryPolicy::Store if model.map_stacks > 0 => CapacityPlan::StoreFirst,
+        _ => CapacityPlan::Skip,
+    }
+}
+
+/// Whether `item_id` (e.g. "minecraft:golden_apple") is on the keep-list;
+/// the `minecraft:` namespace is optional on both sides
+pub fn is_kept(item_id: &str, keep_items: &[String]) -> bool {
+    let bare = |id: &str| id.trim().trim_start_matches("minecraft:").to_ascii_lowercase();
+    let item_id = bare(item_id);
+    keep_items.iter().any(|kept| bare(kept) == item_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inventory(maps: &[u32], other: usize) -> Vec<SlotContent> {
+        // Crafting grid and armour are never counted, even when occupied
+        let mut slots = vec![SlotContent::Other; ITEM_SLOTS.start];
+        slots.extend(maps.iter().map(|&count| SlotContent::Map { count }));
+        slots.extend(std::iter::repeat_n(SlotContent::Other, other));
+        slots.resize(ITEM_SLOTS.end, SlotContent::Empty);
+        // Offhand
+        slots.push(SlotContent::Other);
+        slots
+    }
+
+    #[test]
+    fn test_model_counts_item_slots_only() {
+        let model = InventoryModel::from_slots(&inventory(&[1, 3, 1], 2));
+        assert_eq!(
+            model,
+            InventoryModel { free_slots: 31, map_stacks: 3, maps: 5, other_stacks: 2 }
+        );
+        assert_eq!(model.to_string(), "31 free slot(s), 5 map(s) in 3 stack(s), 2 other stack(s)");
+
+        // A menu that hasn't loaded yet has no room
+        assert!(!InventoryModel::from_slots(&[]).fits_purchase());
+    }
+
+    #[test]
+    fn test_plan_purchase_per_policy() {
+        let room = InventoryModel::from_slots(&inventory(&[1; 35], 0));
+        assert_eq!(plan_purchase(&room, FullInventoryPolicy::Skip), CapacityPlan::Buy);
+
+        let full = InventoryModel::from_slots(&inventory(&[1; 30], 6));
+        assert_eq!(plan_purchase(&full, FullInventoryPolicy::Skip), CapacityPlan::Skip);
+        assert_eq!(plan_purchase(&full, FullInventoryPolicy::List), CapacityPlan::ListFirst);
+        assert_eq!(plan_purchase(&full, FullInventoryPolicy::Store), CapacityPlan::StoreFirst);
+
+        // Full of other items: nothing the bot can list or store
+        let junk = InventoryModel::from_slots(&inventory(&[], 36));
+        assert_eq!(plan_purchase(&junk, FullInventoryPolicy::List), CapacityPlan::Skip);
+    }
+
+    #[test]
+    fn test_keep_list_namespace_is_optional() {
+        let keep = vec!["minecraft:golden_apple".to_string(), "Totem_Of_Undying".to_string()];
+        assert!(is_kept("minecraft:golden_apple", &keep));
+        assert!(is_kept("golden_apple", &keep));
+        assert!(is_kept("minecraft:totem_of_undying", &keep));
+        assert!(!is_kept("minecraft:rotten_flesh", &keep));
+        assert!(!is_kept("minecraft:golden_apple", &[]));
+    }
+}
@@ -0,0 +1,490 @@
+use anyhow::{anyhow, Result};
+use regex::{Regex, RegexBuilder};
+use std::sync::OnceLock;
+use crate::config::ChatPatterns;
+use crate::price_parser::{parse_price, Money};
+use crate::text::normalize;
+
+/// One of our listings bought by another player
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sale {
+    pub buyer: String,
+    /// Item name as the server shows it, e.g. "Map"
+    pub item: String,
+    pub quantity: u32,
+    /// What the buyer paid for the whole listing
+    pub price: Money,
+}
+
+impl Sale {
+    pub fn is_map(&self) -> bool {
+        self.item.to_lowercase().split_whitespace().any(|word| word == "map" || word == "maps")
+    }
+}
+
+/// What a chat line from the server means to the bot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerMessage {
+    /// Someone bought one of our listings
+    Sale(Sale),
+    /// The summary of sales made while we were offline, sent on login
+    OfflineSales { count: Option<u32>, total: Money },
+    /// The auction we clicked was bought by someone else first
+    AlreadyBought,
+    InsufficientFunds,
+    /// The server moved us to an AFK area
+    AfkTeleport,
+    /// A `/shards` reply, with the balance if it could be read
+    ShardBalance { shards: Option<u64> },
+    /// A `/bal` reply
+    Balance { amount: Money },
+    /// The server refused a listing because we're at its cap, which it may name
+    ListingCapReached { cap: Option<u32> },
+    Unknown,
+}
+
+/// The kinds of line a pattern can recognise, in the order they are tried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Sale,
+    OfflineSales,
+    AlreadyBought,
+    InsufficientFunds,
+    ListingCapReached,
+    AfkTeleport,
+    Balance,
+    ShardBalance,
+}
+
+const KINDS: [Kind; 8] = [
+    Kind::Sale,
+    Kind::OfflineSales,
+    Kind::AlreadyBought,
+    Kind::InsufficientFunds,
+    Kind::ListingCapReached,
+    Kind::AfkTeleport,
+    Kind::Balance,
+    Kind::ShardBalance,
+];
+
+impl Kind {
+    /// Key under `chatPatterns` in the config
+    fn key(self) -> &'static str {
+        match self {
+            Kind::Sale => "sale",
+            Kind::OfflineSales => "offlineSales",
+            Kind::AlreadyBought => "alreadyBought",
+            Kind::InsufficientFunds => "insufficientFunds",
+            Kind::ListingCapReached => "listingCap",
+            Kind::AfkTeleport => "afkTeleport",
+            Kind::Balance => "balance",
+            Kind::ShardBalance => "shardBalance",
+        }
+    }
+
+    /// Patterns used when the config doesn't override them, tried in order
+    fn built_in(self) -> &'static [&'static str] {
+        match self {
+            // "Steve bought your Map for $9.9K", "Steve purchased your Map x3 for $29.7K",
+            // "You sold 3x Map to Steve for $29.7K"; usernames can have spaces. Only an
+            // "[AH] " tag or an offline notice may come before the buyer.
+            Kind::Sale => &[
+                r"^(?:\[[^\]]*\]\s*)?(?:while you were (?:offline|away),\s*)?(?P<buyer>[^<>:»]+?)\s+(?:bought|purchased) your (?P<item>.+?) for (?P<price>\$\s*[\d,.]+\s*[kmbt]?)\s*[.!]?$",
+                r"^(?:\[[^\]]*\]\s*)?(?P<buyer>[^<>:»]+?)\s+(?:bought|purchased) (?P<item>.+?) from you for (?P<price>\$\s*[\d,.]+\s*[kmbt]?)\s*[.!]?$",
+                r"^(?:\[[^\]]*\]\s*)?you sold (?:your )?(?P<item>.+?) to (?P<buyer>[^<>:»]+?) for (?P<price>\$\s*[\d,.]+\s*[kmbt]?)\s*[.!]?$",
+            ],
+            // "While you were offline, you sold 3 items for $29.7K",
+            // "You earned $29.7K from 3 auctions while you were offline"
+            Kind::OfflineSales => &[
+                r"^while you were (?:offline|away)\b.*?(?P<total>\$\s*[\d,.]+\s*[kmbt]?)",
+                r"^you (?:earned|made) (?P<total>\$\s*[\d,.]+\s*[kmbt]?).*?while you were (?:offline|away)",
+            ],
+            // "This item has already been bought!", "This auction is no longer available."
+            Kind::AlreadyBought => &[
+                r"^(?:\[[^\]]*\]\s*)?this (?:item|auction|listing) (?:has already been (?:bought|sold)|is no longer (?:available|for sale))\b",
+            ],
+            // "You don't have enough money to buy this!", "You cannot afford this item."
+            Kind::InsufficientFunds => &[
+                r"^(?:\[[^\]]*\]\s*)?you (?:do not|don't) have enough money\b",
+                r"^(?:\[[^\]]*\]\s*)?you (?:cannot|can't|can not) afford\b",
+                r"^(?:\[[^\]]*\]\s*)?(?:not enough money|insufficient (?:funds|balance))\b",
+            ],
+            // "You have reached the maximum number of listings (8)", "You can only have 5
+            // active listings!", "Listing limit reached (8/8)", "You have reached your auction limit"
+            Kind::ListingCapReached => &[
+                r"^you have reached (?:the maximum number of (?:listings|auctions)|your (?:listing|auction) limit)\b",
+                r"^you can only have \d+ (?:active )?(?:listings|auctions)\b",
+                r"^(?:listing|auction) limit reached\b",
+            ],
+            // "You have been teleported to AFK area #3"
+            Kind::AfkTeleport => &[r"^(?:\[[^\]]*\]\s*)?you (?:have been|were) teleported to\b.*\bafk\b"],
+            // "Your balance is $12.5K", "Balance: $1,234", "You have $3.2M"
+            Kind::Balance => &[r"^(?:(?:your )?balance(?: is)?\s*:?|you have)\s*(?P<amount>\$\s*[\d,.]+\s*[kmbt]?)\s*[.!]?$"],
+            // "You have 1,250 shards", "Shards: 1.2K", "Your shards: 1.2K"
+            Kind::ShardBalance => &[
+                r"^you have (?P<amount>\d[\d,.]*\s*[kmbt]?)\s*shards?\b",
+                r"^(?:your )?shards?\s*:\s*(?P<amount>\d[\d,.]*\s*[kmbt]?)\b",
+            ],
+        }
+    }
+
+    /// Capture groups the handler reads from a match
+    fn required_groups(self) -> &'static [&'static str] {
+        match self {
+            Kind::Sale => &["buyer", "price"],
+            Kind::OfflineSales => &["total"],
+            Kind::Balance => &["amount"],
+            _ => &[],
+        }
+    }
+
+    fn configured(self, patterns: &ChatPatterns) -> &str {
+        match self {
+            Kind::Sale => &patterns.sale,
+            Kind::OfflineSales => &patterns.offline_sales,
+            Kind::AlreadyBought => &patterns.already_bought,
+            Kind::InsufficientFunds => &patterns.insufficient_funds,
+            Kind::ListingCapReached => &patterns.listing_cap,
+            Kind::AfkTeleport => &patterns.afk_teleport,
+            Kind::Balance => &patterns.balance,
+            Kind::ShardBalance => &patterns.shard_balance,
+        }
+    }
+}
+
+/// Compile the patterns for `kind`, the configured one if set; errors name the problem
+fn compile(kind: Kind, patterns: &ChatPatterns) -> Result<Vec<Regex>, String> {
+    let configured = kind.configured(patterns).trim();
+    let sources = if configured.is_empty() { kind.built_in() } else { &[configured][..] };
+    sources.iter().map(|pattern| compile_one(kind, pattern)).collect()
+}
+
+fn compile_one(kind: Kind, pattern: &str) -> Result<Regex, String> {
+    let regex = RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| format!("invalid pattern: {}", e))?;
+    let missing: Vec<&str> = kind.required_groups().iter()
+        .filter(|group| !regex.capture_names().flatten().any(|name| name == **group))
+        .copied()
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("pattern needs the named group(s) {}", missing.iter()
+            .map(|group| format!("(?P<{}>...)", group))
+            .collect::<Vec<_>>()
+            .join(", ")));
+    }
+    Ok(regex)
+}
+
+/// Problems with the `chatPatterns` overrides, as (key, message)
+pub fn pattern_problems(patterns: &ChatPatterns) -> Vec<(&'static str, String)> {
+    KINDS.iter()
+        .filter_map(|&kind| compile(kind, patterns).err().map(|problem| (kind.key(), problem)))
+        .collect()
+}
+
+/// Turns chat lines into `ServerMessage`s
+///
+/// Patterns are compiled once, from `chatPatterns` where set and the built-in ones
+/// otherwise, and matched case-insensitively against the line with colour codes removed
+/// and small capitals folded. The first kind that matches wins. Lines typed by players
+/// are never classified, whatever they say.
+pub struct ChatClassifier {
+    patterns: Vec<(Kind, Regex)>,
+}
+
+impl ChatClassifier {
+    pub fn new(patterns: &ChatPatterns) -> Result<Self> {
+        let mut compiled = Vec::new();
+        for kind in KINDS {
+            let regexes = compile(kind, patterns)
+                .map_err(|problem| anyhow!("chatPatterns.{}: {}", kind.key(), problem))?;
+            compiled.extend(regexes.into_iter().map(|regex| (kind, regex)));
+        }
+        Ok(Self { patterns: compiled })
+    }
+
+    pub fn classify(&self, message: &str) -> ServerMessage {
+        // Case is kept: buyer names are reported as sent
+        let text = normalize(message);
+        if is_player_chat(&text) {
+            return ServerMessage::Unknown;
+        }
+        for (kind, regex) in &self.patterns {
+            let Some(caps) = regex.captures(&text) else {
+                continue;
+            };
+            let group = |name: &str| caps.name(name).map_or("", |m| m.as_str().trim());
+            let parsed = match kind {
+                Kind::Sale => parse_price(group("price")).map(|price| {
+                    let (item, quantity) = split_quantity(group("item"));
+                    ServerMessage::Sale(Sale {
+                        buyer: buyer_name(group("buyer")),
+                        item,
+                        quantity: group("quantity").parse().unwrap_or(quantity),
+                        price,
+                    })
+                }),
+                Kind::OfflineSales => parse_price(group("total")).map(|total| ServerMessage::OfflineSales {
+                    count: group("count").parse().ok().or_else(|| sale_count_in(&text)),
+                    total,
+                }),
+                Kind::AlreadyBought => Some(ServerMessage::AlreadyBought),
+                Kind::InsufficientFunds => Some(ServerMessage::InsufficientFunds),
+                Kind::ListingCapReached => Some(ServerMessage::ListingCapReached {
+                    cap: group("cap").parse().ok().or_else(|| cap_in(&text)),
+                }),
+                Kind::AfkTeleport => Some(ServerMessage::AfkTeleport),
+                Kind::Balance => parse_price(group("amount")).map(|amount| ServerMessage::Balance { amount }),
+                Kind::ShardBalance => Some(ServerMessage::ShardBalance { shards: parse_count(group("amount")) }),
+            };
+            if let Some(parsed) = parsed {
+                return parsed;
+            }
+        }
+        ServerMessage::Unknown
+    }
+}
+
+impl Default for ChatClassifier {
+    fn default() -> Self {
+        Self::new(&ChatPatterns::default()).expect("built-in chat patterns compile")
+    }
+}
+
+// Words the server itself puts before a colon, e.g. "Balance: $1,234" or "Shards: 1,250"
+const SERVER_LABELS: &[&str] = &["balance", "money", "shards"];
+
+/// Whether a line was typed by a player: "<Steve> hi", "Steve » hi" or "Steve: hi", with
+/// or without a rank tag like "[VIP] "
+fn is_player_chat(text: &str) -> bool {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(r"^(?:<[^<>]+>|(?:\[[^\]]+\]\s*)*(?P<name>\w{3,16})\s*(?P<separator>»|>>|:))\s").unwrap()
+    });
+    let Some(caps) = re.captures(text.trim_start()) else {
+        return false;
+    };
+    match (caps.name("name"), caps.name("separator")) {
+        (Some(name), Some(separator)) if separator.as_str() == ":" => {
+            !SERVER_LABELS.iter().any(|label| name.as_str().eq_ignore_ascii_case(label))
+        }
+        _ => true,
+    }
+}
+
+/// A count written the way the server writes money: "1,250", "1.2K"
+fn parse_count(text: &str) -> Option<u64> {
+    text.parse::<Money>().ok().map(Money::dollars)
+}
+
+/// Item name and stack size from "Map", "Map x3", "3x Map", "Map (x3)" or "16 Diamond";
+/// a pattern without an item group sold a single map
+fn split_quantity(item: &str) -> (String, u32) {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(r"^(?:(?P<before>\d+)\s*[x×]?\s+)?(?P<name>.+?)(?:\s*\(?\s*[x×]\s*(?P<after>\d+)\s*\)?)?$").unwrap()
+    });
+    let Some(caps) = re.captures(item.trim()) else {
+        return ("Map".to_string(), 1);
+    };
+    let quantity = caps.name("before").or(caps.name("after"))
+        .and_then(|m| m.as_str().parse().ok())
+        .unwrap_or(1);
+    (caps["name"].to_string(), quantity)
+}
+
+/// The buyer without whatever precedes them on the line ("[AH] ", "While you were
+/// offline, "); names themselves may contain spaces
+fn buyer_name(raw: &str) -> String {
+    raw.rsplit([',', ':', ']', '»', '>']).next().unwrap_or(raw).trim().to_string()
+}
+
+/// How many sales an offline summary covers, e.g. the 3 in "you sold 3 items"
+fn sale_count_in(text: &str) -> Option<u32> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(r"(?i)(\d+)\s+(?:of your\s+)?(?:items?|auctions?|sales?|listings?|maps?)\b").unwrap()
+    });
+    re.captures(text).and_then(|caps| caps[1].parse().ok())
+}
+
+/// The listing cap named in a message: the first number, or the second of "8/8"
+fn cap_in(text: &str) -> Option<u32> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| Regex::new(r"(?:\d+\s*/\s*)?(\d+)").unwrap());
+    re.captures(text).and_then(|caps| caps[1].parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(message: &str) -> ServerMessage {
+        ChatClassifier::default().classify(message)
+    }
+
+    /// Name of a variant as written in the corpus
+    fn kind_name(message: &ServerMessage) -> &'static str {
+        match message {
+            ServerMessage::Sale(_) => "sale",
+            ServerMessage::OfflineSales { .. } => "offlineSales",
+            ServerMessage::AlreadyBought => "alreadyBought",
+            ServerMessage::InsufficientFunds => "insufficientFunds",
+            ServerMessage::AfkTeleport => "afkTeleport",
+            ServerMessage::ShardBalance { .. } => "shardBalance",
+            ServerMessage::Balance { .. } => "balance",
+            ServerMessage::ListingCapReached { .. } => "listingCap",
+            ServerMessage::Unknown => "unknown",
+        }
+    }
+
+    #[test]
+    fn test_corpus() {
+        let corpus = include_str!("../fixtures/chat/donutsmp.txt");
+        let mut checked = 0;
+        for line in corpus.lines().filter(|line| !line.trim().is_empty() && !line.starts_with('#')) {
+            let (expected, message) = line.split_once(" | ").expect("corpus lines are \"kind | message\"");
+            assert_eq!(kind_name(&classify(message)), expected.trim(), "{}", message);
+            checked += 1;
+        }
+        assert!(checked >= 20);
+    }
+
+    fn sale(buyer: &str, item: &str, quantity: u32, price: u64) -> ServerMessage {
+        ServerMessage::Sale(Sale {
+            buyer: buyer.to_string(),
+            item: item.to_string(),
+            quantity,
+            price: Money::new(price),
+        })
+    }
+
+    #[test]
+    fn test_sale_fields() {
+        assert_eq!(classify("Test User bought your Map for $9.9K"), sale("Test User", "Map", 1, 9900));
+        assert_eq!(classify("§aSomeGuy123 §7bought your Map for §a$10,000"), sale("SomeGuy123", "Map", 1, 10000));
+        assert_eq!(classify("steve bought your Map for $1.2M"), sale("steve", "Map", 1, 1_200_000));
+    }
+
+    #[test]
+    fn test_sale_formats() {
+        assert_eq!(classify("Steve bought your Map x3 for $29.7K"), sale("Steve", "Map", 3, 29700));
+        assert_eq!(classify("Steve bought your 3x Map for $29.7K"), sale("Steve", "Map", 3, 29700));
+        assert_eq!(classify("Steve bought your Map (x3) for $29.7K"), sale("Steve", "Map", 3, 29700));
+        assert_eq!(classify("Steve purchased your Diamond Sword for $1.5M"), sale("Steve", "Diamond Sword", 1, 1_500_000));
+        assert_eq!(classify("Steve bought 16 Diamond from you for $40K"), sale("Steve", "Diamond", 16, 40000));
+        assert_eq!(classify("You sold 2x Map to Alex for $19.8K"), sale("Alex", "Map", 2, 19800));
+        assert_eq!(classify("[AH] Steve bought your Map for $9.9K"), sale("Steve", "Map", 1, 9900));
+        assert_eq!(classify("While you were offline, Steve bought your Map for $9.9K"), sale("Steve", "Map", 1, 9900));
+    }
+
+    #[test]
+    fn test_offline_summaries() {
+        let offline = |count, total| ServerMessage::OfflineSales { count, total: Money::new(total) };
+        assert_eq!(classify("While you were offline, you sold 3 items for $29.7K"), offline(Some(3), 29700));
+        assert_eq!(classify("You earned $29.7K from 3 auctions while you were offline"), offline(Some(3), 29700));
+        assert_eq!(classify("While you were away, 1 of your listings sold for $9,900"), offline(Some(1), 9900));
+        assert_eq!(classify("You made $1.2M while you were offline!"), offline(None, 1_200_000));
+        // A welcome back without money isn't a summary
+        assert_eq!(classify("While you were offline, the server restarted"), ServerMessage::Unknown);
+    }
+
+    #[test]
+    fn test_map_sales() {
+        let is_map = |message| match classify(message) {
+            ServerMessage::Sale(sale) => sale.is_map(),
+            other => panic!("not a sale: {:?}", other),
+        };
+        assert!(is_map("Steve bought your Map for $9.9K"));
+        assert!(is_map("Steve bought your Filled Map x2 for $9.9K"));
+        assert!(!is_map("Steve bought your Diamond Sword for $9.9K"));
+        assert!(!is_map("Steve bought your Mapleleaf Banner for $9.9K"));
+    }
+
+    #[test]
+    fn test_balance_amounts() {
+        assert_eq!(classify("your balance is $12.5k"), ServerMessage::Balance { amount: Money::new(12500) });
+        assert_eq!(classify("balance: $1,234"), ServerMessage::Balance { amount: Money::new(1234) });
+        assert_eq!(classify("you have $3.2m"), ServerMessage::Balance { amount: Money::new(3_200_000) });
+        assert_eq!(classify("ʏᴏᴜʀ ʙᴀʟᴀɴᴄᴇ: $12.5ᴋ"), ServerMessage::Balance { amount: Money::new(12500) });
+        assert_eq!(classify("steve bought your map for $9.9k"), sale("steve", "map", 1, 9900));
+        assert_eq!(classify("you have 12 shards"), ServerMessage::ShardBalance { shards: Some(12) });
+        assert_eq!(classify("welcome back!"), ServerMessage::Unknown);
+    }
+
+    #[test]
+    fn test_shard_amounts() {
+        let shards = |shards| ServerMessage::ShardBalance { shards };
+        assert_eq!(classify("You have 1250 shards"), shards(Some(1250)));
+        assert_eq!(classify("§dShards: §f1,250"), shards(Some(1250)));
+        assert_eq!(classify("ʏᴏᴜ ʜᴀᴠᴇ 1,250 ꜱʜᴀʀᴅꜱ"), shards(Some(1250)));
+        assert_eq!(classify("Your shards: 1.2K"), shards(Some(1200)));
+        assert_eq!(classify("You have １２ shards."), shards(Some(12)));
+        // Other lines about shards aren't replies
+        assert_eq!(classify("Shards can be spent at /shop"), ServerMessage::Unknown);
+        assert_eq!(classify("Buy 500 shards at store.donutsmp.net!"), ServerMessage::Unknown);
+    }
+
+    #[test]
+    fn test_listing_caps() {
+        let cap = |cap| ServerMessage::ListingCapReached { cap };
+        assert_eq!(classify("you have reached the maximum number of listings (8)"), cap(Some(8)));
+        assert_eq!(classify("you can only have 5 active listings!"), cap(Some(5)));
+        assert_eq!(classify("listing limit reached (8/8)"), cap(Some(8)));
+        assert_eq!(classify("you have reached your auction limit"), cap(None));
+        assert_ne!(kind_name(&classify("steve bought your map for $9.9k")), "listingCap");
+        assert_eq!(classify("you listed a map for $9.9k"), ServerMessage::Unknown);
+        assert_eq!(classify("Limited-time auction event!"), ServerMessage::Unknown);
+        assert_eq!(classify("The auction house has no limit on map prices"), ServerMessage::Unknown);
+    }
+
+    #[test]
+    fn test_player_chat_is_ignored() {
+        assert_eq!(classify("<Steve> Steve bought your Map for $9.9M"), ServerMessage::Unknown);
+        assert_eq!(classify("[VIP] Steve: your balance is $1B"), ServerMessage::Unknown);
+        assert_eq!(classify("Steve » While you were offline, you sold 3 items for $1M"), ServerMessage::Unknown);
+        assert_eq!(classify("<Steve> what's the max auction price?"), ServerMessage::Unknown);
+        assert_eq!(classify("Steve: this item has already been bought"), ServerMessage::Unknown);
+        assert_eq!(classify("Steve: you can't afford it lol"), ServerMessage::Unknown);
+        assert_eq!(classify("Steve: you have been teleported to afk"), ServerMessage::Unknown);
+        // A refusal or teleport has to be the whole line, not something quoted in it
+        assert_eq!(classify("I heard this item has already been bought"), ServerMessage::Unknown);
+        assert_eq!(classify("Nobody was teleported to the afk area today"), ServerMessage::Unknown);
+        // Server lines with a label or tag aren't chat
+        assert_eq!(classify("Balance: $1,234"), ServerMessage::Balance { amount: Money::new(1234) });
+        assert_eq!(classify("[AH] Steve bought your Map for $9.9K"), sale("Steve", "Map", 1, 9900));
+    }
+
+    #[test]
+    fn test_configured_patterns_replace_built_in_ones() {
+        let patterns = ChatPatterns {
+            sale: r"^\[AH\] (?P<buyer>\w+) purchased your item for (?P<price>\S+)".to_string(),
+            afk_teleport: r"welcome to the afk zone".to_string(),
+            ..ChatPatterns::default()
+        };
+        let chat = ChatClassifier::new(&patterns).unwrap();
+        // Without an item group, a sale is of one map
+        assert_eq!(chat.classify("[AH] Steve purchased your item for $9,900"), sale("Steve", "Map", 1, 9900));
+        assert_eq!(chat.classify("Steve bought your Map for $9.9K"), ServerMessage::Unknown);
+        assert_eq!(chat.classify("Welcome to the AFK zone!"), ServerMessage::AfkTeleport);
+        // Patterns not overridden keep working
+        assert_eq!(chat.classify("This item has already been bought!"), ServerMessage::AlreadyBought);
+    }
+
+    #[test]
+    fn test_bad_patterns_are_reported() {
+        let patterns = ChatPatterns {
+            sale: r"(?P<buyer>\w+) bought".to_string(),
+            afk_teleport: "(".to_string(),
+            ..ChatPatterns::default()
+        };
+        let problems = pattern_problems(&patterns);
+        let keys: Vec<&str> = problems.iter().map(|(key, _)| *key).collect();
+        assert_eq!(keys, vec!["sale", "afkTeleport"]);
+        assert!(problems[0].1.contains("(?P<price>...)"), "{}", problems[0].1);
+        assert!(ChatClassifier::new(&patterns).is_err());
+        assert!(pattern_problems(&ChatPatterns::default()).is_empty());
+    }
+}
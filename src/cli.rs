@@ -0,0 +1,164 @@
+use anyhow::{anyhow, Result};
+
+pub const USAGE: &str = "\
+Usage: donutsmp-mapflipper [OPTIONS] [COMMAND]
+
+Commands:
+  (none)                   Connect and start flipping
+  validate-config [PATH]   Check a config file and exit (status 1 on errors)
+  print-effective-config   Show every setting and which layer it came from
+  init-config [PATH]       Write a commented TOML config (default: config.toml)
+
+Options:
+  --config PATH            Config file to use (.json, .toml, .yaml or .yml);
+                           default: the first of config.json, config.toml,
+                           config.yaml, config.yml that exists
+  --set KEY=VALUE          Override a config value, e.g. --set maxBuyPrice=3000
+                           or --set webhook.enabled=true (repeatable)
+  --force                  Let init-config overwrite an existing file
+  -h, --help               Show this help";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Run,
+    ValidateConfig(Option<String>),
+    PrintEffectiveConfig,
+    InitConfig(Option<String>),
+    Help,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cli {
+    pub command: Command,
+    /// `--config` path, if given
+    pub config_path: Option<String>,
+    pub force: bool,
+    /// `--set` overrides as `(key, value)`, in the order given
+    pub overrides: Vec<(String, String)>,
+}
+
+/// Parse command-line arguments (without the program name)
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Cli> {
+    let mut command = None;
+    let mut positional = Vec::new();
+    let mut overrides = Vec::new();
+    let mut config_path = None;
+    let mut force = false;
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => command = Some(Command::Help),
+            "--force" => force = true,
+            "--config" => {
+                config_path = Some(args.next().ok_or_else(|| anyhow!("--config needs a PATH argument"))?);
+            }
+            _ if arg.starts_with("--config=") => config_path = Some(arg["--config=".len()..].to_string()),
+            "--set" => {
+                let pair = args.next().ok_or_else(|| anyhow!("--set needs a KEY=VALUE argument"))?;
+                overrides.push(parse_override(&pair)?);
+            }
+            _ if arg.starts_with("--set=") => overrides.push(parse_override(&arg["--set=".len()..])?),
+            _ if arg.starts_with('-') => return Err(anyhow!("Unknown option '{}'\n\n{}", arg, USAGE)),
+            _ => positional.push(arg),
+        }
+    }
+
+    if command.is_none() {
+        let mut positional = positional.into_iter();
+        command = Some(match positional.next().as_deref() {
+            None => Command::Run,
+            Some("validate-config") => Command::ValidateConfig(positional.next()),
+            Some("print-effective-config") => Command::PrintEffectiveConfig,
+            Some("init-config") => Command::InitConfig(positional.next()),
+            Some(other) => return Err(anyhow!("Unknown command '{}'\n\n{}", other, USAGE)),
+        });
+    }
+
+    Ok(Cli {
+        command: command.unwrap_or(Command::Run),
+        config_path,
+        force,
+        overrides,
+    })
+}
+
+fn parse_override(pair: &str) -> Result<(String, String)> {
+    let (key, value) = pair.split_once('=')
+        .ok_or_else(|| anyhow!("--set expects KEY=VALUE, got '{}'", pair))?;
+    Ok((key.trim().to_string(), value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &[&str]) -> Result<Cli> {
+        parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn pair(key: &str, value: &str) -> (String, String) {
+        (key.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn test_set_overrides() {
+        let cli = parse_args(&["--set", "maxBuyPrice=3000", "--set=webhook.events.sale=false"]).unwrap();
+        assert_eq!(cli.command, Command::Run);
+        assert_eq!(cli.overrides, vec![pair("maxBuyPrice", "3000"), pair("webhook.events.sale", "false")]);
+
+        // Only the first '=' splits, and values may be empty
+        let cli = parse_args(&["--set", "webhook.url=https://example.com/?a=b", "--set", "recordSession="]).unwrap();
+        assert_eq!(cli.overrides, vec![pair("webhook.url", "https://example.com/?a=b"), pair("recordSession", "")]);
+    }
+
+    #[test]
+    fn test_repeated_set_keeps_every_value_in_order() {
+        let cli = parse_args(&["--set", "maxBuyPrice=3000", "--set", "maxBuyPrice=2000"]).unwrap();
+        assert_eq!(cli.overrides, vec![pair("maxBuyPrice", "3000"), pair("maxBuyPrice", "2000")]);
+    }
+
+    #[test]
+    fn test_set_errors() {
+        let error = parse_args(&["--set", "maxBuyPrice"]).unwrap_err().to_string();
+        assert_eq!(error, "--set expects KEY=VALUE, got 'maxBuyPrice'");
+        let error = parse_args(&["--set"]).unwrap_err().to_string();
+        assert_eq!(error, "--set needs a KEY=VALUE argument");
+        let error = parse_args(&["--sett", "maxBuyPrice=1"]).unwrap_err().to_string();
+        assert!(error.starts_with("Unknown option '--sett'"), "{}", error);
+    }
+
+    #[test]
+    fn test_print_effective_config() {
+        let cli = parse_args(&["print-effective-config", "--set", "port=25566"]).unwrap();
+        assert_eq!(cli.command, Command::PrintEffectiveConfig);
+        assert_eq!(cli.overrides, vec![pair("port", "25566")]);
+
+        let error = parse_args(&["print-effective-configs"]).unwrap_err().to_string();
+        assert!(error.starts_with("Unknown command 'print-effective-configs'"), "{}", error);
+        assert!(error.contains(USAGE));
+    }
+
+    #[test]
+    fn test_config_path() {
+        let cli = parse_args(&["--config", "bot.toml"]).unwrap();
+        assert_eq!(cli.config_path.as_deref(), Some("bot.toml"));
+        let cli = parse_args(&["validate-config", "--config=bot.yaml"]).unwrap();
+        assert_eq!(cli.config_path.as_deref(), Some("bot.yaml"));
+        assert_eq!(cli.command, Command::ValidateConfig(None));
+
+        let error = parse_args(&["--config"]).unwrap_err().to_string();
+        assert_eq!(error, "--config needs a PATH argument");
+    }
+
+    #[test]
+    fn test_init_config() {
+        let cli = parse_args(&["init-config"]).unwrap();
+        assert_eq!((cli.command, cli.force), (Command::InitConfig(None), false));
+        let cli = parse_args(&["init-config", "bot.toml", "--force"]).unwrap();
+        assert_eq!((cli.command, cli.force), (Command::InitConfig(Some("bot.toml".to_string())), true));
+
+        let error = parse_args(&["init-config", "--forse"]).unwrap_err().to_string();
+        assert!(error.starts_with("Unknown option '--forse'"), "{}", error);
+    }
+}
@@ -1,9 +1,14 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
-use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Context, Result};
+use crate::price_parser::Money;
+
+// All config keys are camelCase in every file format (`maxBuyPrice`, `displayName`).
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct WebhookEvents {
     #[serde(default = "default_true")]
     pub purchase: bool,
@@ -17,6 +22,12 @@ pub struct WebhookEvents {
     pub error: bool,
     #[serde(default = "default_true")]
     pub startup: bool,
+    #[serde(default = "default_true")]
+    pub digest: bool,
+    #[serde(default = "default_true")]
+    pub config: bool,
+    #[serde(default = "default_true")]
+    pub limits: bool,
 }
 
 impl Default for WebhookEvents {
@@ -28,17 +39,21 @@ impl Default for WebhookEvents {
             afk: true,
             error: true,
             startup: true,
+            digest: true,
+            config: true,
+            limits: true,
         }
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct WebhookConfig {
     #[serde(default)]
     pub enabled: bool,
     #[serde(default)]
     pub url: String,
-    #[serde(default = "default_display_name")]
+    #[serde(default = "default_display_name", alias = "display_name")]
     pub display_name: String,
     #[serde(default)]
     pub events: WebhookEvents,
@@ -55,7 +70,127 @@ impl Default for WebhookConfig {
     }
 }
 
+/// Which digests a single sink receives
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DigestSchedule {
+    #[serde(default = "default_true")]
+    pub hourly: bool,
+    #[serde(default = "default_true")]
+    pub daily: bool,
+}
+
+impl Default for DigestSchedule {
+    fn default() -> Self {
+        Self {
+            hourly: true,
+            daily: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DigestConfig {
+    #[serde(default = "default_digest_state_file")]
+    pub state_file: String,
+    #[serde(default)]
+    pub webhook: DigestSchedule,
+    #[serde(default)]
+    pub console: DigestSchedule,
+}
+
+impl Default for DigestConfig {
+    fn default() -> Self {
+        Self {
+            state_file: default_digest_state_file(),
+            webhook: DigestSchedule::default(),
+            console: DigestSchedule::default(),
+        }
+    }
+}
+
+/// Slots clicked when a GUI button can't be recognised by its item and name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GuiConfig {
+    /// Confirm button on purchase, listing and cancel confirmation screens
+    #[serde(default = "default_confirm_slot")]
+    pub confirm_slot: usize,
+    /// "Random AFK location" button in the `/afk` menu
+    #[serde(default = "default_afk_slot")]
+    pub afk_slot: usize,
+}
+
+impl Default for GuiConfig {
+    fn default() -> Self {
+        Self {
+            confirm_slot: default_confirm_slot(),
+            afk_slot: default_afk_slot(),
+        }
+    }
+}
+
+/// Regexes replacing the built-in chat patterns (see `chat.rs`); empty keeps the built-in one
+///
+/// Matched case-insensitively against the line with colour codes removed and small
+/// capitals folded.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatPatterns {
+    /// Needs `(?P<buyer>...)` and `(?P<price>...)` groups; may name the item with
+    /// `(?P<item>...)` ("Map x3") and the amount with `(?P<quantity>...)`
+    #[serde(default)]
+    pub sale: String,
+    /// The batch notice on login; needs a `(?P<total>...)` group, may have `(?P<count>...)`
+    #[serde(default)]
+    pub offline_sales: String,
+    #[serde(default)]
+    pub already_bought: String,
+    #[serde(default)]
+    pub insufficient_funds: String,
+    /// May name the cap with a `(?P<cap>...)` group
+    #[serde(default)]
+    pub listing_cap: String,
+    #[serde(default)]
+    pub afk_teleport: String,
+    /// Needs an `(?P<amount>...)` group
+    #[serde(default)]
+    pub balance: String,
+    #[serde(default)]
+    pub shard_balance: String,
+}
+
+/// What to do when the next purchase would not fit in the inventory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FullInventoryPolicy {
+    /// Stop buying until a slot frees up
+    Skip,
+    /// List the maps we're holding, then buy
+    #[default]
+    List,
+    /// Move the maps we're holding to the ender chest, then buy
+    Store,
+}
+
+/// What to do with items that are neither maps nor on the keep-list
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum JunkAction {
+    /// Leave them alone
+    #[default]
+    Off,
+    /// Throw them on the ground
+    Drop,
+    /// Sell them on the auction house at `junkPrice`
+    List,
+    /// Move them to the ender chest
+    Store,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Config {
     #[serde(default = "default_host")]
     pub host: String,
@@ -67,127 +202,282 @@ pub struct Config {
     pub auth: String,
     #[serde(default = "default_version")]
     pub version: String,
-    #[serde(default = "default_max_buy_price", rename = "maxBuyPrice")]
-    pub max_buy_price: u32,
-    #[serde(default = "default_sell_price", rename = "sellPrice")]
-    pub sell_price: String,
-    #[serde(default = "default_max_listings_per_cycle", rename = "maxListingsPerCycle")]
+    #[serde(default = "default_max_buy_price")]
+    pub max_buy_price: Money,
+    #[serde(default = "default_sell_price")]
+    pub sell_price: Money,
+    /// Round listing prices up to a one-decimal `k`/`m` form instead of sending the exact amount
+    #[serde(default)]
+    pub compact_listing_prices: bool,
+    /// Skip purchases that would leave less than this in the bank
+    #[serde(default)]
+    pub min_balance_reserve: Money,
+    /// How often to refresh the balance with `/bal`, in ms; 0 disables the check
+    #[serde(default = "default_balance_check_interval")]
+    pub balance_check_interval: u64,
+    // Spending limits and circuit breaker; 0 disables a limit
+    #[serde(default)]
+    pub max_spend_per_hour: Money,
+    #[serde(default)]
+    pub max_spend_per_day: Money,
+    #[serde(default)]
+    pub max_purchases_per_hour: u32,
+    /// Cap on the total cost of maps bought but not yet sold
+    #[serde(default)]
+    pub max_unsold_value: Money,
+    #[serde(default = "default_max_consecutive_failures")]
+    pub max_consecutive_failures: u32,
+    /// Pause when realised profit over this many recent trades is negative
+    #[serde(default = "default_profit_check_trades")]
+    pub profit_check_trades: u32,
+    /// How long a tripped limit pauses buying, in ms; 0 waits for a manual resume
+    #[serde(default = "default_limit_cooldown")]
+    pub limit_cooldown: u64,
+    /// Creating this file resumes buying after a limit trips
+    #[serde(default = "default_resume_file")]
+    pub resume_file: String,
+    /// Where purchase history and the breaker state are kept between restarts
+    #[serde(default = "default_limits_state_file")]
+    pub limits_state_file: String,
+    #[serde(default = "default_max_listings_per_cycle")]
     pub max_listings_per_cycle: u32,
-    #[serde(default = "default_delay_between_cycles", rename = "delayBetweenCycles")]
+    /// The server's active listing cap, if known in advance (0 = unknown)
+    #[serde(default)]
+    pub listing_cap: u32,
+    /// Command that opens the server's view of our own listings
+    #[serde(default = "default_listings_command")]
+    pub listings_command: String,
+    /// How often to read our listings back from that view, in ms; 0 disables it
+    #[serde(default = "default_listings_check_interval")]
+    pub listings_check_interval: u64,
+    /// Command that opens the expired/collect view of the auction house
+    #[serde(default = "default_expired_command")]
+    pub expired_command: String,
+    /// How often to reclaim and relist expired maps, in ms; 0 disables it
+    #[serde(default = "default_reclaim_interval")]
+    pub reclaim_interval: u64,
+    /// Discount applied per repeated expiry when relisting (0 = always full price)
+    #[serde(default)]
+    pub relist_discount_percent: u32,
+    /// Number of expiries before the relist discount starts
+    #[serde(default = "default_relist_discount_after")]
+    pub relist_discount_after: u32,
+    /// How often to look for listings worth repricing, in ms; 0 disables repricing
+    #[serde(default)]
+    pub reprice_interval: u64,
+    /// Listings older than this (ms) are relisted; 0 only reprices on price
+    #[serde(default = "default_reprice_after")]
+    pub reprice_after: u64,
+    /// Reprice listings more than this percent above the market floor
+    #[serde(default = "default_reprice_margin_percent")]
+    pub reprice_margin_percent: u32,
+    /// What to do when the inventory has no free slot for the next purchase
+    #[serde(default)]
+    pub full_inventory_policy: FullInventoryPolicy,
+    /// Command that opens our ender chest
+    #[serde(default = "default_ender_chest_command")]
+    pub ender_chest_command: String,
+    /// What to do with items that are neither maps nor in `keepItems`
+    #[serde(default)]
+    pub junk_action: JunkAction,
+    /// Item ids never treated as junk, e.g. "minecraft:golden_apple"; maps are always kept
+    #[serde(default)]
+    pub keep_items: Vec<String>,
+    /// Price per stack when junk is listed on the auction house
+    #[serde(default = "default_junk_price")]
+    pub junk_price: Money,
+    #[serde(default = "default_delay_between_cycles")]
     pub delay_between_cycles: u64,
-    #[serde(default = "default_delay_after_join", rename = "delayAfterJoin")]
+    #[serde(default = "default_delay_after_join")]
     pub delay_after_join: u64,
-    #[serde(default = "default_window_timeout", rename = "windowTimeout")]
+    #[serde(default = "default_window_timeout")]
     pub window_timeout: u64,
-    #[serde(default = "default_delay_between_listings", rename = "delayBetweenListings")]
+    #[serde(default = "default_delay_between_listings")]
     pub delay_between_listings: u64,
-    #[serde(default, rename = "debugEvents")]
+    #[serde(default)]
     pub debug_events: bool,
-    #[serde(default = "default_true", rename = "enableAfkFarming")]
+    /// File to record commands, clicks, windows and chat to, for replay tests; empty disables it
+    #[serde(default)]
+    pub record_session: String,
+    #[serde(default = "default_true")]
     pub enable_afk_farming: bool,
+    /// How often to read the shard balance with `/shards`, in ms; 0 disables the check
+    #[serde(default = "default_shards_check_interval")]
+    pub shards_check_interval: u64,
+    /// Alert when AFK farming earns no shards for this long, in ms; 0 disables the alert
+    #[serde(default = "default_shard_stall_after")]
+    pub shard_stall_after: u64,
     #[serde(default)]
     pub webhook: WebhookConfig,
+    #[serde(default)]
+    pub digest: DigestConfig,
+    #[serde(default)]
+    pub gui: GuiConfig,
+    #[serde(default)]
+    pub chat_patterns: ChatPatterns,
 }
 
-impl Config {
-    pub fn load() -> Result<Self> {
-        let config_path = Path::new("config.json");
-        
-        if config_path.exists() {
-            let config_data = fs::read_to_string(config_path)
-                .context("Failed to read config.json")?;
-            let mut config: Config = serde_json::from_str(&config_data)
-                .context("Failed to parse config.json")?;
-            
-            // Override with environment variables if set
-            if let Ok(username) = std::env::var("BOT_USERNAME") {
-                config.username = username;
-            }
-            if let Ok(auth) = std::env::var("BOT_AUTH") {
-                config.auth = auth;
-            }
-            if let Ok(max_buy) = std::env::var("MAX_BUY_PRICE") {
-                if let Ok(price) = max_buy.parse() {
-                    config.max_buy_price = price;
-                }
-            }
-            if let Ok(sell) = std::env::var("SELL_PRICE") {
-                config.sell_price = sell;
-            }
-            if let Ok(max_listings) = std::env::var("MAX_LISTINGS_PER_CYCLE") {
-                if let Ok(limit) = max_listings.parse() {
-                    config.max_listings_per_cycle = limit;
-                }
-            }
-            if let Ok(delay) = std::env::var("DELAY_BETWEEN_CYCLES") {
-                if let Ok(d) = delay.parse() {
-                    config.delay_between_cycles = d;
-                }
-            }
-            if let Ok(delay) = std::env::var("DELAY_AFTER_JOIN") {
-                if let Ok(d) = delay.parse() {
-                    config.delay_after_join = d;
+/// Default config file, relative to the working directory
+pub const CONFIG_PATH: &str = "config.json";
+
+/// Files tried, in order, when no `--config` path is given
+const DEFAULT_CONFIG_PATHS: &[&str] = &["config.json", "config.toml", "config.yaml", "config.yml"];
+
+/// Old key names that are still accepted, mapped to their current path
+pub const KEY_ALIASES: &[(&str, &str)] = &[("$.webhook.display_name", "$.webhook.displayName")];
+
+/// Fields whose values must never appear in logs or notifications
+pub const SECRET_FIELDS: &[&str] = &["$.webhook.url"];
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            host: default_host(),
+            port: default_port(),
+            username: default_username(),
+            auth: default_auth(),
+            version: default_version(),
+            max_buy_price: default_max_buy_price(),
+            sell_price: default_sell_price(),
+            compact_listing_prices: false,
+            min_balance_reserve: Money::ZERO,
+            balance_check_interval: default_balance_check_interval(),
+            max_spend_per_hour: Money::ZERO,
+            max_spend_per_day: Money::ZERO,
+            max_purchases_per_hour: 0,
+            max_unsold_value: Money::ZERO,
+            max_consecutive_failures: default_max_consecutive_failures(),
+            profit_check_trades: default_profit_check_trades(),
+            limit_cooldown: default_limit_cooldown(),
+            resume_file: default_resume_file(),
+            limits_state_file: default_limits_state_file(),
+            max_listings_per_cycle: default_max_listings_per_cycle(),
+            listing_cap: 0,
+            listings_command: default_listings_command(),
+            listings_check_interval: default_listings_check_interval(),
+            expired_command: default_expired_command(),
+            reclaim_interval: default_reclaim_interval(),
+            relist_discount_percent: 0,
+            relist_discount_after: default_relist_discount_after(),
+            reprice_interval: 0,
+            reprice_after: default_reprice_after(),
+            reprice_margin_percent: default_reprice_margin_percent(),
+            full_inventory_policy: FullInventoryPolicy::default(),
+            ender_chest_command: default_ender_chest_command(),
+            junk_action: JunkAction::default(),
+            keep_items: Vec::new(),
+            junk_price: default_junk_price(),
+            delay_between_cycles: default_delay_between_cycles(),
+            delay_after_join: default_delay_after_join(),
+            window_timeout: default_window_timeout(),
+            delay_between_listings: default_delay_between_listings(),
+            debug_events: false,
+            record_session: String::new(),
+            enable_afk_farming: true,
+            shards_check_interval: default_shards_check_interval(),
+            shard_stall_after: default_shard_stall_after(),
+            webhook: WebhookConfig::default(),
+            digest: DigestConfig::default(),
+            gui: GuiConfig::default(),
+            chat_patterns: ChatPatterns::default(),
+        }
+    }
+}
+
+/// Supported config file formats, chosen by file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let extension = path.extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("json") => Ok(ConfigFormat::Json),
+            Some("toml") => Ok(ConfigFormat::Toml),
+            Some("yaml") | Some("yml") => Ok(ConfigFormat::Yaml),
+            _ => Err(anyhow!(
+                "Can't tell the format of {} - use a .json, .toml, .yaml or .yml extension",
+                path.display()
+            )),
+        }
+    }
+
+    /// Parse file contents into untyped JSON, whatever the source format
+    pub fn parse(&self, data: &str) -> Result<serde_json::Value> {
+        Ok(match self {
+            ConfigFormat::Json => serde_json::from_str(data)?,
+            ConfigFormat::Toml => toml::from_str(data)?,
+            ConfigFormat::Yaml => serde_yaml::from_str(data)?,
+        })
+    }
+}
+
+/// Commented TOML equivalent of `config.template.json`, written by `init-config`
+pub const TOML_TEMPLATE: &str = include_str!("../config.template.toml");
+
+/// Write the TOML template to `path`, refusing to replace an existing file unless `force` is set
+pub fn write_template(path: &Path, force: bool) -> Result<()> {
+    if path.exists() && !force {
+        return Err(anyhow!("{} already exists (use --force to overwrite it)", path.display()));
+    }
+    fs::write(path, TOML_TEMPLATE)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// The config file to use when none is given: the first default name that exists
+pub fn default_config_path() -> PathBuf {
+    DEFAULT_CONFIG_PATHS.iter()
+        .map(PathBuf::from)
+        .find(|path| path.exists())
+        .unwrap_or_else(|| PathBuf::from(CONFIG_PATH))
+}
+
+/// Read a config file as untyped JSON, e.g. to look for keys the struct doesn't know
+pub fn read_raw(path: &Path) -> Result<serde_json::Value> {
+    let format = ConfigFormat::from_path(path)?;
+    let config_data = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    format.parse(&config_data)
+        .with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Flatten a serialized config into its leaf values, keyed by JSON path (`$.webhook.url`)
+pub fn flatten_leaves(value: &serde_json::Value) -> BTreeMap<String, serde_json::Value> {
+    fn walk(value: &serde_json::Value, path: &str, out: &mut BTreeMap<String, serde_json::Value>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, child) in map {
+                    walk(child, &format!("{}.{}", path, key), out);
                 }
             }
-            if let Ok(enable_afk) = std::env::var("ENABLE_AFK_FARMING") {
-                config.enable_afk_farming = enable_afk == "true";
+            leaf => {
+                out.insert(path.to_string(), leaf.clone());
             }
-            if let Ok(delay) = std::env::var("DELAY_BETWEEN_LISTINGS") {
-                if let Ok(d) = delay.parse() {
-                    config.delay_between_listings = d;
-                }
-            }
-            
-            Ok(config)
-        } else {
-            println!("[CONFIG] No config.json found, using environment variables and defaults");
-            Ok(Config::from_env())
         }
     }
-    
-    pub fn from_env() -> Self {
-        Self {
-            host: std::env::var("BOT_HOST").unwrap_or_else(|_| default_host()),
-            port: std::env::var("BOT_PORT")
-                .ok()
-                .and_then(|p| p.parse().ok())
-                .unwrap_or_else(default_port),
-            username: std::env::var("BOT_USERNAME").unwrap_or_else(|_| default_username()),
-            auth: std::env::var("BOT_AUTH").unwrap_or_else(|_| default_auth()),
-            version: std::env::var("BOT_VERSION").unwrap_or_else(|_| default_version()),
-            max_buy_price: std::env::var("MAX_BUY_PRICE")
-                .ok()
-                .and_then(|p| p.parse().ok())
-                .unwrap_or_else(default_max_buy_price),
-            sell_price: std::env::var("SELL_PRICE").unwrap_or_else(|_| default_sell_price()),
-            max_listings_per_cycle: std::env::var("MAX_LISTINGS_PER_CYCLE")
-                .ok()
-                .and_then(|p| p.parse().ok())
-                .unwrap_or_else(default_max_listings_per_cycle),
-            delay_between_cycles: std::env::var("DELAY_BETWEEN_CYCLES")
-                .ok()
-                .and_then(|d| d.parse().ok())
-                .unwrap_or_else(default_delay_between_cycles),
-            delay_after_join: std::env::var("DELAY_AFTER_JOIN")
-                .ok()
-                .and_then(|d| d.parse().ok())
-                .unwrap_or_else(default_delay_after_join),
-            window_timeout: std::env::var("WINDOW_TIMEOUT")
-                .ok()
-                .and_then(|t| t.parse().ok())
-                .unwrap_or_else(default_window_timeout),
-            delay_between_listings: std::env::var("DELAY_BETWEEN_LISTINGS")
-                .ok()
-                .and_then(|d| d.parse().ok())
-                .unwrap_or_else(default_delay_between_listings),
-            debug_events: std::env::var("DEBUG_EVENTS")
-                .map(|v| v == "true")
-                .unwrap_or(false),
-            enable_afk_farming: std::env::var("ENABLE_AFK_FARMING")
-                .map(|v| v == "true")
-                .unwrap_or(true),
-            webhook: WebhookConfig::default(),
-        }
+
+    let mut out = BTreeMap::new();
+    walk(value, "$", &mut out);
+    out
+}
+
+impl Config {
+    /// Deserialize untyped config, naming the offending key (`$.sellPrice`) on error
+    pub fn from_value(value: serde_json::Value) -> Result<Self> {
+        serde_path_to_error::deserialize(value).map_err(|e| {
+            let path = e.path().to_string();
+            if path == "." {
+                anyhow!("{}", e.inner())
+            } else {
+                anyhow!("at $.{}: {}", path, e.inner())
+            }
+        })
     }
 }
 
@@ -197,11 +487,62 @@ fn default_port() -> u16 { 25565 }
 fn default_username() -> String { "BOT_USERNAME".to_string() }
 fn default_auth() -> String { "microsoft".to_string() }
 fn default_version() -> String { "1.21.11".to_string() }
-fn default_max_buy_price() -> u32 { 2500 }
-fn default_sell_price() -> String { "9.9k".to_string() }
+fn default_max_buy_price() -> Money { Money::new(2500) }
+fn default_sell_price() -> Money { Money::new(9900) }
+fn default_balance_check_interval() -> u64 { 300_000 }
+fn default_shards_check_interval() -> u64 { 1_800_000 }
+fn default_shard_stall_after() -> u64 { 3_600_000 }
+fn default_max_consecutive_failures() -> u32 { 5 }
+fn default_profit_check_trades() -> u32 { 10 }
+fn default_limit_cooldown() -> u64 { 1_800_000 }
+fn default_resume_file() -> String { "resume-buying".to_string() }
+fn default_limits_state_file() -> String { "limits-state.json".to_string() }
 fn default_max_listings_per_cycle() -> u32 { 20 }
+fn default_listings_command() -> String { "/ah myitems".to_string() }
+fn default_listings_check_interval() -> u64 { 600_000 }
+fn default_expired_command() -> String { "/ah expired".to_string() }
+fn default_reclaim_interval() -> u64 { 1_800_000 }
+fn default_relist_discount_after() -> u32 { 2 }
+fn default_reprice_after() -> u64 { 21_600_000 }
+fn default_reprice_margin_percent() -> u32 { 10 }
+fn default_ender_chest_command() -> String { "/ec".to_string() }
+fn default_junk_price() -> Money { Money::new(1000) }
 fn default_delay_between_cycles() -> u64 { 5000 }
 fn default_delay_after_join() -> u64 { 5000 }
 fn default_window_timeout() -> u64 { 15000 }
 fn default_delay_between_listings() -> u64 { 1000 }
 fn default_display_name() -> String { "DonutSMP Map Flipper".to_string() }
+fn default_digest_state_file() -> String { "digest-state.json".to_string() }
+fn default_confirm_slot() -> usize { 15 }
+fn default_afk_slot() -> usize { 49 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toml_template_matches_json_template() {
+        let from_toml = ConfigFormat::Toml.parse(TOML_TEMPLATE).unwrap();
+        let from_json = ConfigFormat::Json.parse(include_str!("../config.template.json")).unwrap();
+        assert_eq!(from_toml, from_json);
+    }
+
+    #[test]
+    fn test_formats_share_key_names() {
+        let yaml = "maxBuyPrice: 3000\nsellPrice: 12k\nwebhook:\n  displayName: Flipper\n";
+        let config: Config = serde_json::from_value(ConfigFormat::Yaml.parse(yaml).unwrap()).unwrap();
+        assert_eq!(config.max_buy_price, Money::new(3000));
+        assert_eq!(config.sell_price, Money::new(12000));
+        assert_eq!(config.webhook.display_name, "Flipper");
+
+        let legacy: Config = serde_json::from_str(r#"{"webhook": {"display_name": "Old"}}"#).unwrap();
+        assert_eq!(legacy.webhook.display_name, "Old");
+    }
+
+    #[test]
+    fn test_format_from_extension() {
+        assert_eq!(ConfigFormat::from_path(Path::new("a/config.yml")).unwrap(), ConfigFormat::Yaml);
+        assert_eq!(ConfigFormat::from_path(Path::new("CONFIG.TOML")).unwrap(), ConfigFormat::Toml);
+        assert!(ConfigFormat::from_path(Path::new("config.ini")).is_err());
+    }
+}
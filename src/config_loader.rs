@@ -0,0 +1,315 @@
+use anyhow::{anyhow, Context, Result};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use crate::config::{self, flatten_leaves, Config, KEY_ALIASES, SECRET_FIELDS};
+
+/// Prefix for environment overrides: `$.webhook.events.sale` → `MAPFLIPPER_WEBHOOK_EVENTS_SALE`
+pub const ENV_PREFIX: &str = "MAPFLIPPER_";
+
+/// Variable names from before the `MAPFLIPPER_` prefix; still honoured, but deprecated
+/// and overridden by their prefixed equivalents
+const LEGACY_ENV: &[(&str, &str)] = &[
+    ("BOT_HOST", "$.host"),
+    ("BOT_PORT", "$.port"),
+    ("BOT_USERNAME", "$.username"),
+    ("BOT_AUTH", "$.auth"),
+    ("BOT_VERSION", "$.version"),
+    ("MAX_BUY_PRICE", "$.maxBuyPrice"),
+    ("SELL_PRICE", "$.sellPrice"),
+    ("MAX_LISTINGS_PER_CYCLE", "$.maxListingsPerCycle"),
+    ("DELAY_BETWEEN_CYCLES", "$.delayBetweenCycles"),
+    ("DELAY_AFTER_JOIN", "$.delayAfterJoin"),
+    ("WINDOW_TIMEOUT", "$.windowTimeout"),
+    ("DELAY_BETWEEN_LISTINGS", "$.delayBetweenListings"),
+    ("DEBUG_EVENTS", "$.debugEvents"),
+    ("ENABLE_AFK_FARMING", "$.enableAfkFarming"),
+];
+
+/// Where an effective config value came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    Default,
+    File,
+    Env(String),
+    Cli,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::File => write!(f, "file"),
+            ConfigSource::Env(name) => write!(f, "env {}", name),
+            ConfigSource::Cli => write!(f, "cli"),
+        }
+    }
+}
+
+/// A fully layered config together with the information needed to explain it
+pub struct LoadedConfig {
+    pub config: Config,
+    /// The config file as untyped JSON, if one was read
+    pub raw_file: Option<Value>,
+    /// Which layer supplied each leaf value, keyed by JSON path
+    pub sources: BTreeMap<String, ConfigSource>,
+}
+
+impl LoadedConfig {
+    /// One `path = value (source)` line per setting, with secrets redacted
+    pub fn describe(&self) -> Vec<String> {
+        let leaves = flatten_leaves(&serde_json::to_value(&self.config).unwrap_or(Value::Null));
+
+        leaves.iter()
+            .map(|(path, value)| {
+                let shown = match value {
+                    Value::String(s) if !s.is_empty() && SECRET_FIELDS.contains(&path.as_str()) => {
+                        "\"<redacted>\"".to_string()
+                    }
+                    other => other.to_string(),
+                };
+                let source = self.sources.get(path).cloned().unwrap_or(ConfigSource::Default);
+                format!("{} = {} ({})", path, shown, source)
+            })
+            .collect()
+    }
+}
+
+/// Builds a `Config` from defaults, then the config file, then environment
+/// variables, then CLI `--set` overrides; each layer wins over the previous one.
+#[derive(Debug, Clone)]
+pub struct ConfigLoader {
+    path: PathBuf,
+    env: Vec<(String, String)>,
+    overrides: Vec<(String, String)>,
+}
+
+impl ConfigLoader {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            env: Vec::new(),
+            overrides: Vec::new(),
+        }
+        .with_env(std::env::vars())
+    }
+
+    /// Replace the captured process environment
+    pub fn with_env(mut self, vars: impl IntoIterator<Item = (String, String)>) -> Self {
+        self.env = vars.into_iter().collect();
+        self
+    }
+
+    /// CLI overrides as `(key, value)` pairs, where key is a config path like `webhook.enabled`
+    pub fn with_overrides(mut self, overrides: Vec<(String, String)>) -> Self {
+        self.overrides = overrides;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn load(&self) -> Result<LoadedConfig> {
+        let defaults = serde_json::to_value(Config::default())?;
+        let known = flatten_leaves(&defaults);
+        let mut merged = defaults;
+        let mut sources: BTreeMap<String, ConfigSource> = known.keys()
+            .map(|path| (path.clone(), ConfigSource::Default))
+            .collect();
+
+        // Layer 2: config file. Unknown keys are left for validation to report.
+        let raw_file = if self.path.exists() {
+            Some(config::read_raw(&self.path)?)
+        } else {
+            println!("[CONFIG] No {} found, using defaults and environment variables", self.path.display());
+            None
+        };
+        if let Some(raw) = &raw_file {
+            for (path, value) in flatten_leaves(raw) {
+                let path = match KEY_ALIASES.iter().find(|(old, _)| *old == path) {
+                    Some((_, current)) => current.to_string(),
+                    None => path,
+                };
+                if known.contains_key(&path) {
+                    set_path(&mut merged, &path, value);
+                    sources.insert(path, ConfigSource::File);
+                }
+            }
+        }
+
+        // Layer 3: environment, legacy names first so prefixed names win
+        let env: BTreeMap<&str, &str> = self.env.iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        for (name, path) in LEGACY_ENV {
+            if let Some(raw_value) = env.get(name) {
+                println!("[CONFIG] ⚠ {} is deprecated, use {} instead", name, env_name(path));
+                let value = typed_value(raw_value, &known[*path])
+                    .with_context(|| format!("Invalid value for {}", name))?;
+                set_path(&mut merged, path, value);
+                sources.insert(path.to_string(), ConfigSource::Env(name.to_string()));
+            }
+        }
+        for (path, default) in &known {
+            let name = env_name(path);
+            if let Some(raw_value) = env.get(name.as_str()) {
+                let value = typed_value(raw_value, default)
+                    .with_context(|| format!("Invalid value for {}", name))?;
+                set_path(&mut merged, path, value);
+                sources.insert(path.clone(), ConfigSource::Env(name));
+            }
+        }
+
+        // Layer 4: CLI overrides
+        for (key, raw_value) in &self.overrides {
+            let path = if key.starts_with("$.") { key.clone() } else { format!("$.{}", key) };
+            let default = known.get(&path)
+                .ok_or_else(|| anyhow!("Unknown config key '{}' in --set", key))?;
+            let value = typed_value(raw_value, default)
+                .with_context(|| format!("Invalid value for --set {}", key))?;
+            set_path(&mut merged, &path, value);
+            sources.insert(path, ConfigSource::Cli);
+        }
+
+        let config = Config::from_value(merged)
+            .context("Failed to build configuration")?;
+
+        Ok(LoadedConfig { config, raw_file, sources })
+    }
+}
+
+/// Environment variable for a config path: `$.webhook.displayName` → `MAPFLIPPER_WEBHOOK_DISPLAY_NAME`
+pub fn env_name(path: &str) -> String {
+    let mut name = String::from(ENV_PREFIX);
+    let segments = path.trim_start_matches("$.").split('.');
+
+    for (i, segment) in segments.enumerate() {
+        if i > 0 {
+            name.push('_');
+        }
+        for (j, c) in segment.chars().enumerate() {
+            if c.is_ascii_uppercase() && j > 0 {
+                name.push('_');
+            }
+            name.push(c.to_ascii_uppercase());
+        }
+    }
+
+    name
+}
+
+/// Interpret a string from the environment or CLI using the type of the default value
+fn typed_value(raw: &str, default: &Value) -> Result<Value> {
+    let raw = raw.trim();
+    match default {
+        Value::Bool(_) => match raw.to_ascii_lowercase().as_str() {
+            "true" | "1" | "yes" | "on" => Ok(Value::Bool(true)),
+            "false" | "0" | "no" | "off" => Ok(Value::Bool(false)),
+            _ => Err(anyhow!("expected true or false, got '{}'", raw)),
+        },
+        Value::Number(_) => {
+            if let Ok(n) = raw.parse::<u64>() {
+                Ok(Value::from(n))
+            } else if let Ok(n) = raw.parse::<i64>() {
+                Ok(Value::from(n))
+            } else if let Some(n) = raw.parse::<f64>().ok().and_then(serde_json::Number::from_f64) {
+                Ok(Value::Number(n))
+            } else {
+                // Not a plain number (e.g. a price like "9.9k"); the field's own
+                // deserializer decides whether that's acceptable
+                Ok(Value::String(raw.to_string()))
+            }
+        }
+        Value::Array(_) | Value::Object(_) => serde_json::from_str(raw)
+            .with_context(|| format!("expected JSON, got '{}'", raw)),
+        _ => Ok(Value::String(raw.to_string())),
+    }
+}
+
+/// Set the value at a `$.a.b` path, creating intermediate objects as needed
+fn set_path(root: &mut Value, path: &str, value: Value) {
+    let mut current = root;
+    let mut segments = path.trim_start_matches("$.").split('.').peekable();
+
+    while let Some(segment) = segments.next() {
+        if !current.is_object() {
+            *current = Value::Object(Default::default());
+        }
+        let map = current.as_object_mut().expect("just made an object");
+        if segments.peek().is_none() {
+            map.insert(segment.to_string(), value);
+            return;
+        }
+        current = map.entry(segment.to_string()).or_insert(Value::Null);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::price_parser::Money;
+
+    fn loader(env: &[(&str, &str)]) -> ConfigLoader {
+        ConfigLoader::new("does-not-exist.json")
+            .with_env(env.iter().map(|(k, v)| (k.to_string(), v.to_string())))
+    }
+
+    #[test]
+    fn test_env_name() {
+        assert_eq!(env_name("$.maxBuyPrice"), "MAPFLIPPER_MAX_BUY_PRICE");
+        assert_eq!(env_name("$.webhook.events.sale"), "MAPFLIPPER_WEBHOOK_EVENTS_SALE");
+        assert_eq!(env_name("$.webhook.displayName"), "MAPFLIPPER_WEBHOOK_DISPLAY_NAME");
+    }
+
+    #[test]
+    fn test_layers_override_in_order() {
+        let loaded = loader(&[
+            ("MAX_BUY_PRICE", "1000"),
+            ("MAPFLIPPER_MAX_BUY_PRICE", "2000"),
+            ("MAPFLIPPER_WEBHOOK_ENABLED", "true"),
+            ("MAPFLIPPER_WINDOW_TIMEOUT", "20000"),
+        ])
+        .with_overrides(vec![("windowTimeout".to_string(), "30000".to_string())])
+        .load()
+        .unwrap();
+
+        assert_eq!(loaded.config.max_buy_price, Money::new(2000));
+        assert!(loaded.config.webhook.enabled);
+        assert_eq!(loaded.config.window_timeout, 30000);
+        assert_eq!(
+            loaded.sources["$.maxBuyPrice"],
+            ConfigSource::Env("MAPFLIPPER_MAX_BUY_PRICE".to_string())
+        );
+        assert_eq!(loaded.sources["$.windowTimeout"], ConfigSource::Cli);
+        assert_eq!(loaded.sources["$.host"], ConfigSource::Default);
+    }
+
+    #[test]
+    fn test_prices_accept_suffixes() {
+        let loaded = loader(&[("MAPFLIPPER_SELL_PRICE", "12.5k")]).load().unwrap();
+        assert_eq!(loaded.config.sell_price, Money::new(12500));
+    }
+
+    #[test]
+    fn test_invalid_values_are_errors() {
+        assert!(loader(&[("MAPFLIPPER_PORT", "abc")]).load().is_err());
+        let err = loader(&[("MAPFLIPPER_SELL_PRICE", "abc")]).load().err().unwrap();
+        assert!(format!("{:#}", err).contains("at $.sellPrice: invalid price \"abc\""));
+        assert!(loader(&[])
+            .with_overrides(vec![("nope".to_string(), "1".to_string())])
+            .load()
+            .is_err());
+    }
+
+    #[test]
+    fn test_describe_redacts_secrets() {
+        let loaded = loader(&[("MAPFLIPPER_WEBHOOK_URL", "https://discord.com/api/webhooks/secret")])
+            .load()
+            .unwrap();
+        let lines = loaded.describe();
+        let url_line = lines.iter().find(|l| l.starts_with("$.webhook.url")).unwrap();
+        assert_eq!(url_line, "$.webhook.url = \"<redacted>\" (env MAPFLIPPER_WEBHOOK_URL)");
+    }
+}
@@ -0,0 +1,224 @@
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::sleep;
+use crate::config::Config;
+use crate::session::{GameSession, Window};
+
+// Minecraft server tick rate: 1 tick = 50 milliseconds
+const MS_PER_TICK: u64 = 50;
+
+// Wait after sending a command before looking for its window (prevents "Invalid sequence" kick)
+const COMMAND_DELAY: u64 = 300;
+
+/// Window id of the player inventory; every other id is a container
+pub const PLAYER_WINDOW_ID: i32 = 0;
+
+/// `windowTimeout` in ticks, rounded up so we never wait less than configured
+pub fn timeout_ticks(config: &Config) -> usize {
+    config.window_timeout.div_ceil(MS_PER_TICK) as usize
+}
+
+/// What the bot knows about the container it had open
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowState {
+    /// Only the player inventory is open
+    Closed,
+    Open(i32),
+    /// The server closed or replaced this container without us asking
+    ClosedByServer(i32),
+}
+
+/// Owns the bot's idea of which container is open, so every flow opens and closes
+/// windows by the same rules:
+///
+/// - a window counts as opened only once the server shows a container id the manager
+///   hasn't handed out yet, so a stale window is never mistaken for the one we asked for
+/// - dropping a window handle never closes anything; windows are closed with `close`,
+///   which does nothing if the server already closed them
+/// - a container that goes away without `close` is noticed and logged as closed by the server
+///
+/// A new manager takes over whatever container is already open.
+///
+/// Only container ids are tracked here, not state ids. azalea stamps each click with the
+/// latest state id it has seen in set-content and set-slot packets, so a click can still
+/// carry a stale one if it crosses a server resync in flight. The server then rejects it
+/// and sends the contents again; the flows check the slot after every click and treat an
+/// unchanged slot as a failed click.
+pub struct ContainerManager<'a, S: GameSession> {
+    bot: &'a S,
+    timeout_ticks: usize,
+    state: WindowState,
+    /// The last container id handed out
+    last_id: Option<i32>,
+}
+
+impl<'a, S: GameSession> ContainerManager<'a, S> {
+    pub fn new(bot: &'a S, config: &Config) -> Self {
+        let id = bot.current_window().id();
+        let state = if id == PLAYER_WINDOW_ID { WindowState::Closed } else { WindowState::Open(id) };
+        Self {
+            bot,
+            timeout_ticks: timeout_ticks(config),
+            state,
+            last_id: (id != PLAYER_WINDOW_ID).then_some(id),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn state(&mut self) -> WindowState {
+        self.refresh();
+        self.state
+    }
+
+    /// Notice the server closing or replacing our container
+    fn refresh(&mut self) {
+        if let WindowState::Open(open) = self.state {
+            if self.bot.current_window().id() != open {
+                println!("[WINDOW] Container {} was closed by the server", open);
+                self.state = WindowState::ClosedByServer(open);
+            }
+        }
+    }
+
+    /// The open container, or `None` if the server has closed it
+    pub fn current(&mut self) -> Option<S::Inventory> {
+        self.refresh();
+        match self.state {
+            WindowState::Open(_) => Some(self.bot.current_window()),
+            _ => None,
+        }
+    }
+
+    /// Wait up to `windowTimeout` for the server to open a new container
+    pub async fn await_next_window(&mut self) -> Option<S::Inventory> {
+        self.await_next_window_within(self.timeout_ticks).await
+    }
+
+    /// Wait up to `ticks` for the server to open a new container
+    pub async fn await_next_window_within(&mut self, ticks: usize) -> Option<S::Inventory> {
+        for tick in 0..=ticks {
+            let window = self.bot.current_window();
+            let id = window.id();
+            if id != PLAYER_WINDOW_ID && Some(id) != self.last_id {
+                self.refresh();
+                self.state = WindowState::Open(id);
+                self.last_id = Some(id);
+                return Some(window);
+            }
+            if tick < ticks {
+                sleep(Duration::from_millis(MS_PER_TICK)).await;
+            }
+        }
+        self.refresh();
+        None
+    }
+
+    /// Send `command` and wait for the window it opens
+    pub async fn open_with_command(&mut self, command: &str) -> Option<S::Inventory> {
+        self.bot.chat(command);
+        sleep(Duration::from_millis(COMMAND_DELAY)).await;
+        self.await_next_window().await
+    }
+
+    /// Close our container, unless the server already did
+    pub fn close(&mut self) {
+        self.refresh();
+        if let WindowState::Open(id) = self.state {
+            println!("[WINDOW] Closing container {}", id);
+            self.bot.current_window().close();
+        }
+        self.state = WindowState::Closed;
+    }
+
+    /// Send `command`, run `scope` on the window it opens, then close that window
+    ///
+    /// Returns `None` if no window opened within `windowTimeout`.
+    pub async fn with_window<T, F>(&mut self, command: &str, scope: impl FnOnce(S::Inventory) -> F) -> Option<T>
+    where
+        F: Future<Output = T>,
+    {
+        let window = self.open_with_command(command).await?;
+        let result = scope(window).await;
+        self.close();
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::fake::{Action, FakeSession, Reaction};
+
+    fn chest() -> Reaction {
+        Reaction::Open { label: "chest".to_string(), slots: vec![None; 27] }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_open_and_close() {
+        let bot = FakeSession::new(vec![]);
+        bot.on_command("/ec", vec![chest()]);
+        let config = Config::default();
+        let mut windows = ContainerManager::new(&bot, &config);
+        assert_eq!(windows.state(), WindowState::Closed);
+
+        let chest = windows.open_with_command("/ec").await.unwrap();
+        assert_eq!(chest.id(), 1);
+        assert_eq!(windows.state(), WindowState::Open(1));
+
+        windows.close();
+        assert_eq!(windows.state(), WindowState::Closed);
+        assert_eq!(bot.current_window().id(), PLAYER_WINDOW_ID);
+        assert!(bot.actions().contains(&Action::Close { window: 1 }));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_window_already_open_is_not_the_next_one() {
+        let bot = FakeSession::new(vec![]);
+        bot.on_command("/ah map", vec![chest()]);
+        bot.chat("/ah map");
+
+        // The server ignores the command, so the auction house stays the only window
+        let config = Config::default();
+        let mut windows = ContainerManager::new(&bot, &config);
+        assert_eq!(windows.state(), WindowState::Open(1));
+        assert!(windows.open_with_command("/ah sell 100").await.is_none());
+        assert!(windows.current().is_some());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_server_side_close_is_detected() {
+        let bot = FakeSession::new(vec![]);
+        bot.on_command("/afk", vec![chest()])
+            .on_click("chest", 13, vec![Reaction::Close]);
+        let config = Config::default();
+        let mut windows = ContainerManager::new(&bot, &config);
+
+        let menu = windows.open_with_command("/afk").await.unwrap();
+        menu.left_click(13);
+        assert_eq!(windows.state(), WindowState::ClosedByServer(1));
+        assert!(windows.current().is_none());
+
+        // Nothing left for us to close
+        windows.close();
+        assert!(!bot.actions().iter().any(|action| matches!(action, Action::Close { .. })));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_with_window_closes_afterwards() {
+        let bot = FakeSession::new(vec![]);
+        bot.on_command("/ah myitems", vec![chest()]);
+        let config = Config::default();
+        let mut windows = ContainerManager::new(&bot, &config);
+
+        let size = windows.with_window("/ah myitems", |window| async move {
+            window.contents().map(|slots| slots.len())
+        }).await;
+        assert_eq!(size, Some(Some(27 + 36)));
+        assert_eq!(windows.state(), WindowState::Closed);
+        assert!(bot.actions().contains(&Action::Close { window: 1 }));
+
+        // No window at all: the scope never runs
+        let ran = windows.with_window("/ah expired", |_| async {}).await;
+        assert!(ran.is_none());
+    }
+}
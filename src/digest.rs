@@ -0,0 +1,370 @@
+use chrono::{DateTime, Duration, DurationRound, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::price_parser::Money;
+
+/// Which digest a window belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigestPeriod {
+    Hourly,
+    Daily,
+}
+
+impl DigestPeriod {
+    pub const ALL: [DigestPeriod; 2] = [DigestPeriod::Hourly, DigestPeriod::Daily];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DigestPeriod::Hourly => "hourly",
+            DigestPeriod::Daily => "daily",
+        }
+    }
+
+    fn length(&self) -> Duration {
+        match self {
+            DigestPeriod::Hourly => Duration::hours(1),
+            DigestPeriod::Daily => Duration::days(1),
+        }
+    }
+
+    /// Start of the (UTC-aligned) window containing `now`
+    fn window_start(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        now.duration_trunc(self.length()).unwrap_or(now)
+    }
+}
+
+/// Trading counters accumulated during one digest window
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DigestCounters {
+    pub purchases: u32,
+    pub sales: u32,
+    pub revenue: Money,
+    pub spend: Money,
+    pub reconnects: u32,
+}
+
+impl DigestCounters {
+    pub fn net_profit(&self) -> i64 {
+        self.revenue.dollars() as i64 - self.spend.dollars() as i64
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DigestWindow {
+    started_at: DateTime<Utc>,
+    #[serde(default)]
+    counters: DigestCounters,
+}
+
+impl DigestWindow {
+    fn new(period: DigestPeriod, now: DateTime<Utc>) -> Self {
+        Self {
+            started_at: period.window_start(now),
+            counters: DigestCounters::default(),
+        }
+    }
+}
+
+/// Everything persisted between restarts: window boundaries, counters and gauges
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DigestState {
+    hourly: DigestWindow,
+    daily: DigestWindow,
+    #[serde(default)]
+    inventory_maps: u32,
+    #[serde(default)]
+    free_slots: Option<u32>,
+    #[serde(default)]
+    shard_balance: Option<String>,
+}
+
+/// A finished digest window, ready to be sent to the configured sinks
+#[derive(Debug, Clone)]
+pub struct DigestReport {
+    pub period: DigestPeriod,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    pub counters: DigestCounters,
+    pub inventory_maps: u32,
+    pub free_slots: Option<u32>,
+    pub listings_outstanding: u32,
+    pub shard_balance: Option<String>,
+}
+
+impl DigestReport {
+    pub fn title(&self) -> String {
+        format!(
+            "📈 {} digest ({} → {} UTC)",
+            capitalize(self.period.name()),
+            self.started_at.format("%Y-%m-%d %H:%M"),
+            self.ended_at.format("%Y-%m-%d %H:%M"),
+        )
+    }
+
+    /// Embed fields in the same (name, value, inline) shape `send_webhook` takes
+    pub fn fields(&self) -> Vec<(String, String, bool)> {
+        let c = &self.counters;
+        vec![
+            ("Purchases".to_string(), c.purchases.to_string(), true),
+            ("Sales".to_string(), c.sales.to_string(), true),
+            ("Reconnects".to_string(), c.reconnects.to_string(), true),
+            ("Revenue".to_string(), c.revenue.to_string(), true),
+            ("Spend".to_string(), c.spend.to_string(), true),
+            ("Net Profit".to_string(), format_signed(c.net_profit()), true),
+            ("Maps in Inventory".to_string(), self.inventory_maps.to_string(), true),
+            (
+                "Free Slots".to_string(),
+                self.free_slots.map(|n| n.to_string()).unwrap_or_else(|| "unknown".to_string()),
+                true,
+            ),
+            ("Listings Outstanding".to_string(), self.listings_outstanding.to_string(), true),
+            (
+                "Shard Balance".to_string(),
+                self.shard_balance.clone().unwrap_or_else(|| "unknown".to_string()),
+                true,
+            ),
+        ]
+    }
+}
+
+/// Tracks hourly and daily digest windows and persists them to disk
+///
+/// Mutations only mark the state dirty; the digest loop takes a snapshot with
+/// `take_unsaved` and writes it off the async runtime, so counters and window boundaries
+/// survive a restart without a file write under the lock on every trade. A window whose
+/// boundary passed while the bot was down is reported on the first check after startup.
+pub struct DigestTracker {
+    path: PathBuf,
+    state: DigestState,
+    dirty: bool,
+}
+
+impl DigestTracker {
+    /// Load the tracker from `path`, starting fresh windows if the file is missing or unreadable
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let now = Utc::now();
+
+        let state = match fs::read_to_string(&path) {
+            Ok(data) => match serde_json::from_str(&data) {
+                Ok(state) => {
+                    println!("[DIGEST] Restored digest windows from {}", path.display());
+                    state
+                }
+                Err(e) => {
+                    eprintln!("[DIGEST] Ignoring unreadable state file {}: {}", path.display(), e);
+                    Self::fresh_state(now)
+                }
+            },
+            Err(_) => Self::fresh_state(now),
+        };
+
+        Self { path, state, dirty: false }
+    }
+
+    fn fresh_state(now: DateTime<Utc>) -> DigestState {
+        DigestState {
+            hourly: DigestWindow::new(DigestPeriod::Hourly, now),
+            daily: DigestWindow::new(DigestPeriod::Daily, now),
+            inventory_maps: 0,
+            free_slots: None,
+            shard_balance: None,
+        }
+    }
+
+    /// The state file's path and contents if anything changed since the last call
+    ///
+    /// Clears the dirty flag; write the result outside the lock.
+    pub fn take_unsaved(&mut self) -> Option<(PathBuf, String)> {
+        if !self.dirty {
+            return None;
+        }
+        match serde_json::to_string_pretty(&self.state) {
+            Ok(data) => {
+                self.dirty = false;
+                Some((self.path.clone(), data))
+            }
+            Err(e) => {
+                eprintln!("[DIGEST] Failed to serialize digest state: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Apply `f` to the counters of both windows and mark the result for saving
+    fn update(&mut self, f: impl Fn(&mut DigestCounters)) {
+        f(&mut self.state.hourly.counters);
+        f(&mut self.state.daily.counters);
+        self.dirty = true;
+    }
+
+    pub fn record_purchase(&mut self, price: Money) {
+        self.update(|c| {
+            c.purchases += 1;
+            c.spend = c.spend.saturating_add(price);
+        });
+    }
+
+    pub fn record_sale(&mut self, price: Money) {
+        self.record_sales(1, price);
+    }
+
+    /// Book `count` sales that made `total` between them, e.g. an offline summary
+    pub fn record_sales(&mut self, count: u32, total: Money) {
+        self.update(|c| {
+            c.sales += count;
+            c.revenue = c.revenue.saturating_add(total);
+        });
+    }
+
+    pub fn record_reconnect(&mut self) {
+        self.update(|c| c.reconnects += 1);
+    }
+
+    pub fn set_inventory_maps(&mut self, count: u32) {
+        self.state.inventory_maps = count;
+        self.dirty = true;
+    }
+
+    pub fn set_free_slots(&mut self, count: u32) {
+        self.state.free_slots = Some(count);
+        self.dirty = true;
+    }
+
+    pub fn set_shard_balance(&mut self, balance: String) {
+        self.state.shard_balance = Some(balance);
+        self.dirty = true;
+    }
+
+    /// Close every window whose boundary has passed and return the finished reports
+    ///
+    /// `listings_outstanding` is the listing tracker's active count, which knows about
+    /// expiries, reclaims and cancels that the digest never sees.
+    pub fn take_due(&mut self, now: DateTime<Utc>, listings_outstanding: u32) -> Vec<DigestReport> {
+        let mut reports = Vec::new();
+
+        for period in DigestPeriod::ALL {
+            let window = match period {
+                DigestPeriod::Hourly => &mut self.state.hourly,
+                DigestPeriod::Daily => &mut self.state.daily,
+            };
+
+            let ended_at = window.started_at + period.length();
+            if now < ended_at {
+                continue;
+            }
+
+            let finished = std::mem::replace(window, DigestWindow::new(period, now));
+            reports.push(DigestReport {
+                period,
+                started_at: finished.started_at,
+                ended_at,
+                counters: finished.counters,
+                inventory_maps: self.state.inventory_maps,
+                free_slots: self.state.free_slots,
+                listings_outstanding,
+                shard_balance: self.state.shard_balance.clone(),
+            });
+        }
+
+        if !reports.is_empty() {
+            self.dirty = true;
+        }
+
+        reports
+    }
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        None => String::new(),
+        Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+    }
+}
+
+/// "$1,200" or "-$1,200"
+pub fn format_signed(value: i64) -> String {
+    let amount = Money::new(value.unsigned_abs());
+    if value < 0 {
+        format!("-{}", amount)
+    } else {
+        amount.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn tracker_at(now: DateTime<Utc>) -> DigestTracker {
+        let path = std::env::temp_dir().join(format!("digest-test-{}.json", now.timestamp_nanos_opt().unwrap()));
+        DigestTracker {
+            path,
+            state: DigestTracker::fresh_state(now),
+            dirty: false,
+        }
+    }
+
+    #[test]
+    fn test_windows_roll_over_independently() {
+        let start = Utc.with_ymd_and_hms(2024, 5, 1, 10, 15, 0).unwrap();
+        let mut tracker = tracker_at(start);
+
+        tracker.record_purchase(Money::new(2000));
+        tracker.record_sale(Money::new(9900));
+        assert!(tracker.take_due(start + Duration::minutes(30), 0).is_empty());
+
+        let reports = tracker.take_due(start + Duration::minutes(50), 2);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].period, DigestPeriod::Hourly);
+        assert_eq!(reports[0].started_at, Utc.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap());
+        assert_eq!(reports[0].counters.net_profit(), 7900);
+        assert_eq!(reports[0].listings_outstanding, 2);
+
+        // The daily window still holds the earlier trades
+        let reports = tracker.take_due(Utc.with_ymd_and_hms(2024, 5, 2, 0, 0, 1).unwrap(), 2);
+        let daily = reports.iter().find(|r| r.period == DigestPeriod::Daily).unwrap();
+        assert_eq!(daily.counters.purchases, 1);
+        assert_eq!(daily.counters.sales, 1);
+
+        let _ = fs::remove_file(&tracker.path);
+    }
+
+    #[test]
+    fn test_offline_summary_is_one_entry() {
+        let mut tracker = tracker_at(Utc::now());
+        tracker.record_sales(3, Money::new(29700));
+        assert_eq!(tracker.state.hourly.counters.sales, 3);
+        assert_eq!(tracker.state.daily.counters.revenue, Money::new(29700));
+    }
+
+    #[test]
+    fn test_changes_are_saved_on_flush_only() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 1, 10, 15, 0).unwrap();
+        let mut tracker = tracker_at(now);
+        assert!(tracker.take_unsaved().is_none());
+
+        tracker.record_purchase(Money::new(2000));
+        tracker.set_inventory_maps(3);
+        assert!(!tracker.path.exists());
+
+        let (path, data) = tracker.take_unsaved().unwrap();
+        assert!(tracker.take_unsaved().is_none());
+        fs::write(&path, data).unwrap();
+
+        let restored = DigestTracker::load(&path);
+        assert_eq!(restored.state.hourly.counters.purchases, 1);
+        assert_eq!(restored.state.inventory_maps, 3);
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_format_signed() {
+        assert_eq!(format_signed(1500), "$1,500");
+        assert_eq!(format_signed(-250), "-$250");
+    }
+}
@@ -0,0 +1,245 @@
+use crate::config::GuiConfig;
+use crate::session::{SlotItem, Window};
+use crate::text::normalize;
+
+/// A button the bot clicks in a server GUI
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    /// Confirm on the purchase, listing and cancel confirmation screens
+    Confirm,
+    /// "Random AFK location" in the `/afk` menu
+    AfkTeleport,
+}
+
+/// Items a button may be drawn with; the `minecraft:` namespace is left off
+const CONFIRM_KINDS: &[&str] = &[
+    "lime_stained_glass_pane", "green_stained_glass_pane", "lime_stained_glass", "green_stained_glass",
+    "lime_concrete", "green_concrete", "lime_wool", "green_wool", "lime_dye", "emerald_block",
+];
+const AFK_KINDS: &[&str] = &["ender_pearl", "ender_eye", "compass", "recovery_compass", "nether_star"];
+
+/// Materials servers draw generic GUI buttons with; an item matched by name alone must be
+/// one of these (or one of the button's own kinds), so a listing a player named "Buy" is
+/// never taken for a button
+const BUTTON_MATERIALS: &[&str] = &[
+    "stained_glass_pane", "stained_glass", "glass_pane", "concrete", "wool", "terracotta", "dye",
+];
+
+/// Words in a button's display name, matched case-insensitively
+const CONFIRM_NAMES: &[&str] = &["confirm", "accept", "purchase", "buy", "yes"];
+const AFK_NAMES: &[&str] = &["random"];
+
+/// Names that mean "don't"; such an item is never clicked, whatever slot it is in
+const CANCEL_NAMES: &[&str] = &["cancel", "deny", "decline", "back", "close", "no"];
+
+impl Button {
+    fn kinds(self) -> &'static [&'static str] {
+        match self {
+            Button::Confirm => CONFIRM_KINDS,
+            Button::AfkTeleport => AFK_KINDS,
+        }
+    }
+
+    fn names(self) -> &'static [&'static str] {
+        match self {
+            Button::Confirm => CONFIRM_NAMES,
+            Button::AfkTeleport => AFK_NAMES,
+        }
+    }
+
+    /// Where the button is expected when it can't be recognised
+    pub fn fallback_slot(self, gui: &GuiConfig) -> usize {
+        match self {
+            Button::Confirm => gui.confirm_slot,
+            Button::AfkTeleport => gui.afk_slot,
+        }
+    }
+}
+
+/// Lowercase words of an item's display name, colour codes removed and small capitals folded
+fn name_words(item: &SlotItem) -> Vec<String> {
+    let Some(name) = &item.name else {
+        return Vec::new();
+    };
+    normalize(name)
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// How sure we are that `item` is `button`: 0 = not it, 1 = right item, 2 = right name
+/// on a button material, 3 = both
+fn score(item: &SlotItem, button: Button) -> u8 {
+    let words = name_words(item);
+    let named = |names: &[&str]| words.iter().any(|word| names.contains(&word.as_str()));
+    if named(CANCEL_NAMES) {
+        return 0;
+    }
+    let kind = item.kind.trim_start_matches("minecraft:").to_ascii_lowercase();
+    let kind_matches = button.kinds().contains(&kind.as_str());
+    let looks_like_button = kind_matches || BUTTON_MATERIALS.iter().any(|material| kind.ends_with(material));
+    let name_matches = named(button.names()) && looks_like_button;
+    u8::from(kind_matches) + 2 * u8::from(name_matches)
+}
+
+/// Find `button` among the container slots of a GUI
+///
+/// A button whose display name matches, drawn with a button material, is used wherever
+/// it is; if several do, the one with the right item wins, then the one in the configured
+/// slot. An item that only looks right (unnamed green glass, say) is accepted in the
+/// configured slot alone. Anything else, including a cancel button or an empty configured
+/// slot, is `None`: better to click nothing than the wrong thing.
+pub fn find_button(slots: &[Option<SlotItem>], button: Button, gui: &GuiConfig) -> Option<usize> {
+    // The last 36 slots are the player's inventory, never a button
+    let container_size = slots.len().saturating_sub(36);
+    let fallback = button.fallback_slot(gui);
+
+    let scored: Vec<(usize, u8)> = slots.iter()
+        .take(container_size)
+        .enumerate()
+        .filter_map(|(slot, item)| Some((slot, score(item.as_ref()?, button))))
+        .collect();
+    let best = scored.iter().map(|&(_, score)| score).max().unwrap_or(0);
+
+    if best >= 2 {
+        let matches: Vec<usize> = scored.iter()
+            .filter(|&&(_, score)| score == best)
+            .map(|&(slot, _)| slot)
+            .collect();
+        return match matches.as_slice() {
+            [slot] => Some(*slot),
+            _ if matches.contains(&fallback) => Some(fallback),
+            _ => {
+                println!("[GUI] ✗ {:?} button is ambiguous (slots {:?}), not clicking", button, matches);
+                None
+            }
+        };
+    }
+
+    if scored.contains(&(fallback, 1)) {
+        println!("[GUI] {:?} button has no recognisable name, using configured slot {}", button, fallback);
+        return Some(fallback);
+    }
+
+    println!("[GUI] ✗ No {:?} button found (configured slot {} doesn't look like one), not clicking", button, fallback);
+    None
+}
+
+/// Click `button` in `window` if it can be found; returns the slot clicked
+pub fn click_button(window: &impl Window, button: Button, gui: &GuiConfig) -> Option<usize> {
+    let slot = find_button(&window.contents()?, button, gui)?;
+    println!("[INVENTORY DEBUG] Container {}: left-click slot {} ({:?} button)", window.id(), slot, button);
+    window.left_click(slot);
+    Some(slot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn button(kind: &str, name: Option<&str>) -> Option<SlotItem> {
+        Some(SlotItem {
+            kind: format!("minecraft:{}", kind),
+            count: 1,
+            name: name.map(str::to_string),
+            lore: Vec::new(),
+            map_id: None,
+        })
+    }
+
+    /// A 3-row confirm screen with `items` placed, above the player's inventory
+    fn screen(items: Vec<(usize, Option<SlotItem>)>) -> Vec<Option<SlotItem>> {
+        let mut slots = vec![None; 27 + 36];
+        for (slot, item) in items {
+            slots[slot] = item;
+        }
+        slots
+    }
+
+    #[test]
+    fn test_finds_moved_confirm_button() {
+        let gui = GuiConfig::default();
+        let slots = screen(vec![
+            (11, button("lime_stained_glass_pane", Some("§a§lConfirm"))),
+            (13, button("filled_map", Some("Map #12"))),
+            (15, button("red_stained_glass_pane", Some("§c§lCancel"))),
+        ]);
+        assert_eq!(find_button(&slots, Button::Confirm, &gui), Some(11));
+    }
+
+    #[test]
+    fn test_never_clicks_cancel() {
+        let gui = GuiConfig::default();
+        // Green, in the configured slot, but named cancel
+        let slots = screen(vec![(15, button("lime_stained_glass_pane", Some("Cancel")))]);
+        assert_eq!(find_button(&slots, Button::Confirm, &gui), None);
+        // Nothing at all where the button should be
+        assert_eq!(find_button(&screen(vec![]), Button::Confirm, &gui), None);
+        // Something unrelated where the button should be
+        let slots = screen(vec![(15, button("filled_map", None))]);
+        assert_eq!(find_button(&slots, Button::Confirm, &gui), None);
+    }
+
+    #[test]
+    fn test_unnamed_button_only_in_configured_slot() {
+        let gui = GuiConfig { confirm_slot: 11, ..GuiConfig::default() };
+        let slots = screen(vec![(11, button("lime_stained_glass_pane", None))]);
+        assert_eq!(find_button(&slots, Button::Confirm, &gui), Some(11));
+
+        let slots = screen(vec![(15, button("lime_stained_glass_pane", None))]);
+        assert_eq!(find_button(&slots, Button::Confirm, &gui), None);
+    }
+
+    #[test]
+    fn test_ambiguous_buttons() {
+        let gui = GuiConfig::default();
+        let two = vec![
+            (10, button("lime_stained_glass_pane", Some("Confirm"))),
+            (12, button("lime_stained_glass_pane", Some("Confirm"))),
+        ];
+        assert_eq!(find_button(&screen(two.clone()), Button::Confirm, &gui), None);
+
+        // The configured slot breaks the tie
+        let gui = GuiConfig { confirm_slot: 12, ..GuiConfig::default() };
+        assert_eq!(find_button(&screen(two), Button::Confirm, &gui), Some(12));
+
+        // The right item beats a name alone
+        let slots = screen(vec![
+            (10, button("white_stained_glass_pane", Some("Confirm"))),
+            (12, button("lime_concrete", Some("Confirm"))),
+        ]);
+        assert_eq!(find_button(&slots, Button::Confirm, &GuiConfig::default()), Some(12));
+    }
+
+    #[test]
+    fn test_named_listing_is_not_a_button() {
+        let gui = GuiConfig::default();
+        // A map a player renamed "Buy" is shown as the listing being bought
+        let slots = screen(vec![
+            (13, button("filled_map", Some("§a§lBuy"))),
+            (15, button("lime_stained_glass_pane", None)),
+        ]);
+        assert_eq!(find_button(&slots, Button::Confirm, &gui), Some(15));
+        let slots = screen(vec![(13, button("filled_map", Some("Purchase")))]);
+        assert_eq!(find_button(&slots, Button::Confirm, &gui), None);
+
+        // A name on a plain button material still counts, wherever it is
+        let slots = screen(vec![(11, button("gray_stained_glass_pane", Some("Buy")))]);
+        assert_eq!(find_button(&slots, Button::Confirm, &gui), Some(11));
+    }
+
+    #[test]
+    fn test_afk_button_ignores_inventory() {
+        let gui = GuiConfig::default();
+        let mut slots = vec![None; 54 + 36];
+        slots[22] = button("ender_pearl", Some("§dRandom AFK Location"));
+        assert_eq!(find_button(&slots, Button::AfkTeleport, &gui), Some(22));
+
+        // The same item in the player's inventory below doesn't count
+        let mut slots = vec![None; 54 + 36];
+        slots[60] = button("ender_pearl", Some("Random AFK Location"));
+        assert_eq!(find_button(&slots, Button::AfkTeleport, &gui), None);
+    }
+}
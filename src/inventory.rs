@@ -1,15 +1,16 @@
-use azalea::prelude::*;
-use azalea::container::ContainerClientExt;
-use azalea::inventory::{ItemStack, Menu};
-use azalea::inventory::components::Lore;
 use anyhow::{Result, anyhow};
+use std::collections::HashMap;
+use std::fmt;
 use std::time::Duration;
+use tokio::sync::Notify;
 use tokio::time::sleep;
+use crate::capacity::{is_kept, InventoryModel, SlotContent, ITEM_SLOTS};
 use crate::config::Config;
-use crate::price_parser::{parse_price, format_price};
-
-// Minecraft server tick rate: 1 tick = 50 milliseconds
-const MS_PER_TICK: u64 = 50;
+use crate::containers::{timeout_ticks, ContainerManager, PLAYER_WINDOW_ID};
+use crate::gui::{click_button, Button};
+use crate::listings::{parse_observed_listing, ObservedListing, RepriceTarget};
+use crate::price_parser::{encode_listing_price, format_price, Money};
+use crate::session::{GameSession, SlotItem, Slots, Window};
 
 // Delay after moving items in inventory
 const INVENTORY_MOVE_DELAY: u64 = 200;
@@ -17,44 +18,89 @@ const INVENTORY_MOVE_DELAY: u64 = 200;
 // Delay after selecting hotbar slot
 const HOTBAR_SELECTION_DELAY: u64 = 300;
 
-/// Check if an ItemStack contains a map
-/// 
-/// Note: Uses debug string matching as Azalea's item.kind doesn't expose direct enum comparison.
-/// This is a known limitation that may require updates if the debug format changes in future versions.
-fn is_map_item(item: &ItemStack) -> bool {
-    if let ItemStack::Present(data) = item {
-        let item_name = format!("{:?}", data.kind);
-        item_name.to_lowercase().contains("map")
-    } else {
-        false
-    }
+// Hotbar slot 0 in the player inventory menu; `/ah sell` lists the stack held there
+const HOTBAR_SLOT_0: usize = 36;
+
+/// Check if a slot contains a map
+fn is_map_item(item: &Option<SlotItem>) -> bool {
+    item.as_ref().is_some_and(SlotItem::is_map)
+}
+
+/// Whether a slot holds something that is neither a map nor on the keep-list
+fn is_junk(item: &Option<SlotItem>, keep_items: &[String]) -> bool {
+    item.as_ref().is_some_and(|item| !item.is_map() && !is_kept(&item.kind, keep_items))
 }
 
 pub struct MapSlot {
     pub slot: usize,
-    pub price: u32,
+    pub price: Money,
     pub seller: String,
+    pub map_id: Option<i32>,
+}
+
+/// How a purchase confirmation screen compares with the listing we picked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfirmCheck {
+    Matches,
+    /// No priced listing on the screen, or more than one; not a purchase confirmation
+    NoListing,
+    PriceChanged { shown: Money },
+    SellerChanged { shown: String },
+    /// A different map than the one scanned
+    OtherMap { shown: Option<i32> },
+}
+
+impl fmt::Display for ConfirmCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfirmCheck::Matches => write!(f, "matches the listing"),
+            ConfirmCheck::NoListing => write!(f, "shows no single priced listing"),
+            ConfirmCheck::PriceChanged { shown } => write!(f, "shows price {}", shown),
+            ConfirmCheck::SellerChanged { shown } => write!(f, "shows seller {}", shown),
+            ConfirmCheck::OtherMap { shown } => write!(f, "shows map id {:?}", shown),
+        }
+    }
+}
+
+/// A stack `list_maps` put up for sale
+pub struct ListedStack {
+    pub price: Money,
+    pub count: u32,
+    pub map_id: Option<i32>,
+    /// Inventory slot the stack was listed from
+    pub slot: usize,
+}
+
+/// A listing taken down by `cancel_listings`, and the inventory slot its maps came back to
+#[derive(Debug, Clone)]
+pub struct CancelledListing {
+    pub target: RepriceTarget,
+    pub slot: usize,
 }
 
 /// Parse price and seller from item lore
-pub fn parse_item_info(lore: &[String]) -> Option<(u32, String)> {
-    use crate::price_parser::{parse_price, strip_minecraft_colors};
+pub fn parse_item_info(lore: &[String]) -> Option<(Money, String)> {
+    use crate::price_parser::parse_price;
+    use crate::text::normalize;
     
     let mut price = None;
     let mut seller = String::from("unknown");
     
     for line in lore {
+        // Labels may be in small capitals ("ᴘʀɪᴄᴇ:"), which fold to lowercase
+        let clean = normalize(line);
+        let label = clean.to_lowercase();
+
         // Look for price
-        if line.contains("Price:") {
-            if let Some(p) = parse_price(line) {
+        if label.contains("price:") {
+            if let Some(p) = parse_price(&clean) {
                 price = Some(p);
             }
         }
         
         // Look for seller
-        if line.contains("Seller:") {
-            let clean = strip_minecraft_colors(line);
-            if let Some(seller_match) = clean.strip_prefix("Seller:") {
+        if label.trim_start().starts_with("seller:") {
+            if let Some((_, seller_match)) = clean.split_once(':') {
                 seller = seller_match.trim().to_string();
             }
         }
@@ -63,50 +109,22 @@ pub fn parse_item_info(lore: &[String]) -> Option<(u32, String)> {
     price.map(|p| (p, seller))
 }
 
-/// Extract lore text from an item
-/// In Minecraft 1.21.1+, lore is stored in components, not NBT
-pub fn extract_lore(item: &ItemStack) -> Vec<String> {
-    // Check if item is present and has lore component
-    if let Some(lore_component) = item.get_component::<Lore>() {
-        // Extract text from each lore line
-        lore_component.lines.iter()
-            .map(|formatted_text| {
-                // Convert FormattedText to plain string
-                format!("{}", formatted_text)
-            })
-            .collect()
-    } else {
-        vec![]
-    }
-}
-
 /// Open the auction house window
 ///
+/// The window is left open for `purchase_map` to click in.
+///
 /// Reference: bot.js lines 323-359
-pub async fn open_auction_house(bot: &Client, config: &Config) -> Result<Option<Menu>> {
+pub async fn open_auction_house(bot: &impl GameSession, config: &Config) -> Result<Option<Slots>> {
     println!("[AH] Opening auction house...");
-    
-    // Send the /ah map command
-    bot.chat("/ah map");
-    
-    // Wait for protocol timing (300ms to prevent "Invalid sequence" kick)
-    sleep(Duration::from_millis(300)).await;
-    
-    // Wait for container to open with timeout (convert ms to ticks: ms/50, round up)
-    let timeout_ticks = (config.window_timeout + MS_PER_TICK - 1) / MS_PER_TICK;
-    
     println!("[AH] Waiting for auction house window to open (timeout: {}ms)...", config.window_timeout);
     
-    // Use Azalea's wait_for_container_open function
-    match bot.wait_for_container_open(Some(timeout_ticks as usize)).await {
+    let mut windows = ContainerManager::new(bot, config);
+    match windows.open_with_command("/ah map").await {
         Some(container_handle) => {
-            // Get the menu from the container
-            if let Some(menu) = container_handle.menu() {
+            // Get the slots from the container
+            if let Some(slots) = container_handle.contents() {
                 println!("[AH] Auction house opened successfully");
-                // Don't close container - we need to keep it open to interact
-                // Drop the handle without closing by using std::mem::forget
-                std::mem::forget(container_handle);
-                Ok(Some(menu))
+                Ok(Some(slots))
             } else {
                 Err(anyhow!("Container opened but menu is not available"))
             }
@@ -117,68 +135,97 @@ pub async fn open_auction_house(bot: &Client, config: &Config) -> Result<Option<
     }
 }
 
-/// Find cheap maps in the auction house container
+/// Find cheap maps in the auction house container, skipping our own listings
 ///
 /// Reference: bot.js lines 361-436
-pub fn find_cheap_maps(menu: &Menu, max_price: u32) -> Option<MapSlot> {
-    println!("[AH] Scanning for cheap maps under ${}...", max_price);
-    
-    // Determine container size based on menu type
-    let container_size = match menu {
-        Menu::Generic9x6 { contents, .. } => contents.len(),
-        Menu::Generic9x5 { contents, .. } => contents.len(),
-        Menu::Generic9x4 { contents, .. } => contents.len(),
-        Menu::Generic9x3 { contents, .. } => contents.len(),
-        Menu::Generic9x2 { contents, .. } => contents.len(),
-        Menu::Generic9x1 { contents, .. } => contents.len(),
-        _ => {
-            println!("[AH] Unknown menu type, cannot scan");
-            return None;
-        }
-    };
+pub fn find_cheap_maps(slots: &[Option<SlotItem>], max_price: Money, own_name: &str) -> Option<MapSlot> {
+    println!("[AH] Scanning for cheap maps under {}...", max_price);
     
-    println!("[AH] Scanning {} container slots...", container_size);
+    // The container's own slots come first; the last 36 are the player inventory
+    let container_size = slots.len().saturating_sub(36);
+    if container_size == 0 {
+        println!("[AH] Not a container window, cannot scan");
+        return None;
+    }
     
-    // Get the actual slots from the menu
-    let slots = menu.slots();
+    println!("[AH] Scanning {} container slots...", container_size);
     
     // ONLY scan container slots, NOT player inventory
-    for slot_index in 0..container_size {
-        if slot_index >= slots.len() {
-            break;
-        }
-        
-        let item = &slots[slot_index];
-        
+    for (slot_index, item) in slots.iter().enumerate().take(container_size) {
         // Skip empty slots
-        if item.is_empty() {
+        let Some(item) = item else {
             continue;
-        }
-        
-        // Extract lore from item
-        let lore_lines = extract_lore(item);
+        };
         
-        if lore_lines.is_empty() {
+        if item.lore.is_empty() {
             continue;
         }
         
         // Parse price and seller from lore
-        if let Some((price, seller)) = parse_item_info(&lore_lines) {
+        if let Some((price, seller)) = parse_item_info(&item.lore) {
+            // Buying back our own (possibly discounted) relist would only pay the tax
+            if seller.eq_ignore_ascii_case(own_name) {
+                continue;
+            }
             if price < max_price {
-                println!("[AH] ✓ Found cheap map at slot {}: ${} (seller: {})", slot_index, price, seller);
+                println!("[AH] ✓ Found cheap map at slot {}: {} (seller: {})", slot_index, price, seller);
                 return Some(MapSlot {
                     slot: slot_index,
                     price,
                     seller,
+                    map_id: item.map_id,
                 });
             }
         }
     }
     
-    println!("[AH] No cheap maps found under ${}", max_price);
+    println!("[AH] No cheap maps found under {}", max_price);
     None
 }
 
+/// Cheapest price per map among other sellers' listings in the auction house window
+pub fn market_floor(slots: &[Option<SlotItem>], own_name: &str) -> Option<Money> {
+    let container_size = slots.len().saturating_sub(36);
+    
+    slots.iter()
+        .take(container_size)
+        .filter_map(|item| {
+            let item = item.as_ref()?;
+            let (price, seller) = parse_item_info(&item.lore)?;
+            if seller.eq_ignore_ascii_case(own_name) {
+                return None;
+            }
+            price.checked_div_ceil(item.count.max(1) as u64)
+        })
+        .min()
+}
+
+/// Compare the listing on a purchase confirmation screen with the one scanned
+///
+/// The seller is only compared when the screen names one; the price must always be shown.
+pub fn check_confirm_screen(slots: &[Option<SlotItem>], map: &MapSlot) -> ConfirmCheck {
+    let container_size = slots.len().saturating_sub(36);
+    let listings: Vec<(&SlotItem, Money, String)> = slots.iter()
+        .take(container_size)
+        .flatten()
+        .filter_map(|item| parse_item_info(&item.lore).map(|(price, seller)| (item, price, seller)))
+        .collect();
+    let [(item, price, seller)] = listings.as_slice() else {
+        return ConfirmCheck::NoListing;
+    };
+    
+    if *price != map.price {
+        return ConfirmCheck::PriceChanged { shown: *price };
+    }
+    if seller != "unknown" && !seller.eq_ignore_ascii_case(&map.seller) {
+        return ConfirmCheck::SellerChanged { shown: seller.clone() };
+    }
+    if map.map_id.is_some() && item.map_id.is_some() && item.map_id != map.map_id {
+        return ConfirmCheck::OtherMap { shown: item.map_id };
+    }
+    ConfirmCheck::Matches
+}
+
 /// Purchase a map from the auction house
 ///
 /// This function now properly handles the container ID change that happens when
@@ -186,59 +233,83 @@ pub fn find_cheap_maps(menu: &Menu, max_price: u32) -> Option<MapSlot> {
 /// 1. Click the map slot in the AH container (ID N)
 /// 2. Server closes AH container and opens confirm screen container (ID N+1)
 /// 3. Wait for the NEW container to open
-/// 4. Click confirm button in the NEW container
+/// 4. Check it shows the listing we picked, at the price we scanned
+/// 5. Click confirm button in the NEW container
+/// 6. Wait for the map to arrive in the inventory
+///
+/// Returns `Ok(false)` when `refused` is notified first, i.e. the server says the auction
+/// was already bought or we can't afford it, or when no map arrives within `windowTimeout`.
 ///
 /// Reference: bot.js lines 438-537
 pub async fn purchase_map(
-    bot: &Client,
+    bot: &impl GameSession,
     map: &MapSlot,
     config: &Config,
+    refused: &Notify,
 ) -> Result<bool> {
-    println!("[AH] Attempting to purchase map at slot {} for ${}...", map.slot, map.price);
+    println!("[AH] Attempting to purchase map at slot {} for {}...", map.slot, map.price);
     
-    // Get the current container ID before clicking
-    let initial_container = bot.get_inventory();
-    let initial_container_id = initial_container.id();
-    println!("[AH] Current container ID: {}", initial_container_id);
+    let mut windows = ContainerManager::new(bot, config);
+    let auction_house = windows.current()
+        .ok_or_else(|| anyhow!("Auction house window is no longer open"))?;
+    println!("[AH] Current container ID: {}", auction_house.id());
     
     // Step 1: Click the map slot in the auction house
     println!("[AH] Clicking map slot {}...", map.slot);
-    initial_container.left_click(map.slot as usize);
+    auction_house.left_click(map.slot);
     
     // Step 2: Wait for the NEW container to open (the confirm screen)
     // The server will close the current container and open a new one with incremented ID
     println!("[AH] Waiting for confirm screen to open...");
     
-    // Convert timeout from milliseconds to ticks (round up to ensure we wait long enough)
-    let timeout_ticks = (config.window_timeout + MS_PER_TICK - 1) / MS_PER_TICK;
-    
-    match bot.wait_for_container_open(Some(timeout_ticks as usize)).await {
+    match windows.await_next_window().await {
         Some(confirm_container) => {
             let confirm_container_id = confirm_container.id();
             println!("[AH] Confirm screen opened with container ID: {}", confirm_container_id);
             
-            // Verify the container ID actually changed
-            if confirm_container_id == initial_container_id {
-                println!("[AH] WARNING: Container ID did not change! This might cause issues.");
+            // Step 3: Make sure we'd pay for the listing we picked; the price may have
+            // changed, or the listing been replaced, since the auction house was scanned
+            let contents = confirm_container.contents();
+            let check = contents.as_deref()
+                .map_or(ConfirmCheck::NoListing, |slots| check_confirm_screen(slots, map));
+            if check != ConfirmCheck::Matches {
+                println!("[AH] ✗ Confirm screen {} (expected {} from {}) - not buying", check, map.price, map.seller);
+                windows.close();
+                return Err(anyhow!("Confirm screen {} - purchase aborted", check));
             }
             
-            // Step 3: Click the confirm button (slot 15) in the NEW container
-            println!("[AH] Clicking confirm button (slot 15) in container ID {}...", confirm_container_id);
-            confirm_container.left_click(15_usize);
+            // Listen for a refusal before confirming, so an instant reply isn't missed
+            let held_before = contents.as_deref().map_or(0, |slots| held_maps(confirm_container_id, slots));
+            let refusal = refused.notified();
+            tokio::pin!(refusal);
+            refusal.as_mut().enable();
             
-            // Keep the container handle alive without closing it
-            // We use forget() here because Azalea will close the container when handle is dropped,
-            // but the server may need time to process the purchase before we close it
-            std::mem::forget(confirm_container);
+            // Step 4: Click the confirm button in the NEW container, if we can tell which it is
+            println!("[AH] Clicking confirm button in container ID {}...", confirm_container_id);
+            if click_button(&confirm_container, Button::Confirm, &config.gui).is_none() {
+                windows.close();
+                return Err(anyhow!("No confirm button on the confirm screen - not buying"));
+            }
             
-            // Step 4: Wait for the window to close and purchase to complete
-            sleep(Duration::from_millis(2000)).await;
+            // Step 5: Only a map turning up in the inventory means we paid for one
+            let failure = tokio::select! {
+                _ = &mut refusal => Some("the server refused the purchase".to_string()),
+                arrived = await_more_maps(bot, held_before, config) => {
+                    (!arrived).then(|| format!("no map arrived within {}ms", config.window_timeout))
+                }
+            };
+            windows.close();
             
-            // For now, assume success
-            // TODO: In the future, add chat message monitoring to verify purchase
-            // We would need to listen for "already bought" or similar messages
-            println!("[AH] Purchase completed");
-            Ok(true)
+            match failure {
+                None => {
+                    println!("[AH] Purchase completed");
+                    Ok(true)
+                }
+                Some(reason) => {
+                    println!("[AH] ✗ Purchase not completed: {}", reason);
+                    Ok(false)
+                }
+            }
         }
         None => {
             println!("[AH] Timeout waiting for confirm screen to open ({}ms)", config.window_timeout);
@@ -247,6 +318,37 @@ pub async fn purchase_map(
     }
 }
 
+// How often to look for a bought map in the inventory (1 tick)
+const PURCHASE_POLL_MS: u64 = 50;
+
+/// Maps in the player's main inventory and hotbar, as shown by window `window_id`
+fn held_maps(window_id: i32, slots: &[Option<SlotItem>]) -> i32 {
+    let held = if window_id == PLAYER_WINDOW_ID {
+        &slots[ITEM_SLOTS.start.min(slots.len())..ITEM_SLOTS.end.min(slots.len())]
+    } else {
+        &slots[slots.len().saturating_sub(36)..]
+    };
+    held.iter()
+        .map(|slot| match slot {
+            Some(item) if item.is_map() => item.count,
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Wait up to `windowTimeout` for the inventory to hold more than `before` maps,
+/// whether or not the confirm screen is still open
+async fn await_more_maps(bot: &impl GameSession, before: i32, config: &Config) -> bool {
+    for _ in 0..=timeout_ticks(config) {
+        let window = bot.current_window();
+        if window.contents().is_some_and(|slots| held_maps(window.id(), &slots) > before) {
+            return true;
+        }
+        sleep(Duration::from_millis(PURCHASE_POLL_MS)).await;
+    }
+    false
+}
+
 /// Unstack all stacked maps in inventory into individual slots
 ///
 /// Minecraft maps can sometimes come in stacks (multiple maps in one slot).
@@ -259,7 +361,10 @@ pub async fn purchase_map(
 /// - Stack of 2: right-click picks up 1, leaves 1
 /// 
 /// Strategy: Repeatedly right-click stacks and place them in empty slots until all maps are singles.
-pub async fn unstack_maps(bot: &Client) -> Result<()> {
+///
+/// Unused since stacks are listed whole (see INVENTORY-FIX.md); kept for reference.
+#[allow(dead_code)]
+pub async fn unstack_maps(bot: &impl GameSession) -> Result<()> {
     println!("[INVENTORY] Checking for stacked maps...");
     
     let max_iterations = 100; // Safety limit to prevent infinite loops
@@ -273,17 +378,15 @@ pub async fn unstack_maps(bot: &Client) -> Result<()> {
         }
         
         // Get a fresh inventory handle to check current state
-        let inventory_handle = bot.get_inventory();
+        let inventory_handle = bot.current_window();
         
-        let stacked_slot = if let Some(menu) = inventory_handle.menu() {
-            let slots = menu.slots();
-            
+        let stacked_slot = if let Some(slots) = inventory_handle.contents() {
             // Find the first stacked map (count > 1)
             let mut found = None;
             for (idx, slot) in slots.iter().enumerate() {
-                if let ItemStack::Present(data) = slot {
-                    if is_map_item(slot) && data.count > 1 {
-                        found = Some((idx, data.count));
+                if let Some(item) = slot {
+                    if item.is_map() && item.count > 1 {
+                        found = Some((idx, item.count));
                         break;
                     }
                 }
@@ -304,12 +407,11 @@ pub async fn unstack_maps(bot: &Client) -> Result<()> {
         
         // Find an empty slot in main inventory (9-35) or hotbar (36-44)
         // NEVER use slots 0-8 (crafting output + grid, armor) or 45 (offhand)
-        let inv_handle = bot.get_inventory();
-        let empty_slot = if let Some(menu) = inv_handle.menu() {
-            let slots = menu.slots();
+        let inv_handle = bot.current_window();
+        let empty_slot = if let Some(slots) = inv_handle.contents() {
             // Only check slots 9-44 (main inventory + hotbar), but respect actual slot count
             (9..slots.len().min(45))
-                .find(|&idx| slots[idx].is_empty())
+                .find(|&idx| slots[idx].is_none())
         } else {
             None
         };
@@ -323,7 +425,7 @@ pub async fn unstack_maps(bot: &Client) -> Result<()> {
                 sleep(Duration::from_millis(300)).await;
                 
                 // Get a fresh handle and left-click empty slot to place the half
-                let inv_handle2 = bot.get_inventory();
+                let inv_handle2 = bot.current_window();
                 println!("[INVENTORY] Left-clicking empty slot {} to place...", empty_idx);
                 inv_handle2.left_click(empty_idx);
                 
@@ -331,12 +433,11 @@ pub async fn unstack_maps(bot: &Client) -> Result<()> {
                 sleep(Duration::from_millis(300)).await;
                 
                 // Verify the split actually worked by checking the original slot count
-                let verify_handle = bot.get_inventory();
-                if let Some(menu) = verify_handle.menu() {
-                    let slots = menu.slots();
+                let verify_handle = bot.current_window();
+                if let Some(slots) = verify_handle.contents() {
                     if stack_slot < slots.len() {
-                        if let ItemStack::Present(data) = &slots[stack_slot] {
-                            let new_count = data.count;
+                        if let Some(item) = &slots[stack_slot] {
+                            let new_count = item.count;
                             if new_count == count {
                                 println!("[INVENTORY] WARNING: Split operation failed - count didn't change (still {})", count);
                                 println!("[INVENTORY] Server rejected the click - operation will be retried in next loop iteration");
@@ -370,39 +471,36 @@ pub async fn unstack_maps(bot: &Client) -> Result<()> {
 /// 1. Find all map slots (including stacks)
 /// 2. For each slot, move to hotbar and list the entire stack at calculated price
 /// 3. Price calculation: base_price × count × 0.5
-pub async fn list_maps(bot: &Client, config: &Config, slots_to_list: &[usize]) -> Result<()> {
+///
+/// `unit_prices` replaces the configured sell price for the stacks in particular inventory
+/// slots (e.g. discounted relists). Listing stops as soon as `cap_reached` is notified, i.e.
+/// when the server says we're at its listing cap. Returns the stacks that were listed
+/// successfully.
+pub async fn list_maps(
+    bot: &impl GameSession,
+    config: &Config,
+    slots_to_list: &[usize],
+    unit_prices: &HashMap<usize, Money>,
+    cap_reached: &Notify,
+) -> Result<Vec<ListedStack>> {
     if slots_to_list.is_empty() {
         println!("[LISTING] No maps to list");
-        return Ok(());
+        return Ok(Vec::new());
     }
     
     println!("[LISTING] Starting to list maps (listing stacks without unstacking)...");
     
-    // Parse the base sell price from config (e.g., "9.9k" -> 9900)
-    let base_price_str = format!("${}", config.sell_price);
-    let base_price = match parse_price(&base_price_str) {
-        Some(price) => price,
-        None => {
-            return Err(anyhow!("Failed to parse sell price from config: {}", config.sell_price));
-        }
-    };
-    println!("[LISTING] Base single map price: ${} ({})", base_price, config.sell_price);
+    // Base sell price from config (already parsed, e.g. "9.9k" -> $9,900)
+    let base_price = config.sell_price;
+    println!("[LISTING] Base single map price: {} ({})", base_price, format_price(base_price));
     
     // Get fresh inventory snapshot
-    let inv = bot.get_inventory();
-    let map_slots: Vec<(usize, i32)> = if let Some(menu) = inv.menu() {
-        let slots = menu.slots();
+    let inv = bot.current_window();
+    let map_slots: Vec<(usize, i32, Option<i32>)> = if let Some(slots) = inv.contents() {
         slots.iter().enumerate()
-            .filter_map(|(idx, slot)| {
-                if is_map_item(slot) {
-                    if let ItemStack::Present(data) = slot {
-                        Some((idx, data.count))
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
+            .filter_map(|(idx, slot)| match slot {
+                Some(item) if item.is_map() => Some((idx, item.count, item.map_id)),
+                _ => None,
             })
             .collect()
     } else {
@@ -411,61 +509,62 @@ pub async fn list_maps(bot: &Client, config: &Config, slots_to_list: &[usize]) -
     
     if map_slots.is_empty() {
         println!("[LISTING] No maps found in inventory");
-        return Ok(());
+        return Ok(Vec::new());
     }
     
     println!("[LISTING] Found {} map slot(s)", map_slots.len());
     
-    // Track how many listings we've made
-    let mut listings_made = 0;
+    // Track the listings we've made
+    let mut listed = Vec::new();
     let max_listings = config.max_listings_per_cycle as usize;
+    let mut windows = ContainerManager::new(bot, config);
     
     // List each stack
-    for (slot_idx, stack_count) in map_slots {
-        if listings_made >= max_listings {
+    for (slot_idx, stack_count, stack_map_id) in map_slots {
+        if listed.len() >= max_listings {
             println!("[LISTING] Reached max listings per cycle ({})", max_listings);
             break;
         }
         
         println!("[LISTING] Processing slot {} with {} map(s)...", slot_idx, stack_count);
         
+        let base_price = match unit_prices.get(&slot_idx) {
+            Some(&price) => {
+                println!("[LISTING] Using relist price {} for slot {}", price, slot_idx);
+                price
+            }
+            None => base_price,
+        };
+        
         // Calculate fair price for the stack: base_price × count × 0.5
-        // Using integer arithmetic to avoid floating-point precision issues
-        let stack_price = (base_price * stack_count as u32) / 2;
-        let price_str = format_price(stack_price);
+        // Using integer arithmetic, rounding the half up so we never list below it
+        let stack_price = base_price.checked_mul(stack_count.max(0) as u64)
+            .and_then(|total| total.checked_div_ceil(2))
+            .ok_or_else(|| anyhow!("Stack price overflow for {} maps at {}", stack_count, base_price))?;
+        let listing_price = encode_listing_price(stack_price, config.compact_listing_prices);
+        let price_str = &listing_price.text;
         
-        println!("[LISTING] Stack of {} maps: ${} each × {} × 0.5 = ${} total ({})", 
+        println!("[LISTING] Stack of {} maps: {} each × {} × 0.5 = {} total ({})", 
                  stack_count, base_price, stack_count, stack_price, price_str);
+        if listing_price.surplus() > Money::ZERO {
+            println!("[LISTING] Rounded up to {} for a compact price (+{})",
+                     listing_price.listed, listing_price.surplus());
+        }
         
         // Move stack to hotbar slot 0
-        const HOTBAR_SLOT_0: usize = 36;
         if slot_idx != HOTBAR_SLOT_0 {
             println!("[LISTING] Moving stack from slot {} to hotbar slot 0...", slot_idx);
-            
-            // Log before first click
-            println!("[INVENTORY DEBUG] About to left-click slot {} (pickup stack)", slot_idx);
-            let inv_handle = bot.get_inventory();
-            let window_id = inv_handle.id();
-            println!("[INVENTORY DEBUG] Window ID: {}", window_id);
-            inv_handle.left_click(slot_idx);
-            sleep(Duration::from_millis(200)).await;
-            
-            // Log before second click
-            println!("[INVENTORY DEBUG] About to left-click slot {} (place stack)", HOTBAR_SLOT_0);
-            let inv_handle2 = bot.get_inventory();
-            inv_handle2.left_click(HOTBAR_SLOT_0);
-            sleep(Duration::from_millis(200)).await;
+            move_to_hotbar(bot, slot_idx).await;
             
             // Verify the move
-            let verify_handle = bot.get_inventory();
-            if let Some(menu) = verify_handle.menu() {
-                let slots = menu.slots();
+            let verify_handle = bot.current_window();
+            if let Some(slots) = verify_handle.contents() {
                 if HOTBAR_SLOT_0 < slots.len() {
                     match &slots[HOTBAR_SLOT_0] {
-                        ItemStack::Present(data) if is_map_item(&slots[HOTBAR_SLOT_0]) => {
-                            println!("[INVENTORY DEBUG] ✓ Verified: {} maps now in hotbar slot 0", data.count);
+                        Some(item) if item.is_map() => {
+                            println!("[INVENTORY DEBUG] ✓ Verified: {} maps now in hotbar slot 0", item.count);
                         }
-                        ItemStack::Empty => {
+                        None => {
                             println!("[INVENTORY DEBUG] ✗ WARNING: Hotbar slot 0 is empty after move!");
                         }
                         _ => {
@@ -480,45 +579,56 @@ pub async fn list_maps(bot: &Client, config: &Config, slots_to_list: &[usize]) -
         
         // Select hotbar slot 0 to hold the stack
         println!("[LISTING] Selecting hotbar slot 0...");
-        bot.set_selected_hotbar_slot(0);
+        bot.select_hotbar_slot(0);
         sleep(Duration::from_millis(300)).await;
         
         // Log what we're about to list
-        let pre_list_inv = bot.get_inventory();
-        if let Some(menu) = pre_list_inv.menu() {
-            let slots = menu.slots();
+        let pre_list_inv = bot.current_window();
+        if let Some(slots) = pre_list_inv.contents() {
             if HOTBAR_SLOT_0 < slots.len() {
                 match &slots[HOTBAR_SLOT_0] {
-                    ItemStack::Present(data) if is_map_item(&slots[HOTBAR_SLOT_0]) => {
-                        println!("[LISTING DEBUG] Holding {} maps in selected hotbar slot 0", data.count);
+                    Some(item) if item.is_map() => {
+                        println!("[LISTING DEBUG] Holding {} maps in selected hotbar slot 0", item.count);
                     }
-                    ItemStack::Empty => {
+                    None => {
                         println!("[LISTING DEBUG] ✗ ERROR: Hotbar slot 0 is EMPTY before command!");
                     }
-                    ItemStack::Present(data) => {
-                        println!("[LISTING DEBUG] ✗ ERROR: Holding wrong item: {:?}", data.kind);
+                    Some(item) => {
+                        println!("[LISTING DEBUG] ✗ ERROR: Holding wrong item: {}", item.kind);
                     }
                 }
             }
         }
         
+        // Listen for the cap message before sending, so an instant reply isn't missed
+        let cap_message = cap_reached.notified();
+        tokio::pin!(cap_message);
+        cap_message.as_mut().enable();
+        
         // Send /ah sell command with calculated price for the stack
         let command = format!("/ah sell {}", price_str);
         println!("[LISTING] Sending command: {}", command);
         bot.chat(&command);
-        sleep(Duration::from_millis(500)).await;
         
-        // Wait for confirmation window
-        let timeout_ticks = (config.window_timeout + 50 - 1) / 50;
+        // Wait for confirmation window, unless the server refuses the listing outright
         println!("[LISTING] Waiting for confirmation window (timeout: {}ms)...", config.window_timeout);
-        match bot.wait_for_container_open(Some(timeout_ticks as usize)).await {
+        let confirm_window = tokio::select! {
+            _ = &mut cap_message => {
+                println!("[LISTING] ✗ Server listing cap reached - stopping after {} listing(s)", listed.len());
+                break;
+            }
+            window = async {
+                sleep(Duration::from_millis(500)).await;
+                windows.await_next_window().await
+            } => window,
+        };
+        match confirm_window {
             Some(confirm_container) => {
                 let container_id = confirm_container.id();
                 println!("[LISTING] ✓ Confirmation window opened (container ID: {})", container_id);
                 
                 // Log what's in the confirmation window
-                if let Some(menu) = confirm_container.menu() {
-                    let slots = menu.slots();
+                if let Some(slots) = confirm_container.contents() {
                     println!("[LISTING DEBUG] Confirmation window has {} slots", slots.len());
                     // The item to be listed should be visible in the confirmation window
                     // Usually in a specific slot depending on server implementation
@@ -527,30 +637,31 @@ pub async fn list_maps(bot: &Client, config: &Config, slots_to_list: &[usize]) -
                 // Wait before clicking confirm to avoid spam kick
                 sleep(Duration::from_millis(300)).await;
                 
-                // Click confirm button (slot 15)
-                println!("[LISTING] Clicking confirm button (slot 15)...");
-                println!("[INVENTORY DEBUG] Container {}: left-click slot 15 (confirm)", container_id);
-                confirm_container.left_click(15_usize);
-                
-                // Forget the handle to prevent early closure
-                std::mem::forget(confirm_container);
+                // Click confirm button
+                println!("[LISTING] Clicking confirm button...");
+                if click_button(&confirm_container, Button::Confirm, &config.gui).is_none() {
+                    println!("[LISTING] ✗ No confirm button on the confirmation window - stopping");
+                    windows.close();
+                    break;
+                }
                 
-                // Wait for the window to close and server to update inventory
+                // Wait for the window to close and server to update inventory,
+                // then make sure it is closed so the hotbar below is ours again
                 sleep(Duration::from_millis(1000)).await;
+                windows.close();
                 
                 // Verify listing by checking if slot is now empty or changed
-                let verify_inv = bot.get_inventory();
+                let verify_inv = bot.current_window();
                 let mut listing_success = false;
-                if let Some(menu) = verify_inv.menu() {
-                    let slots = menu.slots();
+                if let Some(slots) = verify_inv.contents() {
                     if HOTBAR_SLOT_0 < slots.len() {
                         match &slots[HOTBAR_SLOT_0] {
-                            ItemStack::Empty => {
+                            None => {
                                 println!("[LISTING] ✓ Slot now empty - stack listed successfully");
                                 listing_success = true;
                             }
-                            ItemStack::Present(data) if is_map_item(&slots[HOTBAR_SLOT_0]) => {
-                                let remaining = data.count;
+                            Some(item) if item.is_map() => {
+                                let remaining = item.count;
                                 if remaining < stack_count {
                                     // Partial listing occurred - this might indicate server only listed some maps
                                     // This could happen if /ah sell command lists ONE map at a time instead of the whole stack
@@ -570,8 +681,13 @@ pub async fn list_maps(bot: &Client, config: &Config, slots_to_list: &[usize]) -
                 }
                 
                 if listing_success {
-                    listings_made += 1;
-                    println!("[LISTING] ✓ Successfully listed stack {} / {}", listings_made, max_listings);
+                    listed.push(ListedStack {
+                        price: listing_price.listed,
+                        count: stack_count.max(0) as u32,
+                        map_id: stack_map_id,
+                        slot: slot_idx,
+                    });
+                    println!("[LISTING] ✓ Successfully listed stack {} / {}", listed.len(), max_listings);
                 } else {
                     println!("[LISTING] ✗ Listing verification failed - skipping");
                 }
@@ -585,28 +701,393 @@ pub async fn list_maps(bot: &Client, config: &Config, slots_to_list: &[usize]) -
         }
     }
     
-    println!("[LISTING] Finished - listed {} stack(s)", listings_made);
-    Ok(())
+    println!("[LISTING] Finished - listed {} stack(s)", listed.len());
+    Ok(listed)
+}
+
+/// Pick up the stack in `slot` and put it down in hotbar slot 0
+async fn move_to_hotbar(bot: &impl GameSession, slot: usize) {
+    // Log before first click
+    println!("[INVENTORY DEBUG] About to left-click slot {} (pickup stack)", slot);
+    let inv_handle = bot.current_window();
+    println!("[INVENTORY DEBUG] Window ID: {}", inv_handle.id());
+    inv_handle.left_click(slot);
+    sleep(Duration::from_millis(INVENTORY_MOVE_DELAY)).await;
+    
+    // Log before second click
+    println!("[INVENTORY DEBUG] About to left-click slot {} (place stack)", HOTBAR_SLOT_0);
+    bot.current_window().left_click(HOTBAR_SLOT_0);
+    sleep(Duration::from_millis(INVENTORY_MOVE_DELAY)).await;
+}
+
+/// List each stack in `slots` on the auction house for `price` per stack
+///
+/// Same flow as `list_maps` (move to hotbar slot 0, `/ah sell`, confirm), for
+/// items that aren't maps. Returns the number of stacks listed.
+pub async fn list_items(bot: &impl GameSession, config: &Config, slots: &[usize], price: Money) -> u32 {
+    let price_text = encode_listing_price(price, config.compact_listing_prices).text;
+    let mut listed = 0;
+    let mut windows = ContainerManager::new(bot, config);
+    
+    for &slot in slots {
+        if slot != HOTBAR_SLOT_0 {
+            move_to_hotbar(bot, slot).await;
+        }
+        bot.select_hotbar_slot(0);
+        sleep(Duration::from_millis(HOTBAR_SELECTION_DELAY)).await;
+        
+        let command = format!("/ah sell {}", price_text);
+        println!("[JUNK] Sending command: {}", command);
+        let Some(confirm_container) = windows.open_with_command(&command).await else {
+            println!("[JUNK] ✗ Confirmation window did not open for slot {}, stopping", slot);
+            break;
+        };
+        sleep(Duration::from_millis(300)).await;
+        if click_button(&confirm_container, Button::Confirm, &config.gui).is_none() {
+            println!("[JUNK] ✗ No confirm button on the confirmation window, stopping");
+            windows.close();
+            break;
+        }
+        sleep(Duration::from_millis(1000)).await;
+        windows.close();
+        
+        let sold = bot.current_window().contents()
+            .is_some_and(|slots| slots.get(HOTBAR_SLOT_0).is_some_and(Option::is_none));
+        if sold {
+            listed += 1;
+            println!("[JUNK] ✓ Listed stack from slot {} for {}", slot, price_text);
+        } else {
+            println!("[JUNK] ✗ Stack from slot {} is still held - listing failed", slot);
+        }
+        
+        sleep(Duration::from_millis(config.delay_between_listings)).await;
+    }
+    
+    listed
+}
+
+/// Throw each stack in `slots` out of the inventory; returns the number of stacks dropped
+pub async fn drop_items(bot: &impl GameSession, slots: &[usize]) -> u32 {
+    let mut dropped = 0;
+    
+    for &slot in slots {
+        let inv_handle = bot.current_window();
+        println!("[INVENTORY DEBUG] Window {}: throw slot {}", inv_handle.id(), slot);
+        inv_handle.throw_stack(slot);
+        sleep(Duration::from_millis(INVENTORY_MOVE_DELAY)).await;
+        
+        let emptied = bot.current_window().contents()
+            .is_some_and(|slots| slots.get(slot).is_some_and(Option::is_none));
+        if emptied {
+            dropped += 1;
+        } else {
+            println!("[JUNK] ✗ Slot {} did not empty - server rejected the drop", slot);
+        }
+    }
+    
+    dropped
+}
+
+/// Number of empty main inventory and hotbar slots in a window whose last 36 slots are the player's
+fn free_player_slots(slots: &[Option<SlotItem>]) -> usize {
+    let start = slots.len().saturating_sub(36);
+    slots[start..].iter().filter(|item| item.is_none()).count()
+}
+
+/// Collect expired listings back into the inventory
+///
+/// Opens the expired/collect view and clicks each map in it, one at a time and with the
+/// same pauses as `list_maps`, checking after every click that the slot emptied.
+/// Returns the map id (if any) of every map reclaimed.
+pub async fn reclaim_expired(bot: &impl GameSession, config: &Config) -> Result<Vec<Option<i32>>> {
+    println!("[RECLAIM] Opening expired items with {}...", config.expired_command);
+    
+    let mut windows = ContainerManager::new(bot, config);
+    let reclaimed = windows.with_window(&config.expired_command, |container| async move {
+        let mut reclaimed = Vec::new();
+        // One click per slot at most, plus a little slack for retries
+        let max_clicks = 60;
+        
+        for _ in 0..max_clicks {
+            let Some(slots) = container.contents() else {
+                println!("[RECLAIM] Window closed, stopping");
+                break;
+            };
+            if free_player_slots(&slots) == 0 {
+                println!("[RECLAIM] Inventory is full, leaving the rest for later");
+                break;
+            }
+            
+            let container_size = slots.len().saturating_sub(36);
+            let Some((slot, item)) = slots.into_iter()
+                .take(container_size)
+                .enumerate()
+                .find(|(_, item)| is_map_item(item))
+            else {
+                break;
+            };
+            
+            println!("[INVENTORY DEBUG] Container {}: left-click slot {} (reclaim)", container.id(), slot);
+            container.left_click(slot);
+            sleep(Duration::from_millis(INVENTORY_MOVE_DELAY)).await;
+            
+            // Verify the map left the expired view before moving on
+            let still_there = container.contents()
+                .map(|slots| slots.get(slot).is_some_and(|now| now == &item))
+                .unwrap_or(false);
+            if still_there {
+                println!("[RECLAIM] ✗ Slot {} did not empty - server rejected the click, stopping", slot);
+                break;
+            }
+            
+            println!("[RECLAIM] ✓ Reclaimed map from slot {}", slot);
+            reclaimed.push(item.and_then(|item| item.map_id));
+            
+            // Wait before the next click to avoid a spam kick
+            sleep(Duration::from_millis(300)).await;
+        }
+        reclaimed
+    }).await
+        .ok_or_else(|| anyhow!("Timeout waiting for expired items window ({}ms)", config.window_timeout))?;
+    
+    println!("[RECLAIM] Reclaimed {} map(s)", reclaimed.len());
+    Ok(reclaimed)
+}
+
+// How long to wait for an optional confirmation screen after clicking a listing (2s)
+const CANCEL_CONFIRM_TICKS: usize = 40;
+
+/// Take listings down through the listings view so their maps come back to the inventory
+///
+/// Each target is found by its price and, if it has one, its map id, and clicked. If the
+/// server asks for confirmation, its confirm button is clicked too. The view is
+/// reopened for every listing because a click may close it. Returns the targets whose maps
+/// came back, with the inventory slot each landed in.
+pub async fn cancel_listings(bot: &impl GameSession, config: &Config, targets: &[RepriceTarget]) -> Result<Vec<CancelledListing>> {
+    let mut cancelled = Vec::new();
+    let mut windows = ContainerManager::new(bot, config);
+    
+    for target in targets {
+        if inventory_model(bot).free_slots == 0 {
+            println!("[REPRICE] Inventory is full, stopping cancellations");
+            break;
+        }
+        let maps_before = map_counts(bot);
+        
+        let container = windows.open_with_command(&config.listings_command).await
+            .ok_or_else(|| anyhow!("Timeout waiting for listings window ({}ms)", config.window_timeout))?;
+        let Some(slots) = container.contents() else {
+            windows.close();
+            continue;
+        };
+        
+        let container_size = slots.len().saturating_sub(36);
+        let slot = slots.iter()
+            .take(container_size)
+            .position(|item| match item {
+                // Map ids can repeat across listings, so the price has to match as well
+                Some(item) if item.is_map() => item.map_id == target.map_id
+                    && parse_observed_listing(&item.lore, None).is_some_and(|seen| seen.price == target.old_price),
+                _ => false,
+            });
+        let Some(slot) = slot else {
+            println!("[REPRICE] Listing at {} is no longer shown (sold or expired)", target.old_price);
+            windows.close();
+            continue;
+        };
+        
+        println!("[INVENTORY DEBUG] Container {}: left-click slot {} (cancel listing)", container.id(), slot);
+        container.left_click(slot);
+        sleep(Duration::from_millis(300)).await;
+        
+        // Some servers ask before taking a listing down
+        if let Some(confirm) = windows.await_next_window_within(CANCEL_CONFIRM_TICKS).await {
+            sleep(Duration::from_millis(300)).await;
+            if click_button(&confirm, Button::Confirm, &config.gui).is_some() {
+                sleep(Duration::from_millis(1000)).await;
+            }
+        }
+        windows.close();
+        
+        let maps_after = map_counts(bot);
+        let returned_to = (0..maps_after.len())
+            .find(|&i| maps_after[i] > maps_before.get(i).copied().unwrap_or(0));
+        if let Some(slot) = returned_to {
+            println!("[REPRICE] ✓ Took down listing at {} (back in slot {})", target.old_price, slot);
+            cancelled.push(CancelledListing { target: target.clone(), slot });
+        } else {
+            println!("[REPRICE] ✗ Listing at {} did not come back to the inventory", target.old_price);
+        }
+        
+        sleep(Duration::from_millis(config.delay_between_listings)).await;
+    }
+    
+    Ok(cancelled)
+}
+
+/// Move every map in the inventory into the ender chest
+pub async fn store_maps(bot: &impl GameSession, config: &Config) -> Result<u32> {
+    store_items(bot, config, "map", is_map_item).await
+}
+
+/// Move every junk stack (see `keepItems`) into the ender chest
+pub async fn store_junk(bot: &impl GameSession, config: &Config) -> Result<u32> {
+    store_items(bot, config, "junk", |item| is_junk(item, &config.keep_items)).await
+}
+
+/// Move the inventory stacks matching `wanted` into the ender chest, opened with
+/// `enderChestCommand`
+///
+/// Stacks are shift-clicked across one at a time; if one stays put the chest is full and
+/// the rest are left where they are. Returns the number of stacks moved.
+async fn store_items(bot: &impl GameSession, config: &Config, what: &str, wanted: impl Fn(&Option<SlotItem>) -> bool) -> Result<u32> {
+    println!("[STORAGE] Opening ender chest with {}...", config.ender_chest_command);
+    
+    let mut windows = ContainerManager::new(bot, config);
+    let moved = windows.with_window(&config.ender_chest_command, |container| async move {
+        let contents = container.contents()
+            .ok_or_else(|| anyhow!("Ender chest opened but menu is not available"))?;
+        
+        // Our inventory is the last 36 slots of the chest menu
+        let container_size = contents.len().saturating_sub(36);
+        let slots: Vec<usize> = contents.iter()
+            .enumerate()
+            .skip(container_size)
+            .filter(|(_, item)| wanted(item))
+            .map(|(slot, _)| slot)
+            .collect();
+        
+        let mut moved = 0;
+        for slot in slots {
+            println!("[INVENTORY DEBUG] Container {}: shift-click slot {} (store)", container.id(), slot);
+            container.shift_click(slot);
+            sleep(Duration::from_millis(INVENTORY_MOVE_DELAY)).await;
+            
+            let stored = container.contents()
+                .is_some_and(|slots| slots.get(slot).is_some_and(Option::is_none));
+            if !stored {
+                println!("[STORAGE] ✗ Slot {} did not empty - ender chest is full, stopping", slot);
+                break;
+            }
+            moved += 1;
+        }
+        Ok::<_, anyhow::Error>(moved)
+    }).await
+        .ok_or_else(|| anyhow!("Timeout waiting for ender chest window ({}ms)", config.window_timeout))??;
+    
+    println!("[STORAGE] Stored {} {} stack(s) in the ender chest", moved, what);
+    Ok(moved)
+}
+
+/// Open the server's view of our own listings and read price and expiry from each
+///
+/// The window is closed again afterwards.
+pub async fn read_my_listings(bot: &impl GameSession, config: &Config) -> Result<Vec<ObservedListing>> {
+    println!("[LISTINGS] Opening own listings with {}...", config.listings_command);
+    
+    let mut windows = ContainerManager::new(bot, config);
+    let slots = windows.with_window(&config.listings_command, |container| async move { container.contents() })
+        .await
+        .ok_or_else(|| anyhow!("Timeout waiting for listings window ({}ms)", config.window_timeout))?
+        .ok_or_else(|| anyhow!("Listings window opened but menu is not available"))?;
+    
+    // Only the container part; the rest of the menu is our own inventory
+    let container_size = slots.len().saturating_sub(36);
+    let observed: Vec<ObservedListing> = slots.iter()
+        .take(container_size)
+        .flatten()
+        .filter(|item| item.is_map())
+        .filter_map(|item| parse_observed_listing(&item.lore, item.map_id))
+        .collect();
+    
+    println!("[LISTINGS] Found {} listing(s) in the listings window", observed.len());
+    Ok(observed)
 }
 
 /// Get a snapshot of which inventory slots contain maps
 ///
 /// This is used to track which maps are new after a purchase
-pub fn get_map_slots(bot: &Client) -> Vec<usize> {
-    let inventory_handle = bot.get_inventory();
-    let mut map_slots = Vec::new();
+pub fn get_map_slots(bot: &impl GameSession) -> Vec<usize> {
+    find_slots(bot, |_, slot| is_map_item(slot))
+}
+
+/// Main inventory and hotbar slots holding junk: anything that isn't a map or in `keepItems`
+///
+/// Armour and the offhand are never included.
+pub fn get_junk_slots(bot: &impl GameSession, config: &Config) -> Vec<usize> {
+    find_slots(bot, |idx, slot| ITEM_SLOTS.contains(&idx) && is_junk(slot, &config.keep_items))
+}
+
+/// Indices of the occupied inventory slots that `wanted` accepts
+fn find_slots(bot: &impl GameSession, wanted: impl Fn(usize, &Option<SlotItem>) -> bool) -> Vec<usize> {
+    let inventory_handle = bot.current_window();
+    let mut found = Vec::new();
     
-    if let Some(menu) = inventory_handle.menu() {
-        let slots = menu.slots();
-        
+    if let Some(slots) = inventory_handle.contents() {
         for (idx, slot) in slots.iter().enumerate() {
-            if slot.is_present() && is_map_item(slot) {
-                map_slots.push(idx);
+            if slot.is_some() && wanted(idx, slot) {
+                found.push(idx);
             }
         }
     }
     
-    map_slots
+    found
+}
+
+fn slot_content(item: &Option<SlotItem>) -> SlotContent {
+    match item {
+        None => SlotContent::Empty,
+        Some(item) if item.is_map() => SlotContent::Map { count: item.count.max(0) as u32 },
+        Some(_) => SlotContent::Other,
+    }
+}
+
+/// Free slots and map stacks in the main inventory and hotbar
+pub fn inventory_model(bot: &impl GameSession) -> InventoryModel {
+    let slots: Vec<SlotContent> = bot.current_window().contents()
+        .map(|slots| slots.iter().map(slot_content).collect())
+        .unwrap_or_default();
+    InventoryModel::from_slots(&slots)
+}
+
+/// Inventory slots of the maps with these ids, each with the price for its id
+pub fn prices_by_slot(bot: &impl GameSession, by_map_id: &HashMap<i32, Money>) -> HashMap<usize, Money> {
+    bot.current_window().contents()
+        .map(|slots| slots.iter().enumerate()
+            .filter_map(|(idx, slot)| match slot {
+                Some(item) if item.is_map() => Some((idx, *by_map_id.get(&item.map_id?)?)),
+                _ => None,
+            })
+            .collect())
+        .unwrap_or_default()
+}
+
+/// Number of maps in each slot of the open window
+fn map_counts(bot: &impl GameSession) -> Vec<i32> {
+    bot.current_window().contents()
+        .map(|slots| slots.iter()
+            .map(|slot| match slot {
+                Some(item) if item.is_map() => item.count,
+                _ => 0,
+            })
+            .collect())
+        .unwrap_or_default()
+}
+
+/// Count the total number of maps in the inventory, including every map in a stack
+pub fn count_maps(bot: &impl GameSession) -> u32 {
+    let inventory_handle = bot.current_window();
+
+    if let Some(slots) = inventory_handle.contents() {
+        slots.iter()
+            .filter_map(|slot| match slot {
+                Some(item) if item.is_map() => Some(item.count.max(0) as u32),
+                _ => None,
+            })
+            .sum()
+    } else {
+        0
+    }
 }
 
 /*
@@ -649,3 +1130,242 @@ pub fn get_map_slots(bot: &Client) -> Vec<usize> {
  * - Must listen to window_items and set_slot packets
  * - See bot.js lines 161-216 for implementation pattern
  */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::fake::{auction, chest, confirm_screen, map, Action, FakeSession, Reaction};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_parse_item_info_styled_lore() {
+        let lore = |lines: &[&str]| lines.iter().map(|line| line.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            parse_item_info(&lore(&["§7Price: §a$2,000", "§7Seller: §fbob"])),
+            Some((Money::new(2000), "bob".to_string()))
+        );
+        assert_eq!(
+            parse_item_info(&lore(&["&#AAAAAAᴘʀɪᴄᴇ: §x§5§5§f§f§5§5$９.９ᴋ", "ꜱᴇʟʟᴇʀ: Steve"])),
+            Some((Money::new(9900), "Steve".to_string()))
+        );
+        assert_eq!(parse_item_info(&lore(&["Seller: bob"])), None);
+    }
+
+    /// `/ah sell` opens a confirm screen; confirming takes the held stack
+    fn accept_listings(bot: &FakeSession) {
+        bot.on_command("/ah sell", vec![Reaction::Open { label: "sell".to_string(), slots: confirm_screen(None) }])
+            .on_click("sell", 15, vec![Reaction::TakeHeld, Reaction::Close]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_purchase_flow() {
+        let config = Config::default();
+        let bot = FakeSession::new(vec![]);
+        let listings = vec![
+            (0, auction(1, "$3,000", "alice")),
+            (2, auction(3, "$1,500", "FlipperBot")),
+            (4, auction(2, "$2,000", "bob")),
+        ];
+        bot.on_command("/ah map", vec![Reaction::Open { label: "ah".to_string(), slots: chest(6, listings) }])
+            .on_click("ah", 4, vec![Reaction::Open { label: "confirm".to_string(), slots: confirm_screen(Some(auction(2, "$2,000", "bob"))) }])
+            .on_click("confirm", 15, vec![Reaction::Give(map(2, 1)), Reaction::Close]);
+
+        let slots = open_auction_house(&bot, &config).await.unwrap().unwrap();
+        assert_eq!(slots.len(), 54 + 36);
+        // Our own listing neither sets the floor nor gets bought back
+        assert_eq!(market_floor(&slots, "FlipperBot"), Some(Money::new(2000)));
+
+        let cheap = find_cheap_maps(&slots, config.max_buy_price, &bot.username()).unwrap();
+        assert_eq!((cheap.slot, cheap.price, cheap.seller.as_str()), (4, Money::new(2000), "bob"));
+
+        assert!(purchase_map(&bot, &cheap, &config, &Notify::new()).await.unwrap());
+        assert_eq!(bot.commands(), vec!["/ah map"]);
+        assert!(bot.actions().contains(&Action::LeftClick { window: 2, slot: 15 }));
+        assert_eq!(get_map_slots(&bot), vec![9]);
+    }
+
+    #[test]
+    fn test_confirm_screen_must_match_listing() {
+        let picked = MapSlot { slot: 4, price: Money::new(2000), seller: "bob".to_string(), map_id: Some(2) };
+        let check = |listing: Option<SlotItem>| {
+            // As the window shows it, with our inventory below
+            let mut slots = confirm_screen(listing);
+            slots.resize(slots.len() + 36, None);
+            check_confirm_screen(&slots, &picked)
+        };
+
+        assert_eq!(check(Some(auction(2, "$2,000", "Bob"))), ConfirmCheck::Matches);
+        assert_eq!(check(Some(auction(2, "$2.5K", "bob"))), ConfirmCheck::PriceChanged { shown: Money::new(2500) });
+        assert_eq!(check(Some(auction(2, "$2,000", "mallory"))), ConfirmCheck::SellerChanged { shown: "mallory".to_string() });
+        assert_eq!(check(Some(auction(9, "$2,000", "bob"))), ConfirmCheck::OtherMap { shown: Some(9) });
+        assert_eq!(check(None), ConfirmCheck::NoListing);
+
+        // A screen that doesn't name the seller is judged on price alone
+        let priced_only = SlotItem { lore: vec!["Price: $2,000".to_string()], ..map(2, 1) };
+        assert_eq!(check(Some(priced_only)), ConfirmCheck::Matches);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_purchase_without_confirm_screen_is_an_error() {
+        let bot = FakeSession::new(vec![]);
+        let map = MapSlot { slot: 4, price: Money::new(2000), seller: "bob".to_string(), map_id: Some(2) };
+        assert!(purchase_map(&bot, &map, &Config::default(), &Notify::new()).await.is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_purchase_without_confirm_button_is_not_paid() {
+        let bot = FakeSession::new(vec![]);
+        let listings = vec![(4, auction(2, "$2,000", "bob"))];
+        bot.on_command("/ah map", vec![Reaction::Open { label: "ah".to_string(), slots: chest(6, listings) }])
+            .on_click("ah", 4, vec![Reaction::Open { label: "confirm".to_string(), slots: chest(3, vec![]) }]);
+
+        let config = Config::default();
+        let slots = open_auction_house(&bot, &config).await.unwrap().unwrap();
+        let cheap = find_cheap_maps(&slots, config.max_buy_price, &bot.username()).unwrap();
+        assert!(purchase_map(&bot, &cheap, &config, &Notify::new()).await.is_err());
+        assert!(!bot.actions().iter().any(|action| matches!(action, Action::LeftClick { window: 2, .. })));
+        assert!(bot.actions().contains(&Action::Close { window: 2 }));
+    }
+
+    /// An auction house with one map at $2,000 whose confirm button does nothing
+    fn unanswered_purchase() -> FakeSession {
+        let bot = FakeSession::new(vec![]);
+        let listing = auction(2, "$2,000", "bob");
+        bot.on_command("/ah map", vec![Reaction::Open { label: "ah".to_string(), slots: chest(6, vec![(4, listing.clone())]) }])
+            .on_click("ah", 4, vec![Reaction::Open { label: "confirm".to_string(), slots: confirm_screen(Some(listing)) }]);
+        bot
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_refused_purchase_is_not_a_success() {
+        let bot = unanswered_purchase();
+        let config = Config::default();
+        let slots = open_auction_house(&bot, &config).await.unwrap().unwrap();
+        let cheap = find_cheap_maps(&slots, config.max_buy_price, &bot.username()).unwrap();
+
+        // "This item has already been bought!" right after the confirm click
+        let refused = Arc::new(Notify::new());
+        let refuse = {
+            let refused = refused.clone();
+            async move {
+                sleep(Duration::from_millis(500)).await;
+                refused.notify_waiters();
+            }
+        };
+        let (bought, _) = tokio::join!(purchase_map(&bot, &cheap, &config, &refused), refuse);
+
+        assert!(!bought.unwrap());
+        assert!(bot.actions().contains(&Action::LeftClick { window: 2, slot: 15 }));
+        assert!(bot.actions().contains(&Action::Close { window: 2 }));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_purchase_without_a_map_arriving_fails() {
+        let bot = unanswered_purchase();
+        let config = Config::default();
+        let slots = open_auction_house(&bot, &config).await.unwrap().unwrap();
+        let cheap = find_cheap_maps(&slots, config.max_buy_price, &bot.username()).unwrap();
+
+        let started = tokio::time::Instant::now();
+        assert!(!purchase_map(&bot, &cheap, &config, &Notify::new()).await.unwrap());
+        assert!(started.elapsed() >= Duration::from_millis(config.window_timeout));
+        assert!(bot.actions().contains(&Action::Close { window: 2 }));
+        assert_eq!(count_maps(&bot), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_list_maps_prices_each_stack() {
+        let bot = FakeSession::new(vec![(9, map(1, 1)), (20, map(2, 3))]);
+        accept_listings(&bot);
+        let unit_prices = HashMap::from([(20, Money::new(8000))]);
+
+        let listed = list_maps(&bot, &Config::default(), &get_map_slots(&bot), &unit_prices, &Notify::new())
+            .await
+            .unwrap();
+
+        // 9900 × 1 × 0.5, then the relist price 8000 × 3 × 0.5
+        assert_eq!(bot.commands(), vec!["/ah sell 4950", "/ah sell 12k"]);
+        let listed: Vec<_> = listed.iter().map(|s| (s.price, s.count, s.map_id)).collect();
+        assert_eq!(listed, vec![(Money::new(4950), 1, Some(1)), (Money::new(12000), 3, Some(2))]);
+        assert_eq!(count_maps(&bot), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cancelled_listings_relist_from_their_slots() {
+        let bot = FakeSession::new(vec![]);
+        let unnamed = SlotItem { map_id: None, ..auction(0, "$5,000", "FlipperBot") };
+        let listings = vec![
+            (0, auction(3, "$20,000", "FlipperBot")),
+            (1, auction(3, "$9,900", "FlipperBot")),
+            (2, unnamed.clone()),
+        ];
+        bot.on_command("/ah myitems", vec![Reaction::Open { label: "listings".to_string(), slots: chest(6, listings) }])
+            .on_click("listings", 1, vec![Reaction::Give(map(3, 1)), Reaction::Close])
+            .on_click("listings", 2, vec![Reaction::Give(SlotItem { lore: Vec::new(), ..unnamed }), Reaction::Close]);
+        accept_listings(&bot);
+
+        let target = |map_id, old_price, new_price| RepriceTarget {
+            map_id,
+            old_price: Money::new(old_price),
+            count: 1,
+            new_price_per_map: Money::new(new_price),
+        };
+        let targets = vec![target(Some(3), 9900, 8000), target(None, 5000, 4000)];
+        let cancelled = cancel_listings(&bot, &Config::default(), &targets).await.unwrap();
+
+        // The listing with the same map id but another price is left up
+        let clicked: Vec<usize> = bot.actions().iter()
+            .filter_map(|action| match action {
+                Action::LeftClick { slot, .. } => Some(*slot),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(clicked, vec![1, 2]);
+        let slots: Vec<_> = cancelled.iter().map(|c| (c.target.map_id, c.slot)).collect();
+        assert_eq!(slots, vec![(Some(3), 9), (None, 10)]);
+
+        // Both are relisted at their new price, including the one without a map id
+        let unit_prices = cancelled.iter().map(|c| (c.slot, c.target.new_price_per_map.checked_mul(2).unwrap())).collect();
+        list_maps(&bot, &Config::default(), &get_map_slots(&bot), &unit_prices, &Notify::new()).await.unwrap();
+        assert_eq!(bot.commands()[2..], ["/ah sell 8k", "/ah sell 4k"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_list_maps_stops_at_listing_cap() {
+        // The server answers `/ah sell` in chat instead of opening a confirm screen
+        let bot = FakeSession::new(vec![(9, map(1, 1)), (10, map(2, 1))]);
+        let cap_reached = Arc::new(Notify::new());
+
+        let refuse = {
+            let cap_reached = cap_reached.clone();
+            async move {
+                // After the first stack is moved to the hotbar and `/ah sell` is sent
+                sleep(Duration::from_millis(1000)).await;
+                cap_reached.notify_waiters();
+            }
+        };
+        let (config, slots, unit_prices) = (Config::default(), get_map_slots(&bot), HashMap::new());
+        let (listed, _) = tokio::join!(
+            list_maps(&bot, &config, &slots, &unit_prices, &cap_reached),
+            refuse,
+        );
+
+        assert!(listed.unwrap().is_empty());
+        assert_eq!(bot.commands().len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_unstack_maps_splits_into_singles() {
+        let bot = FakeSession::new(vec![(9, map(7, 4))]);
+        unstack_maps(&bot).await.unwrap();
+
+        let counts: Vec<i32> = bot.inventory().iter().flatten().map(|item| item.count).collect();
+        assert_eq!(counts, vec![1, 1, 1, 1]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_unstack_maps_fails_when_inventory_is_full() {
+        let items = (9..45).map(|slot| (slot, map(slot as i32, 2))).collect();
+        assert!(unstack_maps(&FakeSession::new(items)).await.is_err());
+    }
+}
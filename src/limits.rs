@@ -0,0 +1,419 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::config::Config;
+use crate::price_parser::Money;
+
+/// A safety rail that stopped buying
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LimitHit {
+    HourlySpend { spent: Money, limit: Money },
+    DailySpend { spent: Money, limit: Money },
+    HourlyPurchases { count: u32, limit: u32 },
+    UnsoldValue { value: Money, limit: Money },
+    ConsecutiveFailures(u32),
+    NegativeProfit { trades: usize, profit: i64 },
+}
+
+impl fmt::Display for LimitHit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitHit::HourlySpend { spent, limit } => {
+                write!(f, "spent {} in the last hour (limit {})", spent, limit)
+            }
+            LimitHit::DailySpend { spent, limit } => {
+                write!(f, "spent {} in the last 24 hours (limit {})", spent, limit)
+            }
+            LimitHit::HourlyPurchases { count, limit } => {
+                write!(f, "{} purchases in the last hour (limit {})", count, limit)
+            }
+            LimitHit::UnsoldValue { value, limit } => {
+                write!(f, "{} of maps bought but not yet sold (limit {})", value, limit)
+            }
+            LimitHit::ConsecutiveFailures(count) => write!(f, "{} purchases failed in a row", count),
+            LimitHit::NegativeProfit { trades, profit } => {
+                write!(f, "lost ${} over the last {} trades", profit.unsigned_abs(), trades)
+            }
+        }
+    }
+}
+
+/// Result of asking whether another purchase is allowed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LimitCheck {
+    Allowed,
+    /// A limit was hit just now; buying is paused from here on
+    Tripped(LimitHit),
+    /// Still paused from an earlier trip
+    Paused(LimitHit),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Pause {
+    reason: LimitHit,
+    since: DateTime<Utc>,
+}
+
+/// Spending limits and the circuit breaker guarding `purchase_map`
+///
+/// Hitting any limit pauses buying until `limitCooldown` has passed or the operator
+/// creates the `resumeFile`. Rate limits use rolling windows, so after a resume they
+/// only trip again if the last hour or day is still over budget.
+///
+/// Like `DigestTracker`, changes mark the state dirty and are saved to `limitsStateFile`
+/// by the digest loop, so a restart keeps the spending history and any pause.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TradingLimits {
+    /// Purchases from the last 24 hours, oldest first
+    #[serde(default)]
+    purchases: VecDeque<(DateTime<Utc>, Money)>,
+    /// Cost of each map bought but not yet sold, matched to sales first-in first-out
+    #[serde(default)]
+    unsold: VecDeque<Money>,
+    /// Realised profit of the most recent trades, oldest first
+    #[serde(default)]
+    recent_profits: VecDeque<i64>,
+    #[serde(default)]
+    consecutive_failures: u32,
+    #[serde(default)]
+    pause: Option<Pause>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl TradingLimits {
+    /// Load the saved state from `path`, dropping purchases older than a day; starts
+    /// empty if the file is missing or unreadable
+    pub fn load(path: impl AsRef<Path>, now: DateTime<Utc>) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let mut limits = match fs::read_to_string(&path) {
+            Ok(data) => match serde_json::from_str::<TradingLimits>(&data) {
+                Ok(limits) => {
+                    println!("[LIMITS] Restored spending history from {}", path.display());
+                    limits
+                }
+                Err(e) => {
+                    eprintln!("[LIMITS] Ignoring unreadable state file {}: {}", path.display(), e);
+                    TradingLimits::default()
+                }
+            },
+            Err(_) => TradingLimits::default(),
+        };
+        limits.prune(now);
+        limits.path = Some(path);
+        limits
+    }
+
+    /// The state file's path and contents if anything changed since the last call
+    ///
+    /// Clears the dirty flag; write the result outside the lock. Always `None` for a
+    /// tracker that wasn't loaded from a file.
+    pub fn take_unsaved(&mut self) -> Option<(PathBuf, String)> {
+        if !self.dirty {
+            return None;
+        }
+        let path = self.path.clone()?;
+        match serde_json::to_string_pretty(self) {
+            Ok(data) => {
+                self.dirty = false;
+                Some((path, data))
+            }
+            Err(e) => {
+                eprintln!("[LIMITS] Failed to serialize limits state: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Check whether buying one more map at `price` stays within every limit
+    pub fn check(&mut self, now: DateTime<Utc>, price: Money, config: &Config) -> LimitCheck {
+        if let Some(pause) = &self.pause {
+            return LimitCheck::Paused(pause.reason.clone());
+        }
+
+        self.prune(now);
+        let hour_ago = now - Duration::hours(1);
+        let last_hour = self.purchases.iter().filter(|(at, _)| *at > hour_ago);
+        let (hour_count, hour_spend) = last_hour.fold((0u32, Money::ZERO), |(n, total), (_, cost)| {
+            (n + 1, total.saturating_add(*cost))
+        });
+        let day_spend = self.purchases.iter()
+            .fold(Money::ZERO, |total, (_, cost)| total.saturating_add(*cost));
+        let unsold = self.unsold_value();
+
+        let hit = if exceeds(hour_spend, price, config.max_spend_per_hour) {
+            Some(LimitHit::HourlySpend { spent: hour_spend, limit: config.max_spend_per_hour })
+        } else if exceeds(day_spend, price, config.max_spend_per_day) {
+            Some(LimitHit::DailySpend { spent: day_spend, limit: config.max_spend_per_day })
+        } else if config.max_purchases_per_hour > 0 && hour_count >= config.max_purchases_per_hour {
+            Some(LimitHit::HourlyPurchases { count: hour_count, limit: config.max_purchases_per_hour })
+        } else if exceeds(unsold, price, config.max_unsold_value) {
+            Some(LimitHit::UnsoldValue { value: unsold, limit: config.max_unsold_value })
+        } else {
+            None
+        };
+
+        match hit {
+            Some(reason) => {
+                self.trip(now, reason.clone());
+                LimitCheck::Tripped(reason)
+            }
+            None => LimitCheck::Allowed,
+        }
+    }
+
+    pub fn record_purchase(&mut self, now: DateTime<Utc>, price: Money) {
+        self.purchases.push_back((now, price));
+        self.unsold.push_back(price);
+        self.consecutive_failures = 0;
+        self.prune(now);
+        self.dirty = true;
+    }
+
+    /// Count a failed purchase; returns the reason if this trips the breaker
+    pub fn record_failure(&mut self, now: DateTime<Utc>, config: &Config) -> Option<LimitHit> {
+        self.consecutive_failures += 1;
+        self.dirty = true;
+
+        let limit = config.max_consecutive_failures;
+        if limit > 0 && self.consecutive_failures >= limit && self.pause.is_none() {
+            let reason = LimitHit::ConsecutiveFailures(self.consecutive_failures);
+            self.trip(now, reason.clone());
+            return Some(reason);
+        }
+        None
+    }
+
+    /// Match a sale of `quantity` maps to the oldest unsold purchases; returns the reason
+    /// if realised profit over the last `profitCheckTrades` trades has gone negative
+    ///
+    /// `cost` is what the sold listing's maps cost, when known; otherwise the profit is
+    /// worked out from those oldest purchases.
+    pub fn record_sale(&mut self, now: DateTime<Utc>, price: Money, quantity: u32, cost: Option<Money>, config: &Config) -> Option<LimitHit> {
+        let quantity = quantity.max(1) as usize;
+        let oldest: Vec<Money> = (0..quantity).map_while(|_| self.unsold.pop_front()).collect();
+        self.dirty = true;
+        // Maps bought before a restart have no known cost
+        let oldest_cost = (oldest.len() == quantity)
+            .then(|| oldest.iter().fold(Money::ZERO, |total, cost| total.saturating_add(*cost)));
+        let cost = cost.or(oldest_cost)?;
+
+        let window = config.profit_check_trades as usize;
+        if window == 0 {
+            return None;
+        }
+        self.recent_profits.push_back(price.dollars() as i64 - cost.dollars() as i64);
+        while self.recent_profits.len() > window {
+            self.recent_profits.pop_front();
+        }
+
+        let profit: i64 = self.recent_profits.iter().sum();
+        if self.recent_profits.len() == window && profit < 0 && self.pause.is_none() {
+            let reason = LimitHit::NegativeProfit { trades: window, profit };
+            self.trip(now, reason.clone());
+            return Some(reason);
+        }
+        None
+    }
+
+    /// Lift the pause once `cooldown` has passed; a zero cooldown means manual resume only
+    pub fn resume_if_due(&mut self, now: DateTime<Utc>, cooldown: Duration) -> bool {
+        match &self.pause {
+            Some(pause) if !cooldown.is_zero() && now - pause.since >= cooldown => {
+                self.resume();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Lift the pause and give the breaker a clean slate
+    pub fn resume(&mut self) {
+        self.pause = None;
+        self.consecutive_failures = 0;
+        self.recent_profits.clear();
+        self.dirty = true;
+    }
+
+    /// Why buying is paused, if it is
+    pub fn pause_reason(&self) -> Option<&LimitHit> {
+        self.pause.as_ref().map(|pause| &pause.reason)
+    }
+
+    fn unsold_value(&self) -> Money {
+        self.unsold.iter().fold(Money::ZERO, |total, cost| total.saturating_add(*cost))
+    }
+
+    fn trip(&mut self, now: DateTime<Utc>, reason: LimitHit) {
+        self.pause = Some(Pause { reason, since: now });
+        self.dirty = true;
+    }
+
+    fn prune(&mut self, now: DateTime<Utc>) {
+        let day_ago = now - Duration::days(1);
+        while self.purchases.front().is_some_and(|(at, _)| *at <= day_ago) {
+            self.purchases.pop_front();
+        }
+    }
+}
+
+/// Would adding `price` to `current` go over `limit`? A zero limit is unlimited.
+fn exceeds(current: Money, price: Money, limit: Money) -> bool {
+    limit > Money::ZERO && current.saturating_add(price) > limit
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn start() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_hourly_spend_rolls_off() {
+        let config = Config { max_spend_per_hour: Money::new(5000), ..Config::default() };
+        let mut limits = TradingLimits::default();
+        let now = start();
+
+        limits.record_purchase(now, Money::new(2000));
+        limits.record_purchase(now + Duration::minutes(10), Money::new(2000));
+        assert_eq!(
+            limits.check(now + Duration::minutes(20), Money::new(2000), &config),
+            LimitCheck::Tripped(LimitHit::HourlySpend { spent: Money::new(4000), limit: Money::new(5000) })
+        );
+        assert!(matches!(limits.check(now, Money::new(1), &config), LimitCheck::Paused(_)));
+
+        // After the cooldown the first purchase has left the window
+        assert!(limits.resume_if_due(now + Duration::minutes(65), Duration::minutes(30)));
+        assert_eq!(limits.check(now + Duration::minutes(65), Money::new(2000), &config), LimitCheck::Allowed);
+    }
+
+    #[test]
+    fn test_purchase_count_and_unsold_value() {
+        let config = Config {
+            max_purchases_per_hour: 2,
+            max_unsold_value: Money::new(10000),
+            ..Config::default()
+        };
+        let now = start();
+
+        let mut limits = TradingLimits::default();
+        limits.record_purchase(now, Money::new(1000));
+        limits.record_purchase(now, Money::new(1000));
+        assert!(matches!(
+            limits.check(now, Money::new(1000), &config),
+            LimitCheck::Tripped(LimitHit::HourlyPurchases { count: 2, .. })
+        ));
+
+        let mut limits = TradingLimits::default();
+        limits.record_purchase(now - Duration::hours(2), Money::new(6000));
+        assert!(matches!(
+            limits.check(now, Money::new(5000), &config),
+            LimitCheck::Tripped(LimitHit::UnsoldValue { .. })
+        ));
+        limits.resume();
+        limits.record_sale(now, Money::new(9900), 1, None, &config);
+        assert_eq!(limits.check(now, Money::new(5000), &config), LimitCheck::Allowed);
+    }
+
+    #[test]
+    fn test_consecutive_failures_trip_until_manual_resume() {
+        let config = Config { max_consecutive_failures: 3, ..Config::default() };
+        let mut limits = TradingLimits::default();
+        let now = start();
+
+        assert_eq!(limits.record_failure(now, &config), None);
+        limits.record_purchase(now, Money::new(1000));
+        assert_eq!(limits.record_failure(now, &config), None);
+        assert_eq!(limits.record_failure(now, &config), None);
+        assert_eq!(limits.record_failure(now, &config), Some(LimitHit::ConsecutiveFailures(3)));
+
+        // A zero cooldown never resumes on its own
+        assert!(!limits.resume_if_due(now + Duration::days(7), Duration::zero()));
+        limits.resume();
+        assert_eq!(limits.pause_reason(), None);
+    }
+
+    #[test]
+    fn test_negative_profit_over_last_trades() {
+        let config = Config { profit_check_trades: 3, ..Config::default() };
+        let mut limits = TradingLimits::default();
+        let now = start();
+
+        for _ in 0..3 {
+            limits.record_purchase(now, Money::new(2000));
+        }
+        assert_eq!(limits.record_sale(now, Money::new(2500), 1, None, &config), None);
+        assert_eq!(limits.record_sale(now, Money::new(1500), 1, None, &config), None);
+        assert_eq!(
+            limits.record_sale(now, Money::new(1000), 1, None, &config),
+            Some(LimitHit::NegativeProfit { trades: 3, profit: -1000 })
+        );
+
+        // Sales with no recorded purchase are ignored
+        limits.resume();
+        assert_eq!(limits.record_sale(now, Money::new(1), 1, None, &config), None);
+    }
+
+    #[test]
+    fn test_stack_sale_uses_listing_cost() {
+        let config = Config { profit_check_trades: 1, ..Config::default() };
+        let mut limits = TradingLimits::default();
+        let now = start();
+
+        for price in [1000, 5000, 5000] {
+            limits.record_purchase(now, Money::new(price));
+        }
+        // Two maps sold for less than the listing cost, though more than the oldest two
+        assert_eq!(
+            limits.record_sale(now, Money::new(9000), 2, Some(Money::new(10000)), &config),
+            Some(LimitHit::NegativeProfit { trades: 1, profit: -1000 })
+        );
+        assert_eq!(limits.unsold_value(), Money::new(5000));
+
+        // Without it, the oldest purchases are the cost; too few of them is no cost at all
+        limits.resume();
+        assert_eq!(limits.record_sale(now, Money::new(9000), 2, None, &config), None);
+        assert_eq!(limits.unsold_value(), Money::ZERO);
+    }
+
+    #[test]
+    fn test_state_survives_restart() {
+        let config = Config { max_spend_per_day: Money::new(5000), ..Config::default() };
+        let path = std::env::temp_dir().join(format!("limits-test-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let now = start();
+
+        let mut limits = TradingLimits::load(&path, now);
+        assert!(limits.take_unsaved().is_none());
+        limits.record_purchase(now - Duration::hours(30), Money::new(1000));
+        limits.record_purchase(now - Duration::hours(2), Money::new(4000));
+        assert!(matches!(limits.check(now, Money::new(2000), &config), LimitCheck::Tripped(_)));
+        let (saved, data) = limits.take_unsaved().unwrap();
+        assert!(limits.take_unsaved().is_none());
+        fs::write(&saved, data).unwrap();
+
+        // The pause and the day's spending come back; the older purchase is dropped
+        let mut restored = TradingLimits::load(&path, now + Duration::hours(1));
+        assert_eq!(
+            restored.pause_reason(),
+            Some(&LimitHit::DailySpend { spent: Money::new(4000), limit: Money::new(5000) })
+        );
+        assert_eq!(restored.purchases.len(), 1);
+        assert_eq!(restored.unsold_value(), Money::new(5000));
+        restored.resume();
+        assert!(matches!(
+            restored.check(now + Duration::hours(1), Money::new(2000), &config),
+            LimitCheck::Tripped(LimitHit::DailySpend { .. })
+        ));
+
+        let _ = fs::remove_file(&path);
+    }
+}
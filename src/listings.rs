@@ -0,0 +1,731 @@
+use chrono::{DateTime, Duration, Utc};
+use regex::Regex;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::OnceLock;
+use crate::config::Config;
+use crate::price_parser::{parse_price, Money};
+use crate::text::normalize;
+
+/// How long finished listings are kept around for matching late sale messages
+const FINISHED_RETENTION_HOURS: i64 = 24;
+
+// A cap learned from chat is forgotten after this long, so a misread line or a raised
+// cap (e.g. a rank upgrade) doesn't hold buying back for good
+const LEARNED_CAP_TTL_MINS: i64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListingStatus {
+    Active,
+    Sold,
+    /// Gone from the listings GUI without a sale message (expired, or sold while offline)
+    Ended,
+    /// Taken down by us to be repriced
+    Cancelled,
+}
+
+/// One `/ah sell` we made
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Listing {
+    pub price: Money,
+    pub count: u32,
+    pub listed_at: DateTime<Utc>,
+    pub map_id: Option<i32>,
+    /// Known once the listings GUI has been read
+    pub expires_at: Option<DateTime<Utc>>,
+    pub status: ListingStatus,
+    pub status_changed_at: DateTime<Utc>,
+    /// What we paid for the maps in it; unknown unless we bought every one of them
+    pub cost: Option<Money>,
+}
+
+impl Listing {
+    /// What selling this listing for `price` made us, when its cost is known
+    pub fn profit(&self, price: Money) -> Option<i64> {
+        self.cost.map(|cost| price.dollars() as i64 - cost.dollars() as i64)
+    }
+}
+
+/// A listing as shown in the server's own listings GUI
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObservedListing {
+    pub price: Money,
+    pub expires_in: Option<Duration>,
+    pub map_id: Option<i32>,
+}
+
+/// A listing worth taking down and putting back up, cheaper or just fresher
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepriceTarget {
+    pub map_id: Option<i32>,
+    pub old_price: Money,
+    pub count: u32,
+    /// Price per map to list at now
+    pub new_price_per_map: Money,
+}
+
+/// One completed reprice
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepriceEvent {
+    pub at: DateTime<Utc>,
+    pub map_id: Option<i32>,
+    pub old_price: Money,
+    pub new_price: Money,
+}
+
+/// Formats as "Map #412: $9,900 → $8,200"
+impl fmt::Display for RepriceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.map_id {
+            Some(id) => write!(f, "Map #{}", id)?,
+            None => write!(f, "Map")?,
+        }
+        write!(f, ": {} → {}", self.old_price, self.new_price)
+    }
+}
+
+/// Listings the bot has made, reconciled against the server's listings GUI
+#[derive(Debug, Default)]
+pub struct ListingTracker {
+    listings: Vec<Listing>,
+    /// Cap learned from the server and when, which wins over the configured one
+    learned_cap: Option<(u32, DateTime<Utc>)>,
+    /// How many times each map (by map id) has come back unsold
+    expiries: HashMap<i32, u32>,
+    /// Cheapest price per map other sellers are asking, from the last auction house scan
+    market_floor: Option<Money>,
+    reprices: Vec<RepriceEvent>,
+    /// What we paid for each map bought but not listed yet, by map id, oldest first
+    unlisted_costs: Vec<(Option<i32>, Money)>,
+    /// Sales from an offline summary not yet matched to listings by a refresh
+    offline_sales: u32,
+    last_refresh: Option<DateTime<Utc>>,
+    last_reclaim: Option<DateTime<Utc>>,
+    last_reprice: Option<DateTime<Utc>>,
+}
+
+impl ListingTracker {
+    /// Remember what a map cost, to be attached to the listing it ends up in
+    pub fn record_purchase(&mut self, map_id: Option<i32>, cost: Money) {
+        self.unlisted_costs.push((map_id, cost));
+    }
+
+    pub fn record_listing(&mut self, now: DateTime<Utc>, price: Money, count: u32, map_id: Option<i32>) {
+        let cost = self.take_costs(count, map_id);
+        self.listings.push(Listing {
+            price,
+            count,
+            listed_at: now,
+            map_id,
+            expires_at: None,
+            status: ListingStatus::Active,
+            status_changed_at: now,
+            cost,
+        });
+        // The server took a listing past the cap we learned, so that cap was wrong
+        if self.learned_cap.is_some_and(|(cap, _)| self.active_count() > cap) {
+            self.learned_cap = None;
+        }
+    }
+
+    /// Total cost of `count` maps with `map_id`: purchases of that map first, then
+    /// purchases whose map id wasn't known. `None` if any of them wasn't bought by us.
+    fn take_costs(&mut self, count: u32, map_id: Option<i32>) -> Option<Money> {
+        let mut total = Some(Money::ZERO);
+        for _ in 0..count {
+            let index = self.unlisted_costs.iter()
+                .position(|(id, _)| id.is_some() && *id == map_id)
+                .or_else(|| self.unlisted_costs.iter().position(|(id, _)| id.is_none()));
+            let cost = index.map(|i| self.unlisted_costs.remove(i).1);
+            total = total.zip(cost).map(|(total, cost)| total.saturating_add(cost));
+        }
+        total
+    }
+
+    /// Put the cost of a listing's maps back for when they are listed again
+    fn return_costs(&mut self, index: usize) {
+        let listing = &self.listings[index];
+        let Some(cost) = listing.cost else {
+            return;
+        };
+        let count = listing.count.max(1) as u64;
+        let share = cost.dollars() / count;
+        let first = cost.dollars() - share * (count - 1);
+        let map_id = listing.map_id;
+        self.unlisted_costs.push((map_id, Money::new(first)));
+        for _ in 1..count {
+            self.unlisted_costs.push((map_id, Money::new(share)));
+        }
+    }
+
+    /// Mark the oldest active listing at `price` as sold, preferring one of `quantity`
+    /// maps; a sale at a price we have no listing for closes nothing
+    pub fn mark_sold(&mut self, now: DateTime<Utc>, price: Money, quantity: u32) -> Option<&Listing> {
+        let active_at = |l: &Listing| l.status == ListingStatus::Active && l.price == price;
+        let index = self.listings.iter()
+            .position(|l| active_at(l) && l.count == quantity)
+            .or_else(|| self.listings.iter().position(active_at))?;
+
+        let listing = &mut self.listings[index];
+        listing.status = ListingStatus::Sold;
+        listing.status_changed_at = now;
+        if let Some(id) = listing.map_id {
+            self.expiries.remove(&id);
+        }
+        Some(listing)
+    }
+
+    /// Bring the tracker in line with what the listings GUI shows; returns the listings
+    /// it found sold
+    ///
+    /// Observed listings are matched to tracked ones by map id, then by price (oldest
+    /// first). Tracked listings that are no longer shown sold while we were offline, up to
+    /// the count from [`Self::expect_offline_sales`] (oldest first), or else have ended.
+    /// Shown listings we weren't tracking (e.g. from before a restart) are adopted.
+    pub fn reconcile(&mut self, now: DateTime<Utc>, observed: &[ObservedListing]) -> Vec<Listing> {
+        let mut unmatched: Vec<usize> = self.listings.iter()
+            .enumerate()
+            .filter(|(_, l)| l.status == ListingStatus::Active)
+            .map(|(i, _)| i)
+            .collect();
+
+        for seen in observed {
+            let by_id = seen.map_id.and_then(|id| {
+                unmatched.iter().position(|&i| self.listings[i].map_id == Some(id))
+            });
+            let matched = by_id.or_else(|| {
+                unmatched.iter().position(|&i| self.listings[i].price == seen.price)
+            });
+            let expires_at = seen.expires_in.map(|left| now + left);
+
+            match matched {
+                Some(pos) => {
+                    let listing = &mut self.listings[unmatched.remove(pos)];
+                    listing.expires_at = expires_at.or(listing.expires_at);
+                }
+                None => self.listings.push(Listing {
+                    price: seen.price,
+                    count: 1,
+                    listed_at: now,
+                    map_id: seen.map_id,
+                    expires_at,
+                    status: ListingStatus::Active,
+                    status_changed_at: now,
+                    cost: None,
+                }),
+            }
+        }
+
+        let mut sold = Vec::new();
+        for i in unmatched {
+            self.listings[i].status_changed_at = now;
+            if self.offline_sales > 0 {
+                self.offline_sales -= 1;
+                self.listings[i].status = ListingStatus::Sold;
+                if let Some(id) = self.listings[i].map_id {
+                    self.expiries.remove(&id);
+                }
+                sold.push(self.listings[i].clone());
+                continue;
+            }
+            self.listings[i].status = ListingStatus::Ended;
+            // An expired map comes back to be relisted; without an id we couldn't tell
+            // it from one sold while we were offline
+            if self.listings[i].map_id.is_some() {
+                self.return_costs(i);
+            }
+        }
+
+        self.offline_sales = 0;
+
+        let cutoff = now - Duration::hours(FINISHED_RETENTION_HOURS);
+        self.listings.retain(|l| l.status == ListingStatus::Active || l.status_changed_at > cutoff);
+        sold
+    }
+
+    pub fn active(&self) -> impl Iterator<Item = &Listing> {
+        self.listings.iter().filter(|l| l.status == ListingStatus::Active)
+    }
+
+    pub fn active_count(&self) -> u32 {
+        self.active().count() as u32
+    }
+
+    /// Active listings that expire before `now + within`, soonest first
+    pub fn expiring_within(&self, now: DateTime<Utc>, within: Duration) -> Vec<&Listing> {
+        let mut expiring: Vec<&Listing> = self.active()
+            .filter(|l| l.expires_at.is_some_and(|at| at <= now + within))
+            .collect();
+        expiring.sort_by_key(|l| l.expires_at);
+        expiring
+    }
+
+    /// The server's listing cap: learned from the server within the last
+    /// `LEARNED_CAP_TTL_MINS` if seen, else the configured one (0 = unknown)
+    pub fn cap(&self, now: DateTime<Utc>, configured: u32) -> Option<u32> {
+        self.learned_cap
+            .filter(|(_, at)| now - *at < Duration::minutes(LEARNED_CAP_TTL_MINS))
+            .map(|(cap, _)| cap)
+            .or((configured > 0).then_some(configured))
+    }
+
+    /// Record the cap from a server message; without a number in it, the cap is
+    /// however many listings we have active right now
+    pub fn learn_cap(&mut self, now: DateTime<Utc>, cap: Option<u32>) -> u32 {
+        let cap = cap.unwrap_or_else(|| self.active_count()).max(1);
+        self.learned_cap = Some((cap, now));
+        cap
+    }
+
+    /// Would `waiting` more listings (stacks in the inventory, plus one for a new
+    /// purchase) still fit under the cap? Always true while the cap is unknown.
+    pub fn has_room_for(&self, now: DateTime<Utc>, waiting: u32, configured: u32) -> bool {
+        self.cap(now, configured).is_none_or(|cap| self.active_count() + waiting <= cap)
+    }
+
+    /// Whether a listings GUI refresh is due; if so, the attempt is recorded so a
+    /// failing refresh is retried after `interval` rather than every cycle
+    pub fn take_refresh_due(&mut self, now: DateTime<Utc>, interval: Duration) -> bool {
+        take_due(&mut self.last_refresh, now, interval)
+    }
+
+    /// Note that `count` listings sold while we were offline, without saying which; the
+    /// next [`Self::take_refresh_due`] is made true so a refresh can find them
+    pub fn expect_offline_sales(&mut self, count: u32) {
+        self.offline_sales = self.offline_sales.saturating_add(count);
+        self.last_refresh = None;
+    }
+
+    /// Same as [`Self::take_refresh_due`], for visiting the expired-items view
+    pub fn take_reclaim_due(&mut self, now: DateTime<Utc>, interval: Duration) -> bool {
+        take_due(&mut self.last_reclaim, now, interval)
+    }
+
+    /// Same as [`Self::take_refresh_due`], for the repricing job
+    pub fn take_reprice_due(&mut self, now: DateTime<Utc>, interval: Duration) -> bool {
+        take_due(&mut self.last_reprice, now, interval)
+    }
+
+    pub fn set_market_floor(&mut self, floor: Money) {
+        self.market_floor = Some(floor);
+    }
+
+    pub fn market_floor(&self) -> Option<Money> {
+        self.market_floor
+    }
+
+    /// Active listings that are older than `repriceAfter`, or whose price per map is more
+    /// than `repriceMargin` percent above the market floor
+    ///
+    /// The new price is the floor (or the current price, if that's lower) but never
+    /// below `maxBuyPrice`, so a reprice can't undercut what we pay for maps.
+    pub fn reprice_targets(&self, now: DateTime<Utc>, config: &Config) -> Vec<RepriceTarget> {
+        let max_age = Duration::milliseconds(config.reprice_after as i64);
+        let margin = 100 + config.reprice_margin_percent as u64;
+
+        self.active()
+            .filter_map(|listing| {
+                let per_map = listing.price.checked_div_ceil(listing.count.max(1) as u64)?;
+                let overpriced = self.market_floor
+                    .and_then(|floor| floor.checked_mul(margin))
+                    .and_then(|limit| limit.checked_div(100))
+                    .is_some_and(|limit| per_map > limit);
+                let stale = config.reprice_after > 0 && now - listing.listed_at >= max_age;
+                if !overpriced && !stale {
+                    return None;
+                }
+
+                let target = self.market_floor.map_or(per_map, |floor| floor.min(per_map));
+                Some(RepriceTarget {
+                    map_id: listing.map_id,
+                    old_price: listing.price,
+                    count: listing.count,
+                    new_price_per_map: target.max(config.max_buy_price),
+                })
+            })
+            .collect()
+    }
+
+    /// Mark the listing matching `target` as taken down
+    pub fn mark_cancelled(&mut self, now: DateTime<Utc>, target: &RepriceTarget) {
+        let found = self.listings.iter().position(|l| {
+            l.status == ListingStatus::Active
+                && l.price == target.old_price
+                && (target.map_id.is_none() || l.map_id == target.map_id)
+        });
+        if let Some(index) = found {
+            self.listings[index].status = ListingStatus::Cancelled;
+            self.listings[index].status_changed_at = now;
+            self.return_costs(index);
+        }
+    }
+
+    /// Remember a reprice; returns how many times this map has been repriced in the last day
+    pub fn record_reprice(&mut self, event: RepriceEvent) -> usize {
+        let cutoff = event.at - Duration::hours(FINISHED_RETENTION_HOURS);
+        self.reprices.retain(|e| e.at > cutoff);
+
+        let map_id = event.map_id;
+        self.reprices.push(event);
+        self.reprices.iter().filter(|e| map_id.is_some() && e.map_id == map_id).count().max(1)
+    }
+
+    /// Note that a map came back unsold; returns how many times it has now expired
+    ///
+    /// The listing it came from has ended, and its cost goes back to be attached to the
+    /// relist. A map without an id can't be told apart from our other listings, so that
+    /// is left to the next listings refresh.
+    pub fn record_expired(&mut self, now: DateTime<Utc>, map_id: Option<i32>) -> u32 {
+        let Some(id) = map_id else {
+            return 1;
+        };
+        let found = self.listings.iter()
+            .position(|l| l.status == ListingStatus::Active && l.map_id == Some(id));
+        if let Some(index) = found {
+            self.listings[index].status = ListingStatus::Ended;
+            self.listings[index].status_changed_at = now;
+            self.return_costs(index);
+        }
+        let count = self.expiries.entry(id).or_insert(0);
+        *count += 1;
+        *count
+    }
+}
+
+fn take_due(last: &mut Option<DateTime<Utc>>, now: DateTime<Utc>, interval: Duration) -> bool {
+    let due = last.is_none_or(|at| now - at >= interval);
+    if due {
+        *last = Some(now);
+    }
+    due
+}
+
+/// Price each map is listed at when relisting a map that has expired `expiries` times
+///
+/// Listings go up at half of `sellPrice` per map. From the `relistDiscountAfter`-th expiry
+/// on, each expiry takes another `relistDiscountPercent` off `sellPrice` (rounded up), but
+/// the price per map never goes below `maxBuyPrice`.
+pub fn relist_price(config: &Config, expiries: u32) -> Money {
+    let percent = config.relist_discount_percent.min(100) as u64;
+    let after = config.relist_discount_after.max(1);
+    let steps = if percent == 0 || expiries < after { 0 } else { expiries - after + 1 };
+
+    let mut price = config.sell_price;
+    for _ in 0..steps {
+        price = price.checked_mul(100 - percent)
+            .and_then(|p| p.checked_div_ceil(100))
+            .unwrap_or(price);
+    }
+    price.checked_div_ceil(2).unwrap_or(price).max(config.max_buy_price)
+}
+
+/// Parse the time left on a listing from its lore, e.g. "Expires in: 1d 4h 30m",
+/// "Time Left: 2 hours 5 minutes" or "Expired"
+pub fn parse_expiry(lore: &[String]) -> Option<Duration> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(r"(?i)(\d+)\s*(days?|d|hours?|hrs?|h|minutes?|mins?|m|seconds?|secs?|s)\b").unwrap()
+    });
+
+    for line in lore {
+        let clean = normalize(line).to_lowercase();
+        if !clean.contains("expire") && !clean.contains("time left") {
+            continue;
+        }
+        if clean.trim_end().ends_with("expired") {
+            return Some(Duration::zero());
+        }
+
+        let mut total = Duration::zero();
+        let mut found = false;
+        for caps in re.captures_iter(&clean) {
+            let amount: i64 = caps[1].parse().ok()?;
+            let unit = match caps[2].chars().next()? {
+                'd' => Duration::days(1),
+                'h' => Duration::hours(1),
+                'm' => Duration::minutes(1),
+                _ => Duration::seconds(1),
+            };
+            total += unit * amount as i32;
+            found = true;
+        }
+        if found {
+            return Some(total);
+        }
+    }
+
+    None
+}
+
+/// Read one of our listings from its GUI lore
+pub fn parse_observed_listing(lore: &[String], map_id: Option<i32>) -> Option<ObservedListing> {
+    let price = lore.iter().find_map(|line| parse_price(line))?;
+    Some(ObservedListing {
+        price,
+        expires_in: parse_expiry(lore),
+        map_id,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn lore(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    fn start() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_parse_expiry() {
+        assert_eq!(
+            parse_expiry(&lore(&["§7Price: §a$9.9K", "§7Expires in: §f1d 4h 30m"])),
+            Some(Duration::days(1) + Duration::hours(4) + Duration::minutes(30))
+        );
+        assert_eq!(
+            parse_expiry(&lore(&["Time Left: 2 hours 5 minutes"])),
+            Some(Duration::hours(2) + Duration::minutes(5))
+        );
+        assert_eq!(parse_expiry(&lore(&["§cExpired"])), Some(Duration::zero()));
+        assert_eq!(parse_expiry(&lore(&["Price: $9.9K", "Seller: me"])), None);
+    }
+
+    #[test]
+    fn test_sale_marks_matching_listing() {
+        let now = start();
+        let mut tracker = ListingTracker::default();
+        tracker.record_listing(now, Money::new(4950), 1, None);
+        tracker.record_listing(now, Money::new(9900), 2, None);
+
+        let sold = tracker.mark_sold(now, Money::new(9900), 1).unwrap();
+        assert_eq!(sold.count, 2);
+        assert_eq!(tracker.active_count(), 1);
+
+        // A price we have no listing at closes nothing
+        assert!(tracker.mark_sold(now, Money::new(1), 1).is_none());
+        assert_eq!(tracker.active_count(), 1);
+    }
+
+    #[test]
+    fn test_sale_prefers_matching_quantity() {
+        let now = start();
+        let mut tracker = ListingTracker::default();
+        tracker.record_listing(now, Money::new(9900), 1, Some(1));
+        tracker.record_listing(now, Money::new(9900), 3, Some(2));
+
+        assert_eq!(tracker.mark_sold(now, Money::new(9900), 3).unwrap().map_id, Some(2));
+        assert_eq!(tracker.mark_sold(now, Money::new(9900), 3).unwrap().map_id, Some(1));
+    }
+
+    #[test]
+    fn test_sale_reports_profit_on_purchase_cost() {
+        let now = start();
+        let mut tracker = ListingTracker::default();
+        tracker.record_purchase(Some(7), Money::new(2000));
+        tracker.record_purchase(None, Money::new(2500));
+        tracker.record_purchase(Some(7), Money::new(3000));
+
+        // Both copies of map #7 go into one stack
+        tracker.record_listing(now, Money::new(9900), 2, Some(7));
+        // Map #8's id wasn't known when we bought it
+        tracker.record_listing(now, Money::new(4950), 1, Some(8));
+        // Map #9 was in the inventory before we started
+        tracker.record_listing(now, Money::new(4950), 1, Some(9));
+
+        let sold = tracker.mark_sold(now, Money::new(9900), 2).unwrap();
+        assert_eq!((sold.cost, sold.profit(Money::new(9900))), (Some(Money::new(5000)), Some(4900)));
+        let sold = tracker.mark_sold(now, Money::new(4950), 1).unwrap();
+        assert_eq!((sold.map_id, sold.profit(Money::new(4950))), (Some(8), Some(2450)));
+        let sold = tracker.mark_sold(now, Money::new(4950), 1).unwrap();
+        assert_eq!((sold.map_id, sold.profit(Money::new(4950))), (Some(9), None));
+    }
+
+    #[test]
+    fn test_cost_survives_relisting() {
+        let config = Config { reprice_after: 1, max_buy_price: Money::new(1000), ..Config::default() };
+        let now = start();
+        let mut tracker = ListingTracker::default();
+        tracker.record_purchase(Some(4), Money::new(2000));
+        tracker.record_purchase(Some(4), Money::new(2001));
+        tracker.record_listing(now, Money::new(9900), 2, Some(4));
+
+        // Repriced: taken down and put back up cheaper
+        let target = tracker.reprice_targets(now + Duration::hours(1), &config).remove(0);
+        tracker.mark_cancelled(now, &target);
+        tracker.record_listing(now, Money::new(8000), 2, Some(4));
+        assert_eq!(tracker.mark_sold(now, Money::new(8000), 2).unwrap().profit(Money::new(8000)), Some(3999));
+
+        // Expired, reclaimed from the auction house and relisted
+        tracker.record_purchase(Some(5), Money::new(2000));
+        tracker.record_listing(now, Money::new(9900), 1, Some(5));
+        assert_eq!(tracker.record_expired(now, Some(5)), 1);
+        assert_eq!(tracker.active_count(), 0);
+        tracker.record_listing(now, Money::new(9000), 1, Some(5));
+        assert_eq!(tracker.mark_sold(now, Money::new(9000), 1).unwrap().cost, Some(Money::new(2000)));
+    }
+
+    #[test]
+    fn test_reconcile_with_gui() {
+        let now = start();
+        let mut tracker = ListingTracker::default();
+        tracker.record_listing(now, Money::new(9900), 1, Some(7));
+        tracker.record_listing(now, Money::new(9900), 1, Some(8));
+
+        let later = now + Duration::hours(1);
+        tracker.reconcile(later, &[
+            ObservedListing { price: Money::new(9900), expires_in: Some(Duration::minutes(20)), map_id: Some(8) },
+            ObservedListing { price: Money::new(4950), expires_in: Some(Duration::hours(40)), map_id: None },
+        ]);
+
+        // #7 is gone, #8 got its expiry, and the unknown listing was adopted
+        assert_eq!(tracker.active_count(), 2);
+        let expiring = tracker.expiring_within(later, Duration::hours(1));
+        assert_eq!(expiring.len(), 1);
+        assert_eq!(expiring[0].map_id, Some(8));
+        assert!(tracker.take_refresh_due(later, Duration::minutes(10)));
+        assert!(!tracker.take_refresh_due(later + Duration::minutes(5), Duration::minutes(10)));
+    }
+
+    #[test]
+    fn test_refresh_finds_offline_sales() {
+        let now = start();
+        let mut tracker = ListingTracker::default();
+        tracker.record_purchase(Some(1), Money::new(2000));
+        tracker.record_purchase(Some(2), Money::new(2100));
+        tracker.record_listing(now, Money::new(9900), 1, Some(1));
+        tracker.record_listing(now, Money::new(9900), 1, Some(2));
+        tracker.record_listing(now, Money::new(9900), 1, Some(3));
+
+        // One sold while offline; the other missing listing expired
+        tracker.expect_offline_sales(1);
+        assert!(tracker.take_refresh_due(now, Duration::hours(1)));
+        let seen = [ObservedListing { price: Money::new(9900), expires_in: None, map_id: Some(3) }];
+        let sold = tracker.reconcile(now, &seen);
+        assert_eq!(sold.len(), 1);
+        assert_eq!((sold[0].map_id, sold[0].cost), (Some(1), Some(Money::new(2000))));
+        assert_eq!(tracker.active_count(), 1);
+
+        // Only the expired map's cost comes back for its relist
+        tracker.record_listing(now, Money::new(9900), 1, Some(2));
+        assert_eq!(tracker.mark_sold(now, Money::new(9900), 1).unwrap().map_id, Some(3));
+        assert_eq!(tracker.mark_sold(now, Money::new(9900), 1).unwrap().cost, Some(Money::new(2100)));
+
+        // The count is used up by one refresh
+        assert!(tracker.reconcile(now, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_relist_price_discounts_repeated_expiry() {
+        let config = Config {
+            sell_price: Money::new(10000),
+            max_buy_price: Money::new(3500),
+            relist_discount_percent: 10,
+            relist_discount_after: 2,
+            ..Config::default()
+        };
+        // Maps are listed at half of sellPrice each
+        assert_eq!(relist_price(&config, 1), Money::new(5000));
+        assert_eq!(relist_price(&config, 2), Money::new(4500));
+        assert_eq!(relist_price(&config, 3), Money::new(4050));
+        assert_eq!(relist_price(&config, 4), Money::new(3645));
+        // Never below what we'd pay for a map
+        assert_eq!(relist_price(&config, 5), Money::new(3500));
+
+        let no_discount = Config { relist_discount_percent: 0, ..config };
+        assert_eq!(relist_price(&no_discount, 9), Money::new(5000));
+    }
+
+    #[test]
+    fn test_expiry_counts_reset_on_sale() {
+        let now = start();
+        let mut tracker = ListingTracker::default();
+        assert_eq!(tracker.record_expired(now, Some(3)), 1);
+        assert_eq!(tracker.record_expired(now, Some(3)), 2);
+        assert_eq!(tracker.record_expired(now, None), 1);
+
+        tracker.record_listing(now, Money::new(9900), 1, Some(3));
+        tracker.mark_sold(now, Money::new(9900), 1);
+        assert_eq!(tracker.record_expired(now, Some(3)), 1);
+    }
+
+    #[test]
+    fn test_reprice_targets() {
+        let config = Config {
+            max_buy_price: Money::new(2500),
+            reprice_after: 6 * 60 * 60 * 1000,
+            reprice_margin_percent: 10,
+            ..Config::default()
+        };
+        let now = start();
+        let mut tracker = ListingTracker::default();
+        tracker.record_listing(now - Duration::hours(1), Money::new(4950), 1, Some(1));
+        tracker.record_listing(now - Duration::hours(1), Money::new(8600), 2, Some(2));
+        tracker.record_listing(now - Duration::hours(7), Money::new(4950), 1, Some(3));
+
+        // No floor known: only the stale listing, at its current price
+        let targets = tracker.reprice_targets(now, &config);
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].map_id, Some(3));
+        assert_eq!(targets[0].new_price_per_map, Money::new(4950));
+
+        // A floor of $4,000: $4,950 is over 10% above it, $4,300 per map is not
+        tracker.set_market_floor(Money::new(4000));
+        let targets = tracker.reprice_targets(now, &config);
+        let ids: Vec<_> = targets.iter().map(|t| t.map_id).collect();
+        assert_eq!(ids, vec![Some(1), Some(3)]);
+        assert!(targets.iter().all(|t| t.new_price_per_map == Money::new(4000)));
+
+        // Never below maxBuyPrice
+        tracker.set_market_floor(Money::new(1000));
+        assert!(tracker.reprice_targets(now, &config).iter().all(|t| t.new_price_per_map == Money::new(2500)));
+
+        tracker.mark_cancelled(now, &targets[0]);
+        assert_eq!(tracker.active_count(), 2);
+    }
+
+    #[test]
+    fn test_room_under_cap() {
+        let now = start();
+        let mut tracker = ListingTracker::default();
+        assert!(tracker.has_room_for(now, 100, 0));
+
+        tracker.record_listing(now, Money::new(9900), 1, None);
+        tracker.record_listing(now, Money::new(9900), 1, None);
+        assert_eq!(tracker.learn_cap(now, None), 2);
+        assert!(!tracker.has_room_for(now, 1, 0));
+
+        assert_eq!(tracker.learn_cap(now, Some(4)), 4);
+        assert!(tracker.has_room_for(now, 2, 0));
+        assert!(!tracker.has_room_for(now, 3, 0));
+    }
+
+    #[test]
+    fn test_cap_prefers_learned_value() {
+        let now = start();
+        let mut tracker = ListingTracker::default();
+        assert_eq!(tracker.cap(now, 0), None);
+        assert_eq!(tracker.cap(now, 8), Some(8));
+        tracker.learn_cap(now, Some(5));
+        assert_eq!(tracker.cap(now, 8), Some(5));
+    }
+
+    #[test]
+    fn test_learned_cap_is_relearned() {
+        let now = start();
+        let mut tracker = ListingTracker::default();
+        tracker.learn_cap(now, Some(1));
+        assert_eq!(tracker.cap(now + Duration::minutes(59), 0), Some(1));
+        assert_eq!(tracker.cap(now + Duration::minutes(LEARNED_CAP_TTL_MINS), 8), Some(8));
+
+        // A listing the server accepts past the learned cap proves it wrong
+        tracker.learn_cap(now, Some(1));
+        tracker.record_listing(now, Money::new(9900), 1, None);
+        assert_eq!(tracker.cap(now, 0), Some(1));
+        tracker.record_listing(now, Money::new(9900), 1, None);
+        assert_eq!(tracker.cap(now, 0), None);
+    }
+}
@@ -1,49 +1,187 @@
 use azalea::prelude::*;
 use parking_lot::Mutex;
 use std::collections::HashMap;
+use std::future::Future;
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
 use tokio::time::sleep;
 use anyhow::{anyhow, Result};
-use regex::Regex;
 
 mod config;
 mod price_parser;
 mod webhook;
 mod inventory;
+mod digest;
+mod validation;
+mod reload;
+mod config_loader;
+mod cli;
+mod balance;
+mod limits;
+mod listings;
+mod capacity;
+mod session;
+mod containers;
+mod gui;
+mod chat;
+mod text;
+mod azalea_session;
+mod recording;
+mod shards;
 
-use config::Config;
-use price_parser::parse_price;
+use balance::{BalanceCheck, BalanceTracker};
+use capacity::{plan_purchase, CapacityPlan};
+use cli::Command;
+use config::{Config, JunkAction};
+use config_loader::ConfigLoader;
+use limits::{LimitCheck, LimitHit, TradingLimits};
+use listings::{relist_price, ListingTracker, RepriceEvent};
+use digest::{format_signed, DigestPeriod, DigestReport, DigestTracker};
+use reload::{plan_reload, ReloadPlan};
+use price_parser::Money;
 use webhook::send_webhook;
-use inventory::{open_auction_house, find_cheap_maps, purchase_map, list_maps, get_map_slots};
+use session::{GameSession, Window};
+use containers::ContainerManager;
+use gui::{click_button, Button};
+use chat::{ChatClassifier, Sale, ServerMessage};
+use recording::{Recorded, Recorder, SessionEvent};
+use shards::{ShardAlert, ShardTracker};
+use inventory::{open_auction_house, find_cheap_maps, purchase_map, list_maps, get_map_slots, count_maps, prices_by_slot, read_my_listings, reclaim_expired, ListedStack, market_floor, cancel_listings, inventory_model, store_maps, get_junk_slots, drop_items, list_items, store_junk};
 
 #[derive(Clone, Component)]
 pub struct BotState {
     pub is_running: Arc<Mutex<bool>>,
     pub is_afk_detected: Arc<Mutex<bool>>,
-    pub config: Arc<Config>,
-    pub awaiting_shards_response: Arc<Mutex<bool>>,
+    pub config: Arc<Mutex<Arc<Config>>>,
+    pub pending_config: Arc<Mutex<Option<ReloadPlan>>>,
+    pub config_loader: Arc<ConfigLoader>,
+    pub digest: Arc<Mutex<DigestTracker>>,
+    pub balance: Arc<Mutex<BalanceTracker>>,
+    pub shards: Arc<Mutex<ShardTracker>>,
+    pub limits: Arc<Mutex<TradingLimits>>,
+    pub listings: Arc<Mutex<ListingTracker>>,
+    /// Woken when the server refuses a listing because we're at its cap
+    pub listing_cap_reached: Arc<Notify>,
+    /// Woken when the server refuses a purchase: already bought, or not enough money
+    pub purchase_refused: Arc<Notify>,
+    pub recorder: Recorder,
+    /// Built from `chatPatterns`; rebuilt when the config is reloaded
+    pub chat: Arc<Mutex<Arc<ChatClassifier>>>,
+    /// Pollers that use the current connection's client; aborted on disconnect
+    pub session_tasks: Arc<Mutex<Vec<JoinHandle<()>>>>,
 }
 
-impl Default for BotState {
-    fn default() -> Self {
+impl BotState {
+    pub fn new(config: Config, config_loader: ConfigLoader) -> Self {
+        let digest = DigestTracker::load(&config.digest.state_file);
+        let limits = TradingLimits::load(&config.limits_state_file, chrono::Utc::now());
+        let recorder = Recorder::open(&config.record_session).unwrap_or_else(|e| {
+            eprintln!("[RECORD] Not recording this session: {:#}", e);
+            Recorder::default()
+        });
+        let chat = ChatClassifier::new(&config.chat_patterns).unwrap_or_else(|e| {
+            eprintln!("[CHAT] {:#} - using the built-in chat patterns", e);
+            ChatClassifier::default()
+        });
         Self {
             is_running: Arc::new(Mutex::new(false)),
             is_afk_detected: Arc::new(Mutex::new(false)),
-            config: Arc::new(Config::from_env()),
-            awaiting_shards_response: Arc::new(Mutex::new(false)),
+            config: Arc::new(Mutex::new(Arc::new(config))),
+            pending_config: Arc::new(Mutex::new(None)),
+            config_loader: Arc::new(config_loader),
+            digest: Arc::new(Mutex::new(digest)),
+            balance: Arc::new(Mutex::new(BalanceTracker::default())),
+            shards: Arc::new(Mutex::new(ShardTracker::default())),
+            limits: Arc::new(Mutex::new(limits)),
+            listings: Arc::new(Mutex::new(ListingTracker::default())),
+            listing_cap_reached: Arc::new(Notify::new()),
+            purchase_refused: Arc::new(Notify::new()),
+            recorder,
+            chat: Arc::new(Mutex::new(Arc::new(chat))),
+            session_tasks: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+    
+    /// Snapshot of the live config; a hot reload swaps it between cycles
+    pub fn config(&self) -> Arc<Config> {
+        self.config.lock().clone()
+    }
+    
+    pub fn chat(&self) -> Arc<ChatClassifier> {
+        self.chat.lock().clone()
+    }
+    
+    /// Run `task` until the connection it was started for goes away
+    pub fn spawn_session_task(&self, task: impl Future<Output = ()> + Send + 'static) {
+        self.session_tasks.lock().push(tokio::spawn(task));
+    }
+    
+    /// Stop every task from `spawn_session_task`
+    pub fn abort_session_tasks(&self) {
+        for task in self.session_tasks.lock().drain(..) {
+            task.abort();
         }
     }
 }
 
+/// Built-in defaults only; `main` hands the real, layered state to the client builder
+impl Default for BotState {
+    fn default() -> Self {
+        Self::new(Config::default(), ConfigLoader::new(config::CONFIG_PATH))
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
+    let cli = cli::parse(std::env::args().skip(1))?;
+    let config_path = cli.config_path.as_ref()
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(config::default_config_path);
+    let loader = ConfigLoader::new(&config_path).with_overrides(cli.overrides);
+    
+    // Subcommands that run without connecting to the server
+    match cli.command {
+        Command::Help => {
+            println!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Command::ValidateConfig(path) => {
+            let path = path.map(std::path::PathBuf::from).unwrap_or(config_path);
+            std::process::exit(validation::run_validate_command(&path));
+        }
+        Command::InitConfig(path) => {
+            let path = std::path::PathBuf::from(path.as_deref().unwrap_or("config.toml"));
+            config::write_template(&path, cli.force)?;
+            println!("[CONFIG] Wrote {} - edit it, then run with --config {}", path.display(), path.display());
+            return Ok(());
+        }
+        Command::PrintEffectiveConfig => {
+            let loaded = loader.load()?;
+            println!("# Effective configuration for {} (defaults < file < env {}* < --set)",
+                     loader.path().display(), config_loader::ENV_PREFIX);
+            for line in loaded.describe() {
+                println!("{}", line);
+            }
+            return Ok(());
+        }
+        Command::Run => {}
+    }
+    
     println!("[STARTUP] DonutSMP Map Flipper Bot (Rust/Azalea)");
     
-    // Load configuration
-    let config = Config::load()?;
+    // Load configuration: defaults, then config file, then environment, then --set
+    let loaded = loader.load()?;
+    let config = loaded.config.clone();
     println!("[CONFIG] Loaded configuration");
     
+    // Validate before touching the network so a typo doesn't cost a Microsoft login
+    let issues = validation::validate(&config, loaded.raw_file.as_ref());
+    if validation::report(&issues) {
+        return Err(anyhow!("Configuration is invalid - fix the errors above (check with `validate-config`)"));
+    }
+    
     // Log webhook configuration status
     if config.webhook.enabled {
         println!("[CONFIG] Webhook notifications: ENABLED");
@@ -81,6 +219,9 @@ async fn main() -> Result<()> {
             ("afk", config.webhook.events.afk),
             ("error", config.webhook.events.error),
             ("startup", config.webhook.events.startup),
+            ("digest", config.webhook.events.digest),
+            ("config", config.webhook.events.config),
+            ("limits", config.webhook.events.limits),
         ];
         let enabled_events: Vec<&str> = events.iter()
             .filter_map(|(name, enabled)| enabled.then_some(*name))
@@ -93,12 +234,7 @@ async fn main() -> Result<()> {
         }
     }
 
-    let _state = BotState {
-        is_running: Arc::new(Mutex::new(false)),
-        is_afk_detected: Arc::new(Mutex::new(false)),
-        config: Arc::new(config.clone()),
-        awaiting_shards_response: Arc::new(Mutex::new(false)),
-    };
+    let state = BotState::new(config.clone(), loader);
 
     // Create account based on auth type
     let account = if config.auth == "microsoft" {
@@ -116,6 +252,7 @@ async fn main() -> Result<()> {
 
     let exit_code = ClientBuilder::new()
         .set_handler(handle_event)
+        .set_state(state)
         .start(account, address.as_str())
         .await;
 
@@ -123,40 +260,34 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-/// Start AFK farming by sending /afk command and clicking slot 49
+/// Start AFK farming by sending /afk command and clicking the random location button
 /// This allows the bot to farm shards while flipping auctions
-async fn start_afk_farming(bot: Client, config: &Config) -> Result<()> {
+async fn start_afk_farming(bot: &impl GameSession, config: &Config) -> Result<()> {
     println!("[AFK] Starting AFK farming setup...");
     
-    // Step 1: Send /afk command
+    // Step 1: Send /afk command and wait for the AFK menu to open
     println!("[AFK] Sending /afk command...");
-    bot.chat("/afk");
-    
-    // Step 2: Wait for protocol timing (300ms to prevent "Invalid sequence" kick)
-    sleep(Duration::from_millis(300)).await;
-    
-    // Step 3: Wait for AFK menu to open with timeout
-    println!("[AFK] Waiting for AFK menu to open...");
-    let timeout_ticks = (config.window_timeout + 50 - 1) / 50; // Convert ms to ticks, round up
-    
-    match bot.wait_for_container_open(Some(timeout_ticks as usize)).await {
+    let mut windows = ContainerManager::new(bot, config);
+    match windows.open_with_command("/afk").await {
         Some(afk_menu) => {
             let menu_id = afk_menu.id();
             println!("[AFK] AFK menu opened with container ID: {}", menu_id);
             
-            // Step 4: Click slot 49 to go to random AFK location
-            println!("[AFK] Clicking slot 49 to teleport to random AFK location...");
-            afk_menu.left_click(49_usize);
+            // Step 2: Click the button for a random AFK location (slot 49 on DonutSMP)
+            println!("[AFK] Clicking random AFK location button...");
+            if click_button(&afk_menu, Button::AfkTeleport, &config.gui).is_none() {
+                windows.close();
+                return Err(anyhow!("No random AFK location button in the AFK menu"));
+            }
             
-            // Step 5: Wait for the click to be processed
+            // Step 3: Wait for the click to be processed
             sleep(Duration::from_millis(300)).await;
             
-            // Step 6: Properly close the container by dropping the handle
-            // This ensures Azalea sends the close packet to the server
-            drop(afk_menu);
+            // Step 4: Close the menu, unless the teleport already did
+            windows.close();
             println!("[AFK] AFK menu closed");
             
-            // Step 7: Wait for teleportation to complete
+            // Step 5: Wait for teleportation to complete
             sleep(Duration::from_millis(2000)).await;
             
             println!("[AFK] AFK farming setup completed successfully");
@@ -186,11 +317,12 @@ async fn handle_event(bot: Client, event: Event, state: BotState) -> Result<()>
             
             if was_running {
                 println!("[BOT] Reconnected after disconnect - resuming operations");
+                state.digest.lock().record_reconnect();
             }
             
             // Send startup webhook
             if let Err(e) = send_webhook(
-                &state.config,
+                &state.config(),
                 "startup",
                 if was_running {
                     "🔄 Bot reconnected and resuming operations"
@@ -199,7 +331,7 @@ async fn handle_event(bot: Client, event: Event, state: BotState) -> Result<()>
                 },
                 if was_running { 0xf39c12 } else { 0x2ecc71 },
                 vec![
-                    ("Server".to_string(), state.config.host.clone(), true),
+                    ("Server".to_string(), state.config().host.clone(), true),
                     ("Username".to_string(), bot.username().to_string(), true),
                 ],
             ).await {
@@ -207,15 +339,16 @@ async fn handle_event(bot: Client, event: Event, state: BotState) -> Result<()>
             }
             
             // Execute AFK startup action if enabled
-            if state.config.enable_afk_farming {
-                if let Err(e) = start_afk_farming(bot.clone(), &state.config).await {
-                    eprintln!("[AFK] Failed to start AFK farming: {}", e);
+            if state.config().enable_afk_farming {
+                match start_afk_farming(&Recorded::new(bot.clone(), state.recorder.clone()), &state.config()).await {
+                    Ok(()) => state.shards.lock().start_farming(chrono::Utc::now()),
+                    Err(e) => eprintln!("[AFK] Failed to start AFK farming: {}", e),
                 }
             }
             
             // Wait before starting main loop
-            println!("[BOT] Waiting {}ms before starting...", state.config.delay_after_join);
-            sleep(Duration::from_millis(state.config.delay_after_join)).await;
+            println!("[BOT] Waiting {}ms before starting...", state.config().delay_after_join);
+            sleep(Duration::from_millis(state.config().delay_after_join)).await;
             
             // Start or restart main loop
             // For reconnections, we always restart the loop regardless of the flag
@@ -231,108 +364,267 @@ async fn handle_event(bot: Client, event: Event, state: BotState) -> Result<()>
             }
             tokio::spawn(main_loop(bot.clone(), state.clone()));
             
-            // Start shards tracking task (runs every 30 minutes)
-            tokio::spawn(shards_tracking_loop(bot.clone(), state.clone()));
+            // Start the shards (every shardsCheckInterval) and balance (every
+            // balanceCheckInterval) pollers; these replace the previous connection's in
+            // case its disconnect was never seen
+            state.abort_session_tasks();
+            state.shards.lock().set_awaiting_response(false);
+            state.balance.lock().set_awaiting_response(false);
+            state.spawn_session_task(shards_tracking_loop(bot.clone(), state.clone()));
+            state.spawn_session_task(balance_tracking_loop(bot.clone(), state.clone()));
+            
+            // The digest scheduler and config watcher don't use the client,
+            // so one instance of each survives reconnects
+            if !was_running {
+                tokio::spawn(digest_loop(state.clone()));
+                tokio::spawn(config_watch_loop(state.clone()));
+            }
         }
         Event::Chat(m) => {
-            let message = m.message().to_string();
+            let message = text::flatten(&m.message());
             println!("[CHAT] {}", message);
+            state.recorder.record(SessionEvent::Chat { message: message.clone() });
             
-            // Check if we're waiting for a shards response
-            let awaiting = {
-                let awaiting_lock = state.awaiting_shards_response.lock();
-                *awaiting_lock
-            };
-            
-            if awaiting {
-                // Check if this message contains "shard" (case insensitive)
-                let normalized = normalize_text(&message);
-                if normalized.contains("shard") {
-                    println!("[SHARDS] Captured shards response: {}", message);
-                    
-                    // Send to webhook
-                    let _ = send_webhook(
-                        &state.config,
-                        "shards",
-                        &format!("📊 Shards Update: {}", message),
-                        0xf1c40f,
-                        vec![],
-                    ).await;
-                    
-                    // Reset the flag
-                    let mut awaiting_lock = state.awaiting_shards_response.lock();
-                    *awaiting_lock = false;
-                }
-            }
-            
-            // Check for AFK teleport notification
-            let normalized = normalize_text(&message);
-            if normalized.contains("teleported to") && normalized.contains("afk") {
-                println!("[AFK] Detected AFK teleport - continuing operations in AFK zone");
-                
-                // Send webhook notification
-                let _ = send_webhook(
-                    &state.config,
-                    "afk",
-                    "🌙 Teleported to AFK zone - continuing to flip auctions",
-                    0x9b59b6,
-                    vec![],
-                ).await;
-            }
-            
-            // Check for map sale
-            check_for_sale(&message, &state.config).await;
+            let parsed = state.chat().classify(&message);
+            handle_server_message(parsed, &message, &state).await;
+        }
+        Event::Disconnect(reason) => {
+            let reason = reason.map(|r| r.to_string()).unwrap_or_default();
+            println!("[BOT] Disconnected: {}", reason);
+            state.recorder.record(SessionEvent::Kicked { reason });
+            // The pollers would keep sending commands through the dead client
+            state.abort_session_tasks();
+            // Not earning shards until AFK farming is set up again on rejoin
+            state.shards.lock().stop_farming();
         }
         _ => {}
     }
     Ok(())
 }
 
-// Shards tracking interval: 30 minutes in seconds
-const SHARDS_CHECK_INTERVAL_SECS: u64 = 30 * 60;
-
 async fn shards_tracking_loop(bot: Client, state: BotState) {
+    let bot = Recorded::new(bot, state.recorder.clone());
+    
     // Wait 30 seconds before first check (give bot time to fully initialize)
     sleep(Duration::from_secs(30)).await;
     
     loop {
+        let interval = state.config().shards_check_interval;
+        if interval == 0 {
+            // Disabled; look again in case a config reload turns it on
+            sleep(Duration::from_secs(60)).await;
+            continue;
+        }
+        
         println!("[SHARDS] Sending /shards command...");
+        state.shards.lock().set_awaiting_response(true);
+        bot.chat("/shards");
+        
+        // The chat handler clears the flag when it sees the reply
+        sleep(Duration::from_secs(10)).await;
         
-        // Set flag that we're waiting for a response
         {
-            let mut awaiting = state.awaiting_shards_response.lock();
-            *awaiting = true;
+            let mut shards = state.shards.lock();
+            if shards.is_awaiting_response() {
+                println!("[SHARDS] No shards response received within timeout");
+                shards.set_awaiting_response(false);
+            }
         }
         
-        // Send /shards command
-        bot.chat("/shards");
+        println!("[SHARDS] Next shards check in {} min", interval / 60_000);
+        sleep(Duration::from_millis(interval)).await;
+    }
+}
+
+async fn balance_tracking_loop(bot: Client, state: BotState) {
+    let bot = Recorded::new(bot, state.recorder.clone());
+    
+    // Give the bot time to settle in before the first check
+    sleep(Duration::from_secs(15)).await;
+    
+    loop {
+        let interval = state.config().balance_check_interval;
+        if interval == 0 {
+            // Disabled; look again in case a config reload turns it on
+            sleep(Duration::from_secs(60)).await;
+            continue;
+        }
         
-        // Wait 10 seconds for response before timing out
-        // The chat handler will capture any message containing "shard"
+        println!("[BALANCE] Sending /bal command...");
+        state.balance.lock().set_awaiting_response(true);
+        bot.chat("/bal");
+        
+        // The chat handler clears the flag when it sees the reply
         sleep(Duration::from_secs(10)).await;
         
-        // Check if we got a response
-        let got_response = {
-            let awaiting = state.awaiting_shards_response.lock();
-            !*awaiting  // If flag is false, we got a response
+        {
+            let mut balance = state.balance.lock();
+            if balance.is_awaiting_response() {
+                println!("[BALANCE] No balance response received within timeout");
+                balance.set_awaiting_response(false);
+            }
+        }
+        
+        sleep(Duration::from_millis(interval)).await;
+    }
+}
+
+// How often the digest scheduler checks whether a window has closed and saves the digest
+// and limits state
+const DIGEST_CHECK_INTERVAL_SECS: u64 = 10;
+
+async fn digest_loop(state: BotState) {
+    loop {
+        let outstanding = state.listings.lock().active_count();
+        let reports = state.digest.lock().take_due(chrono::Utc::now(), outstanding);
+        
+        for report in reports {
+            send_digest(&report, &state.config()).await;
+        }
+        
+        let digest = state.digest.lock().take_unsaved();
+        save_state("DIGEST", digest).await;
+        let limits = state.limits.lock().take_unsaved();
+        save_state("LIMITS", limits).await;
+        sleep(Duration::from_secs(DIGEST_CHECK_INTERVAL_SECS)).await;
+    }
+}
+
+/// Write a `take_unsaved` snapshot to disk without holding its lock or blocking the runtime
+async fn save_state(tag: &str, snapshot: Option<(std::path::PathBuf, String)>) {
+    let Some((path, data)) = snapshot else {
+        return;
+    };
+    let shown = path.display().to_string();
+    match tokio::task::spawn_blocking(move || std::fs::write(&path, data)).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => eprintln!("[{}] Failed to write {}: {}", tag, shown, e),
+        Err(e) => eprintln!("[{}] Saving {} failed: {}", tag, shown, e),
+    }
+}
+
+/// Deliver a finished digest to every sink that subscribes to its period
+async fn send_digest(report: &DigestReport, config: &Config) {
+    let (to_console, to_webhook) = match report.period {
+        DigestPeriod::Hourly => (config.digest.console.hourly, config.digest.webhook.hourly),
+        DigestPeriod::Daily => (config.digest.console.daily, config.digest.webhook.daily),
+    };
+    
+    if to_console {
+        println!("[DIGEST] {}", report.title());
+        for (name, value, _) in report.fields() {
+            println!("[DIGEST]   {}: {}", name, value);
+        }
+    }
+    
+    if to_webhook {
+        if let Err(e) = send_webhook(config, "digest", &report.title(), 0x3498db, report.fields()).await {
+            eprintln!("[WEBHOOK] Error sending {} digest webhook: {}", report.period.name(), e);
+        }
+    }
+}
+
+// How often config.json is checked for modifications
+const CONFIG_WATCH_INTERVAL_SECS: u64 = 5;
+
+fn config_modified_time(path: &std::path::Path) -> Option<std::time::SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Watch the config file and stage validated changes for the main loop to apply
+async fn config_watch_loop(state: BotState) {
+    let path = state.config_loader.path().to_path_buf();
+    let mut last_modified = config_modified_time(&path);
+    
+    loop {
+        sleep(Duration::from_secs(CONFIG_WATCH_INTERVAL_SECS)).await;
+        
+        let modified = config_modified_time(&path);
+        if modified.is_none() || modified == last_modified {
+            continue;
+        }
+        last_modified = modified;
+        println!("[CONFIG] Detected change to {} - reloading", path.display());
+        
+        // Re-run every layer so environment and --set overrides still apply
+        let (candidate, raw_file) = match state.config_loader.load() {
+            Ok(loaded) => (loaded.config, loaded.raw_file),
+            Err(e) => {
+                eprintln!("[CONFIG] Reload failed, keeping current config: {:#}", e);
+                continue;
+            }
         };
         
-        if !got_response {
-            println!("[SHARDS] No shards response received within timeout");
-            // Reset flag anyway
-            let mut awaiting = state.awaiting_shards_response.lock();
-            *awaiting = false;
+        let issues = validation::validate(&candidate, raw_file.as_ref());
+        if validation::report(&issues) {
+            eprintln!("[CONFIG] Reloaded config is invalid, keeping current config");
+            let _ = send_webhook(
+                &state.config(),
+                "error",
+                "⚠️ Config reload rejected - the new config.json has errors",
+                0xe74c3c,
+                issues.iter()
+                    .filter(|i| i.is_error())
+                    .map(|i| (i.path.clone(), i.message.clone(), false))
+                    .collect(),
+            ).await;
+            continue;
+        }
+        
+        let plan = plan_reload(&state.config(), candidate);
+        for change in &plan.restart_required {
+            println!("[CONFIG] ⚠ {} requires a restart to take effect", change.describe());
         }
         
-        // Wait 30 minutes before next check
-        println!("[SHARDS] Next shards check in 30 minutes");
-        sleep(Duration::from_secs(SHARDS_CHECK_INTERVAL_SECS)).await;
+        if plan.is_empty() {
+            println!("[CONFIG] No effective changes");
+        } else {
+            if !plan.applied.is_empty() {
+                println!("[CONFIG] {} change(s) will apply at the next cycle boundary", plan.applied.len());
+            }
+            *state.pending_config.lock() = Some(plan);
+        }
+    }
+}
+
+/// Swap in a staged config reload, if there is one, and announce what changed
+async fn apply_pending_config(state: &BotState) {
+    let Some(plan) = state.pending_config.lock().take() else {
+        return;
+    };
+    
+    *state.config.lock() = Arc::new(plan.config);
+    let config = state.config();
+    match ChatClassifier::new(&config.chat_patterns) {
+        Ok(chat) => *state.chat.lock() = Arc::new(chat),
+        Err(e) => eprintln!("[CONFIG] {:#} - keeping the previous chat patterns", e),
+    }
+    
+    for change in &plan.applied {
+        println!("[CONFIG] Applied {}", change.describe());
     }
+    
+    let mut fields: Vec<(String, String, bool)> = plan.applied.iter()
+        .map(|c| (c.path.clone(), format!("{} → {}", c.old, c.new), false))
+        .collect();
+    fields.extend(plan.restart_required.iter()
+        .map(|c| (c.path.clone(), format!("{} → {} (restart required)", c.old, c.new), false)));
+    
+    let _ = send_webhook(
+        &config,
+        "config",
+        &format!("🔧 Config reloaded - {} change(s) applied", plan.applied.len()),
+        0x1abc9c,
+        fields,
+    ).await;
 }
 
 async fn main_loop(bot: Client, state: BotState) {
+    let bot = Recorded::new(bot, state.recorder.clone());
     loop {
-        match run_cycle(bot.clone(), state.clone()).await {
+        apply_pending_config(&state).await;
+        
+        match run_cycle(&bot, &state).await {
             Ok(success) => {
                 if success {
                     println!("[LOOP] Cycle completed successfully");
@@ -345,7 +637,7 @@ async fn main_loop(bot: Client, state: BotState) {
                 
                 // Send error webhook
                 let _ = send_webhook(
-                    &state.config,
+                    &state.config(),
                     "error",
                     &format!("⚠️ Bot encountered an error: {}", e),
                     0xe74c3c,
@@ -353,40 +645,299 @@ async fn main_loop(bot: Client, state: BotState) {
                 ).await;
                 
                 // Wait before retry
-                sleep(Duration::from_millis(3000.max(state.config.delay_between_cycles))).await;
+                sleep(Duration::from_millis(3000.max(state.config().delay_between_cycles))).await;
             }
         }
+
+        // A cycle that bought nothing leaves the auction house open; start the
+        // housekeeping below from the player inventory
+        ContainerManager::new(&bot, &state.config()).close();
+
+        // Between cycles, so these windows never interrupt a purchase
+        refresh_listings_if_due(&bot, &state).await;
+        reclaim_expired_if_due(&bot, &state).await;
+        reprice_if_due(&bot, &state).await;
         
         // Wait between cycles
-        sleep(Duration::from_millis(state.config.delay_between_cycles)).await;
+        sleep(Duration::from_millis(state.config().delay_between_cycles)).await;
+    }
+}
+
+fn record_listed(state: &BotState, listed: &[ListedStack]) {
+    let now = chrono::Utc::now();
+    let mut listings = state.listings.lock();
+    for stack in listed {
+        listings.record_listing(now, stack.price, stack.count, stack.map_id);
+    }
+}
+
+/// Pull expired maps back out of the auction house and relist them,
+/// discounted per `relistDiscountPercent` if they keep expiring
+async fn reclaim_expired_if_due(bot: &impl GameSession, state: &BotState) {
+    let config = state.config();
+    if config.reclaim_interval == 0 {
+        return;
+    }
+    let interval = chrono::Duration::milliseconds(config.reclaim_interval as i64);
+    if !state.listings.lock().take_reclaim_due(chrono::Utc::now(), interval) {
+        return;
+    }
+    
+    let reclaimed = match reclaim_expired(bot, &config).await {
+        Ok(reclaimed) => reclaimed,
+        Err(e) => {
+            eprintln!("[RECLAIM] Could not reclaim expired maps: {}", e);
+            return;
+        }
+    };
+    if reclaimed.is_empty() {
+        return;
+    }
+    
+    // list_maps charges unit price × count × 0.5, so double the price per map
+    let mut prices_by_id = HashMap::new();
+    {
+        let now = chrono::Utc::now();
+        let mut listings = state.listings.lock();
+        for map_id in &reclaimed {
+            let expiries = listings.record_expired(now, *map_id);
+            if let Some(id) = map_id {
+                let price = relist_price(&config, expiries);
+                if price != relist_price(&config, 0) {
+                    println!("[RECLAIM] Map #{} expired {} time(s), relisting at {} per map", id, expiries, price);
+                }
+                prices_by_id.insert(*id, price.checked_mul(2).unwrap_or(price));
+            }
+        }
+    }
+    
+    let unit_prices = prices_by_slot(bot, &prices_by_id);
+    let all_maps = get_map_slots(bot);
+    match list_maps(bot, &config, &all_maps, &unit_prices, &state.listing_cap_reached).await {
+        Ok(listed) => record_listed(state, &listed),
+        Err(e) => eprintln!("[RECLAIM] Error relisting maps: {}", e),
+    }
+    state.digest.lock().set_inventory_maps(count_maps(bot));
+}
+
+/// Take down stale or overpriced listings and relist them at the market floor
+async fn reprice_if_due(bot: &impl GameSession, state: &BotState) {
+    let config = state.config();
+    if config.reprice_interval == 0 {
+        return;
+    }
+    let now = chrono::Utc::now();
+    let interval = chrono::Duration::milliseconds(config.reprice_interval as i64);
+    let (targets, floor) = {
+        let mut listings = state.listings.lock();
+        if !listings.take_reprice_due(now, interval) {
+            return;
+        }
+        (listings.reprice_targets(now, &config), listings.market_floor())
+    };
+    if targets.is_empty() {
+        return;
+    }
+    
+    let floor = floor.map(|f| f.to_string()).unwrap_or_else(|| "unknown".to_string());
+    println!("[REPRICE] {} listing(s) to reprice (market floor: {} per map)", targets.len(), floor);
+    
+    let cancelled = match cancel_listings(bot, &config, &targets).await {
+        Ok(cancelled) => cancelled,
+        Err(e) => {
+            eprintln!("[REPRICE] Could not take listings down: {}", e);
+            return;
+        }
+    };
+    if cancelled.is_empty() {
+        return;
+    }
+    
+    // list_maps charges unit price × count × 0.5, so double the price per map. Keyed by
+    // the slot each stack came back to, so maps without an id get their price too
+    let mut unit_prices = HashMap::new();
+    {
+        let now = chrono::Utc::now();
+        let mut listings = state.listings.lock();
+        for cancelled in &cancelled {
+            let target = &cancelled.target;
+            listings.mark_cancelled(now, target);
+            unit_prices.insert(cancelled.slot, target.new_price_per_map.checked_mul(2).unwrap_or(target.new_price_per_map));
+        }
+    }
+    
+    let all_maps = get_map_slots(bot);
+    let listed = match list_maps(bot, &config, &all_maps, &unit_prices, &state.listing_cap_reached).await {
+        Ok(listed) => listed,
+        Err(e) => {
+            eprintln!("[REPRICE] Error relisting maps: {}", e);
+            return;
+        }
+    };
+    record_listed(state, &listed);
+    state.digest.lock().set_inventory_maps(count_maps(bot));
+    
+    for stack in &listed {
+        let Some(target) = cancelled.iter().find(|c| c.slot == stack.slot).map(|c| &c.target) else {
+            continue;
+        };
+        let event = RepriceEvent {
+            at: chrono::Utc::now(),
+            map_id: stack.map_id,
+            old_price: target.old_price,
+            new_price: stack.price,
+        };
+        let description = event.to_string();
+        let times = state.listings.lock().record_reprice(event);
+        println!("[REPRICE] {} (reprice #{} today)", description, times);
+        
+        let _ = send_webhook(
+            &config,
+            "listing",
+            &format!("🏷️ Repriced {}", description),
+            0x3498db,
+            vec![
+                ("Old Price".to_string(), target.old_price.to_string(), true),
+                ("New Price".to_string(), stack.price.to_string(), true),
+            ],
+        ).await;
+    }
+}
+
+// Listings expiring within this window are called out after each refresh
+const LISTING_EXPIRY_WARNING_MINS: i64 = 60;
+
+async fn refresh_listings_if_due(bot: &impl GameSession, state: &BotState) {
+    let config = state.config();
+    if config.listings_check_interval == 0 {
+        return;
+    }
+    let now = chrono::Utc::now();
+    let interval = chrono::Duration::milliseconds(config.listings_check_interval as i64);
+    if !state.listings.lock().take_refresh_due(now, interval) {
+        return;
+    }
+    
+    let observed = match read_my_listings(bot, &config).await {
+        Ok(observed) => observed,
+        Err(e) => {
+            eprintln!("[LISTINGS] Could not read listings: {}", e);
+            return;
+        }
+    };
+    
+    let now = chrono::Utc::now();
+    let sold = {
+        let mut listings = state.listings.lock();
+        let sold = listings.reconcile(now, &observed);
+        let active = listings.active_count();
+        let cap = match listings.cap(now, config.listing_cap) {
+            Some(cap) => format!("{}/{}", active, cap),
+            None => active.to_string(),
+        };
+        let expiring = listings.expiring_within(now, chrono::Duration::minutes(LISTING_EXPIRY_WARNING_MINS));
+        println!("[LISTINGS] {} active listing(s), {} expiring within {} min",
+                 cap, expiring.len(), LISTING_EXPIRY_WARNING_MINS);
+        for listing in expiring {
+            if let Some(at) = listing.expires_at {
+                println!("[LISTINGS]   {} × {} expires in {} min",
+                         listing.count, listing.price, (at - now).num_minutes().max(0));
+            }
+        }
+        sold
+    };
+    
+    // The offline summary already went to the digest and balance; the limits need the
+    // listings themselves, for the unsold maps and the profit check
+    for listing in &sold {
+        println!("[LISTINGS] Listing of {} map(s) at {} sold while offline", listing.count, listing.price);
+        let tripped = state.limits.lock().record_sale(now, listing.price, listing.count, listing.cost, &config);
+        if let Some(reason) = tripped {
+            notify_limit_tripped(&reason, &config).await;
+        }
     }
 }
 
-async fn run_cycle(bot: Client, state: BotState) -> Result<bool> {
+async fn run_cycle(bot: &impl GameSession, state: &BotState) -> Result<bool> {
     println!("[CYCLE] Starting new cycle");
     
+    // One snapshot per cycle, so a hot reload never changes settings mid-purchase
+    let config = state.config();
+    
+    if !buying_allowed(state, &config).await {
+        return Ok(false);
+    }
+    
+    clear_junk(bot, &config).await;
+    
+    if !make_room_for_purchase(bot, state, &config).await {
+        return Ok(false);
+    }
+    
     // Step 1: Open auction house
-    match open_auction_house(&bot, &state.config).await {
+    match open_auction_house(bot, &config).await {
         Ok(Some(menu)) => {
             println!("[AH] Auction house opened successfully");
             
+            if let Some(floor) = market_floor(&menu, &bot.username()) {
+                state.listings.lock().set_market_floor(floor);
+            }
+            
             // Step 2: Find cheap maps
-            if let Some(map) = find_cheap_maps(&menu, state.config.max_buy_price) {
-                println!("[AH] Found cheap map: ${} from {}", map.price, map.seller);
+            if let Some(map) = find_cheap_maps(&menu, config.max_buy_price, &bot.username()) {
+                println!("[AH] Found cheap map: {} from {}", map.price, map.seller);
+                
+                let check = state.balance.lock().check_purchase(map.price, config.min_balance_reserve);
+                if let BalanceCheck::BelowReserve { balance, remaining } = check {
+                    println!("[BALANCE] Skipping {} map: balance {} would drop to {}, below the {} reserve",
+                             map.price, balance, remaining, config.min_balance_reserve);
+                    return Ok(false);
+                }
+                
+                // Every map stack waiting in the inventory needs a listing slot too
+                let waiting = get_map_slots(bot).len() as u32 + 1;
+                if !state.listings.lock().has_room_for(chrono::Utc::now(), waiting, config.listing_cap) {
+                    println!("[LISTINGS] Skipping purchase: no room under the listing cap for {} more listing(s)", waiting);
+                    return Ok(false);
+                }
+                
+                let check = state.limits.lock().check(chrono::Utc::now(), map.price, &config);
+                match check {
+                    LimitCheck::Allowed => {}
+                    LimitCheck::Tripped(reason) => {
+                        notify_limit_tripped(&reason, &config).await;
+                        return Ok(false);
+                    }
+                    LimitCheck::Paused(reason) => {
+                        println!("[LIMITS] Buying paused: {}", reason);
+                        return Ok(false);
+                    }
+                }
                 
                 // Step 3: Attempt purchase
-                match purchase_map(&bot, &map, &state.config).await {
+                match purchase_map(bot, &map, &config, &state.purchase_refused).await {
                     Ok(true) => {
                         println!("[AH] Purchase successful!");
+                        state.digest.lock().record_purchase(map.price);
+                        state.limits.lock().record_purchase(chrono::Utc::now(), map.price);
+                        state.listings.lock().record_purchase(map.map_id, map.price);
+                        let balance = {
+                            let mut tracker = state.balance.lock();
+                            tracker.record_purchase(map.price);
+                            tracker.balance()
+                        };
+                        if let Some(balance) = balance {
+                            println!("[BALANCE] Balance now about {}", balance);
+                        }
                         
                         // Send webhook notification
                         let _ = send_webhook(
-                            &state.config,
+                            &config,
                             "purchase",
-                            &format!("💰 Purchased map for ${}", map.price),
+                            &format!("💰 Purchased map for {}", map.price),
                             0x2ecc71,
                             vec![
-                                ("Price".to_string(), format!("${}", map.price), true),
+                                ("Price".to_string(), map.price.to_string(), true),
                                 ("Seller".to_string(), map.seller.clone(), true),
                             ],
                         ).await;
@@ -394,30 +945,35 @@ async fn run_cycle(bot: Client, state: BotState) -> Result<bool> {
                         // Step 4: List ALL maps in inventory
                         // The new list_maps function handles stacks naturally by listing them one at a time
                         // This efficiently clears the entire inventory without needing a separate unstacking step
-                        let all_maps = get_map_slots(&bot);
+                        let all_maps = get_map_slots(bot);
                         
                         if !all_maps.is_empty() {
                             println!("[CYCLE] Found {} map slot(s) in inventory (including stacks)", all_maps.len());
                             println!("[CYCLE] Listing all maps to clear inventory...");
                             
-                            if let Err(e) = list_maps(&bot, &state.config, &all_maps).await {
-                                eprintln!("[LISTING] Error listing maps: {}", e);
+                            match list_maps(bot, &config, &all_maps, &HashMap::new(), &state.listing_cap_reached).await {
+                                Ok(listed) => record_listed(state, &listed),
+                                Err(e) => eprintln!("[LISTING] Error listing maps: {}", e),
                             }
                         } else {
                             println!("[CYCLE] No maps in inventory - purchase may have failed");
                         }
                         
+                        state.digest.lock().set_inventory_maps(count_maps(bot));
+                        
                         return Ok(true);
                     }
                     Ok(false) => {
-                        println!("[AH] Purchase failed (already bought or error)");
+                        println!("[AH] Purchase failed (already bought, not enough money, or no map arrived)");
+                        record_purchase_failure(state, &config).await;
                     }
                     Err(e) => {
                         eprintln!("[AH] Purchase error: {}", e);
+                        record_purchase_failure(state, &config).await;
                     }
                 }
             } else {
-                println!("[AH] No cheap maps found under ${}", state.config.max_buy_price);
+                println!("[AH] No cheap maps found under {}", config.max_buy_price);
             }
         }
         Ok(None) => {
@@ -431,45 +987,343 @@ async fn run_cycle(bot: Client, state: BotState) -> Result<bool> {
     Ok(false)
 }
 
-async fn check_for_sale(message: &str, config: &Config) {
-    // Check for map sale - format: "Username bought your Map for $price"
-    let re = Regex::new(r"(.+?)\s+bought your Map for \$([0-9,.]+)(K?)").unwrap();
+/// Resume after a tripped limit if the cooldown passed or the resume file appeared,
+/// and report whether buying may go ahead this cycle
+async fn buying_allowed(state: &BotState, config: &Config) -> bool {
+    let resume_file = std::path::Path::new(&config.resume_file);
+    let manual = resume_file.exists() && state.limits.lock().pause_reason().is_some();
+    let resumed = if manual {
+        if let Err(e) = std::fs::remove_file(resume_file) {
+            eprintln!("[LIMITS] Failed to remove {}: {}", resume_file.display(), e);
+        }
+        state.limits.lock().resume();
+        Some("manually")
+    } else {
+        let cooldown = chrono::Duration::milliseconds(config.limit_cooldown as i64);
+        state.limits.lock()
+            .resume_if_due(chrono::Utc::now(), cooldown)
+            .then_some("after the cooldown")
+    };
     
-    if let Some(caps) = re.captures(message) {
-        let buyer = caps.get(1).map(|m| m.as_str()).unwrap_or("Unknown");
-        let price_str = caps.get(2).map(|m| m.as_str()).unwrap_or("0");
-        let k_suffix = caps.get(3).map(|m| m.as_str()).unwrap_or("");
-        
-        let full_price_str = format!("Price: ${}{}", price_str, k_suffix);
-        
-        if let Some(sale_price) = parse_price(&full_price_str) {
-            println!("[SALE] {} bought a map for ${}", buyer, sale_price);
+    if let Some(how) = resumed {
+        println!("[LIMITS] Buying resumed {}", how);
+        let _ = send_webhook(
+            config,
+            "limits",
+            &format!("▶️ Buying resumed {}", how),
+            0x2ecc71,
+            vec![],
+        ).await;
+    }
+    
+    match state.limits.lock().pause_reason() {
+        Some(reason) => {
+            println!("[LIMITS] Buying paused: {}", reason);
+            false
+        }
+        None => true,
+    }
+}
+
+/// Drop, list or store everything that is neither a map nor in `keepItems`, per `junkAction`
+async fn clear_junk(bot: &impl GameSession, config: &Config) {
+    if config.junk_action == JunkAction::Off {
+        return;
+    }
+    let junk = get_junk_slots(bot, config);
+    if junk.is_empty() {
+        return;
+    }
+    
+    println!("[JUNK] {} junk stack(s) in the inventory ({:?})", junk.len(), config.junk_action);
+    let cleared = match config.junk_action {
+        JunkAction::Off => 0,
+        JunkAction::Drop => drop_items(bot, &junk).await,
+        JunkAction::List => list_items(bot, config, &junk, config.junk_price).await,
+        JunkAction::Store => store_junk(bot, config).await.unwrap_or_else(|e| {
+            eprintln!("[STORAGE] Could not store junk: {}", e);
+            0
+        }),
+    };
+    println!("[JUNK] Cleared {} of {} junk stack(s)", cleared, junk.len());
+}
+
+/// Check the next purchase has a free slot, clearing one per `fullInventoryPolicy` if not,
+/// and report whether buying may go ahead this cycle
+async fn make_room_for_purchase(bot: &impl GameSession, state: &BotState, config: &Config) -> bool {
+    let model = inventory_model(bot);
+    println!("[INVENTORY] {}", model);
+    state.digest.lock().set_free_slots(model.free_slots);
+    
+    match plan_purchase(&model, config.full_inventory_policy) {
+        CapacityPlan::Buy => return true,
+        CapacityPlan::Skip => {
+            println!("[INVENTORY] Inventory full - skipping purchases until a slot frees up");
+            return false;
+        }
+        CapacityPlan::ListFirst => {
+            println!("[INVENTORY] Inventory full - listing maps before buying");
+            let all_maps = get_map_slots(bot);
+            match list_maps(bot, config, &all_maps, &HashMap::new(), &state.listing_cap_reached).await {
+                Ok(listed) => record_listed(state, &listed),
+                Err(e) => eprintln!("[LISTING] Error listing maps: {}", e),
+            }
+        }
+        CapacityPlan::StoreFirst => {
+            println!("[INVENTORY] Inventory full - storing maps before buying");
+            if let Err(e) = store_maps(bot, config).await {
+                eprintln!("[STORAGE] Could not store maps: {}", e);
+            }
+        }
+    }
+    
+    let model = inventory_model(bot);
+    {
+        let mut digest = state.digest.lock();
+        digest.set_free_slots(model.free_slots);
+        digest.set_inventory_maps(model.maps);
+    }
+    if model.fits_purchase() {
+        println!("[INVENTORY] ✓ Made room: {}", model);
+        true
+    } else {
+        println!("[INVENTORY] Still no free slot - skipping purchases this cycle");
+        false
+    }
+}
+
+async fn record_purchase_failure(state: &BotState, config: &Config) {
+    let tripped = state.limits.lock().record_failure(chrono::Utc::now(), config);
+    if let Some(reason) = tripped {
+        notify_limit_tripped(&reason, config).await;
+    }
+}
+
+async fn notify_limit_tripped(reason: &LimitHit, config: &Config) {
+    let resume = if config.limit_cooldown > 0 {
+        format!("in {} min, or create {}", config.limit_cooldown / 60_000, config.resume_file)
+    } else {
+        format!("when {} is created", config.resume_file)
+    };
+    println!("[LIMITS] 🛑 Buying paused: {} - resuming {}", reason, resume);
+    
+    let _ = send_webhook(
+        config,
+        "limits",
+        &format!("🛑 Buying paused: {}", reason),
+        0xe67e22,
+        vec![("Resumes".to_string(), resume, false)],
+    ).await;
+}
+
+/// React to a classified chat line
+async fn handle_server_message(parsed: ServerMessage, message: &str, state: &BotState) {
+    match parsed {
+        ServerMessage::ShardBalance { shards } => {
+            // Only a reply to our own /shards counts
+            let awaiting = state.shards.lock().is_awaiting_response();
+            if !awaiting {
+                return;
+            }
+            match shards {
+                Some(shards) => record_shards(shards, state).await,
+                None => {
+                    // A reply we can't read a number from; pass it on as it is
+                    println!("[SHARDS] Captured shards response: {}", message);
+                    state.shards.lock().set_awaiting_response(false);
+                    state.digest.lock().set_shard_balance(message.to_string());
+                    let _ = send_webhook(
+                        &state.config(),
+                        "shards",
+                        &format!("📊 Shards Update: {}", message),
+                        0xf1c40f,
+                        vec![],
+                    ).await;
+                }
+            }
+        }
+        ServerMessage::Balance { amount } => {
+            // Only a reply to our own /bal counts
+            let awaiting_balance = state.balance.lock().is_awaiting_response();
+            if awaiting_balance {
+                println!("[BALANCE] Balance is {}", amount);
+                state.balance.lock().set(amount);
+            }
+        }
+        ServerMessage::ListingCapReached { cap } => {
+            let cap = state.listings.lock().learn_cap(chrono::Utc::now(), cap);
+            println!("[LISTINGS] Server listing cap reached ({} listings)", cap);
+            state.listing_cap_reached.notify_waiters();
+        }
+        ServerMessage::AfkTeleport => {
+            println!("[AFK] Detected AFK teleport - continuing operations in AFK zone");
             
+            // Send webhook notification
             let _ = send_webhook(
-                config,
-                "sale",
-                "💰 Sold a map!",
-                0x57eb8b,
-                vec![
-                    ("Buyer".to_string(), buyer.to_string(), true),
-                    ("Price".to_string(), format!("${}", sale_price), true),
-                ],
+                &state.config(),
+                "afk",
+                "🌙 Teleported to AFK zone - continuing to flip auctions",
+                0x9b59b6,
+                vec![],
             ).await;
         }
+        ServerMessage::Sale(sale) => record_sale(&sale, state).await,
+        ServerMessage::OfflineSales { count, total } => record_offline_sales(count, total, state).await,
+        // purchase_map reports these as failed purchases, which count towards the breaker
+        ServerMessage::AlreadyBought => {
+            println!("[AH] Server says the auction was already bought by someone else");
+            state.purchase_refused.notify_waiters();
+        }
+        ServerMessage::InsufficientFunds => {
+            println!("[BALANCE] Server says we don't have enough money - the tracked balance may be stale");
+            state.purchase_refused.notify_waiters();
+        }
+        ServerMessage::Unknown => {}
+    }
+}
+
+/// Record a `/shards` reading, report the farming rate, and alert when farming stalls
+/// or recovers
+async fn record_shards(shards: u64, state: &BotState) {
+    let config = state.config();
+    let stall_after = chrono::Duration::milliseconds(config.shard_stall_after as i64);
+    let update = state.shards.lock().record(chrono::Utc::now(), shards, stall_after);
+    
+    let mut summary = format!("{} shards", update.shards);
+    let mut details = Vec::new();
+    if let Some(change) = update.change {
+        details.push(format!("{:+} since the last check", change));
+    }
+    if let Some(rate) = update.rate_per_hour {
+        details.push(format!("{}/h while AFK farming", rate));
+    }
+    if !details.is_empty() {
+        summary = format!("{} ({})", summary, details.join(", "));
+    }
+    println!("[SHARDS] {}", summary);
+    state.digest.lock().set_shard_balance(match update.rate_per_hour {
+        Some(rate) => format!("{} ({}/h)", update.shards, rate),
+        None => update.shards.to_string(),
+    });
+    
+    let mut fields = vec![("Shards".to_string(), update.shards.to_string(), true)];
+    if let Some(change) = update.change {
+        fields.push(("Change".to_string(), format!("{:+}", change), true));
+    }
+    if let Some(rate) = update.rate_per_hour {
+        fields.push(("Per Hour".to_string(), rate.to_string(), true));
+    }
+    let _ = send_webhook(
+        &config,
+        "shards",
+        &format!("📊 Shards Update: {}", summary),
+        0xf1c40f,
+        fields,
+    ).await;
+    
+    match update.alert {
+        Some(ShardAlert::Stalled { since }) => {
+            let minutes = (chrono::Utc::now() - since).num_minutes();
+            println!("[SHARDS] ⚠ No shards earned for {} min while AFK farming - AFK farming may have stopped working", minutes);
+            let _ = send_webhook(
+                &config,
+                "shards",
+                &format!("⚠️ No shards earned for {} min - AFK farming may have stopped working", minutes),
+                0xe74c3c,
+                vec![("Shards".to_string(), update.shards.to_string(), true)],
+            ).await;
+        }
+        Some(ShardAlert::Resumed) => {
+            println!("[SHARDS] Earning shards again");
+            let _ = send_webhook(
+                &config,
+                "shards",
+                "✅ Earning shards again",
+                0x2ecc71,
+                vec![("Shards".to_string(), update.shards.to_string(), true)],
+            ).await;
+        }
+        None => {}
+    }
+}
+
+/// Book a sale with every tracker; returns its profit when we know what the maps cost
+async fn book_sale(sale: &Sale, state: &BotState) -> Option<i64> {
+    state.digest.lock().record_sale(sale.price);
+    state.balance.lock().record_sale(sale.price);
+    // Junk listings aren't tracked and weren't bought
+    if !sale.is_map() {
+        return None;
+    }
+    
+    let now = chrono::Utc::now();
+    let (cost, profit) = match state.listings.lock().mark_sold(now, sale.price, sale.quantity) {
+        Some(listing) => {
+            println!("[LISTINGS] Sale matched our {} listing of {} map(s) from {} min ago",
+                     listing.price, listing.count, (now - listing.listed_at).num_minutes());
+            (listing.cost, listing.profit(sale.price))
+        }
+        None => (None, None),
+    };
+    let config = state.config();
+    let tripped = state.limits.lock().record_sale(now, sale.price, sale.quantity, cost, &config);
+    if let Some(reason) = tripped {
+        notify_limit_tripped(&reason, &config).await;
     }
+    profit
 }
 
-fn normalize_text(text: &str) -> String {
-    // Map for small caps to ASCII
-    let small_caps: HashMap<char, char> = [
-        ('ᴀ', 'a'), ('ʙ', 'b'), ('ᴄ', 'c'), ('ᴅ', 'd'), ('ᴇ', 'e'), ('ꜰ', 'f'),
-        ('ɢ', 'g'), ('ʜ', 'h'), ('ɪ', 'i'), ('ᴊ', 'j'), ('ᴋ', 'k'), ('ʟ', 'l'),
-        ('ᴍ', 'm'), ('ɴ', 'n'), ('ᴏ', 'o'), ('ᴘ', 'p'), ('ʀ', 'r'), ('ꜱ', 's'),
-        ('ᴛ', 't'), ('ᴜ', 'u'), ('ᴠ', 'v'), ('ᴡ', 'w'), ('ʏ', 'y'), ('ᴢ', 'z'),
-    ].iter().cloned().collect();
-    
-    text.chars()
-        .map(|c| small_caps.get(&c).copied().unwrap_or(c))
-        .collect::<String>()
-        .to_lowercase()
+/// Book a sale of one of our listings, e.g. "Username bought your Map x3 for $price"
+async fn record_sale(sale: &Sale, state: &BotState) {
+    let item = if sale.quantity > 1 {
+        format!("{} x{}", sale.item, sale.quantity)
+    } else {
+        sale.item.clone()
+    };
+    let profit = book_sale(sale, state).await;
+    match profit {
+        Some(profit) => println!("[SALE] {} bought {} for {} (profit {})", sale.buyer, item, sale.price, format_signed(profit)),
+        None => println!("[SALE] {} bought {} for {}", sale.buyer, item, sale.price),
+    }
+    
+    let mut fields = vec![
+        ("Buyer".to_string(), sale.buyer.clone(), true),
+        ("Item".to_string(), item.clone(), true),
+        ("Price".to_string(), sale.price.to_string(), true),
+    ];
+    if let Some(profit) = profit {
+        fields.push(("Profit".to_string(), format_signed(profit), true));
+    }
+    let _ = send_webhook(
+        &state.config(),
+        "sale",
+        &format!("💰 Sold {}!", item),
+        0x57eb8b,
+        fields,
+    ).await;
+}
+
+/// Book the sales the server summarises on login, e.g. "While you were offline, you
+/// sold 3 items for $29.7K"
+///
+/// The summary doesn't say which listings sold or for how much each, so it is booked as
+/// one digest entry and no listing is closed from it. The listings refresh is brought
+/// forward instead, and marks up to `count` of the listings that are gone as sold.
+async fn record_offline_sales(count: Option<u32>, total: Money, state: &BotState) {
+    let count = count.unwrap_or(1).max(1);
+    println!("[SALE] Sold {} listing(s) for {} while offline", count, total);
+    state.digest.lock().record_sales(count, total);
+    state.balance.lock().record_sale(total);
+    state.listings.lock().expect_offline_sales(count);
+    
+    let _ = send_webhook(
+        &state.config(),
+        "sale",
+        &format!("💰 Sold {} listing(s) while offline", count),
+        0x57eb8b,
+        vec![
+            ("Sales".to_string(), count.to_string(), true),
+            ("Total".to_string(), total.to_string(), true),
+        ],
+    ).await;
 }
@@ -1,73 +1,404 @@
 use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use crate::text::normalize;
 
-/// Parse price from text containing format like "Price: $995" or "Price: $5K" or "Price: $9.9K"
-pub fn parse_price(text: &str) -> Option<u32> {
-    // Remove Minecraft color codes (§x format)
-    let clean_text = strip_minecraft_colors(text);
-    
-    // Match price patterns: $995, $5K, $9.9K, $10,000, etc.
-    let re = Regex::new(r"\$([0-9,.]+)([Kk]?)").ok()?;
-    let caps = re.captures(&clean_text)?;
-    
-    let number_str = caps.get(1)?.as_str().replace(",", "");
-    let suffix = caps.get(2).map(|m| m.as_str()).unwrap_or("");
-    
-    let base_number: f64 = number_str.parse().ok()?;
-    
-    let price = if suffix.eq_ignore_ascii_case("K") {
-        (base_number * 1000.0) as u32
-    } else {
-        base_number as u32
+/// Suffixes the server uses and accepts, with their multipliers
+const SUFFIXES: [(char, u64); 4] = [
+    ('k', 1_000),
+    ('m', 1_000_000),
+    ('b', 1_000_000_000),
+    ('t', 1_000_000_000_000),
+];
+
+/// A whole-dollar amount of in-game money
+///
+/// Parses everything the server prints ("$995", "$9.9K", "$1.25M", "$10,000",
+/// "$1 000", small-caps "9.9ᴋ") without going through floating point, and formats
+/// back to the shortest string `/ah sell` accepts via [`Money::to_compact`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Money(u64);
+
+impl Money {
+    pub const ZERO: Money = Money(0);
+
+    pub const fn new(dollars: u64) -> Self {
+        Money(dollars)
+    }
+
+    pub const fn dollars(self) -> u64 {
+        self.0
+    }
+
+    pub fn checked_add(self, other: Money) -> Option<Money> {
+        self.0.checked_add(other.0).map(Money)
+    }
+
+    pub fn checked_sub(self, other: Money) -> Option<Money> {
+        self.0.checked_sub(other.0).map(Money)
+    }
+
+    pub fn checked_mul(self, factor: u64) -> Option<Money> {
+        self.0.checked_mul(factor).map(Money)
+    }
+
+    pub fn checked_div(self, divisor: u64) -> Option<Money> {
+        self.0.checked_div(divisor).map(Money)
+    }
+
+    /// Division that rounds up, so a share of a price is never undercharged
+    pub fn checked_div_ceil(self, divisor: u64) -> Option<Money> {
+        if divisor == 0 {
+            return None;
+        }
+        Some(Money(self.0.div_ceil(divisor)))
+    }
+
+    pub fn saturating_add(self, other: Money) -> Money {
+        Money(self.0.saturating_add(other.0))
+    }
+
+    pub fn saturating_sub(self, other: Money) -> Money {
+        Money(self.0.saturating_sub(other.0))
+    }
+
+    /// Shortest exact representation the server accepts: a plain integer, or a
+    /// `k`/`m`/`b`/`t` suffix with at most one decimal ("9.9k", "2m", "995").
+    /// Ties go to the suffixed form.
+    pub fn to_compact(self) -> String {
+        let mut best = self.0.to_string();
+
+        for (suffix, unit) in SUFFIXES {
+            if self.0 < unit || !self.0.is_multiple_of(unit / 10) {
+                continue;
+            }
+            let whole = self.0 / unit;
+            let tenths = (self.0 % unit) / (unit / 10);
+            let candidate = if tenths == 0 {
+                format!("{}{}", whole, suffix)
+            } else {
+                format!("{}.{}{}", whole, tenths, suffix)
+            };
+            if candidate.len() <= best.len() {
+                best = candidate;
+            }
+        }
+
+        best
+    }
+}
+
+/// Formats as "$9,900"
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.0.to_string();
+        let mut grouped = String::with_capacity(digits.len() + digits.len() / 3 + 1);
+        for (i, c) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                grouped.push(',');
+            }
+            grouped.push(c);
+        }
+        write!(f, "${}", grouped)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseMoneyError(String);
+
+impl fmt::Display for ParseMoneyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid price \"{}\" - use a number like \"9900\", \"9.9k\" or \"1.5m\"", self.0)
+    }
+}
+
+impl std::error::Error for ParseMoneyError {}
+
+/// Parses a bare amount with an optional leading `$`: "9.9k", "$10,000", "1.5M"
+impl FromStr for Money {
+    type Err = ParseMoneyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let folded = normalize(s);
+        let caps = amount_regex()
+            .captures(folded.trim())
+            .filter(|caps| caps.get(0).map(|m| m.as_str().len()) == Some(folded.trim().len()))
+            .ok_or_else(|| ParseMoneyError(s.to_string()))?;
+
+        let number = caps.get(1).map(|m| m.as_str()).unwrap_or("");
+        let suffix = caps.get(2).map(|m| m.as_str()).unwrap_or("");
+        amount_from_parts(number, suffix).ok_or_else(|| ParseMoneyError(s.to_string()))
+    }
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.0)
+    }
+}
+
+/// Accepts either a number (`2500`) or a string (`"9.9k"`)
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Number(u64),
+            Text(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Number(n) => Ok(Money(n)),
+            Raw::Text(s) => s.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+// Digits with optional thousands separators (comma or space) and decimals, then an optional suffix
+const AMOUNT_PATTERN: &str = r"(?i)((?:[0-9]{1,3}(?:[,\s][0-9]{3})+|[0-9]+)(?:\.[0-9]+)?)\s*([kmbt])?\b";
+
+fn amount_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(&format!(r"^\$?\s*{}", AMOUNT_PATTERN)).unwrap())
+}
+
+fn price_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(&format!(r"\$\s*{}", AMOUNT_PATTERN)).unwrap())
+}
+
+/// Exact decimal arithmetic: "9.95" + "k" → 9950. Fractions of a dollar are truncated.
+fn amount_from_parts(number: &str, suffix: &str) -> Option<Money> {
+    let number: String = number.chars().filter(|c| c.is_ascii_digit() || *c == '.').collect();
+    let (whole, fraction) = number.split_once('.').unwrap_or((&number, ""));
+
+    let multiplier = match suffix.chars().next() {
+        None => 1,
+        Some(c) => SUFFIXES.iter()
+            .find(|(s, _)| *s == c.to_ascii_lowercase())
+            .map(|(_, unit)| *unit)?,
     };
-    
-    Some(price)
+
+    let whole: u128 = if whole.is_empty() { 0 } else { whole.parse().ok()? };
+    let mut total = whole.checked_mul(multiplier as u128)?;
+
+    if !fraction.is_empty() {
+        // Digits beyond what the multiplier can resolve are below a dollar
+        let digits = fraction.len().min(19);
+        let fraction_value: u128 = fraction[..digits].parse().ok()?;
+        let scale = 10u128.pow(digits as u32);
+        total = total.checked_add(fraction_value * multiplier as u128 / scale)?;
+    }
+
+    u64::try_from(total).ok().map(Money)
+}
+
+/// Parse price from text containing format like "Price: $995", "Price: $5K" or "Price: $1.2M"
+pub fn parse_price(text: &str) -> Option<Money> {
+    let clean_text = normalize(text);
+
+    let caps = price_regex().captures(&clean_text)?;
+    let number = caps.get(1)?.as_str();
+    let suffix = caps.get(2).map(|m| m.as_str()).unwrap_or("");
+
+    amount_from_parts(number, suffix)
 }
 
-/// Strip Minecraft color codes from text
-pub fn strip_minecraft_colors(text: &str) -> String {
-    let re = Regex::new(r"§[0-9a-fk-or]").unwrap();
-    re.replace_all(text, "").to_string()
+/// Format a price the way `/ah sell` expects it, e.g. "9.9k", "1.5m" or "14850"
+pub fn format_price(price: Money) -> String {
+    price.to_compact()
 }
 
-/// Format a price value into a string like "9.9k" or "316.8k"
-pub fn format_price(price: u32) -> String {
-    if price >= 1000 {
-        let k_value = price as f64 / 1000.0;
-        // Format to 1 decimal place (rounds during formatting)
-        format!("{:.1}k", k_value)
-    } else {
-        price.to_string()
+/// A price encoded for `/ah sell`, with the amount the listing will actually ask for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListingPrice {
+    /// What we wanted to list at
+    pub intended: Money,
+    /// What the server will parse `text` as; never below `intended`
+    pub listed: Money,
+    /// Argument for `/ah sell`
+    pub text: String,
+}
+
+impl ListingPrice {
+    /// Extra asked on top of the intended price because of rounding
+    pub fn surplus(&self) -> Money {
+        self.listed.saturating_sub(self.intended)
     }
 }
 
+/// Encode `intended` for `/ah sell`
+///
+/// By default the exact amount is sent, in the shortest form that still parses back to
+/// the same value ("9.9k", but "14850" rather than "14.8k"). With `compact` set, amounts
+/// that need more than one decimal are rounded *up* to the next one-decimal `k`/`m`/`b`/`t`
+/// value ("14.9k"), and the rounding shows up in [`ListingPrice::surplus`].
+pub fn encode_listing_price(intended: Money, compact: bool) -> ListingPrice {
+    let listed = if compact { round_up_to_compact(intended) } else { intended };
+    let text = listed.to_compact();
+    debug_assert_eq!(text.parse::<Money>(), Ok(listed), "listing text must parse back exactly");
+
+    ListingPrice { intended, listed, text }
+}
+
+/// Smallest amount >= `price` that has a one-decimal suffixed form
+fn round_up_to_compact(price: Money) -> Money {
+    let Some(&(_, unit)) = SUFFIXES.iter().rev().find(|(_, unit)| price.0 >= *unit) else {
+        // Below 1k the plain integer is already short and exact
+        return price;
+    };
+    let step = unit / 10;
+    price.0.div_ceil(step)
+        .checked_mul(step)
+        .map(Money)
+        .unwrap_or(price)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+    use proptest::prelude::*;
+
+    fn money(n: u64) -> Option<Money> {
+        Some(Money::new(n))
+    }
+
     #[test]
     fn test_parse_price() {
-        assert_eq!(parse_price("Price: $995"), Some(995));
-        assert_eq!(parse_price("Price: $5K"), Some(5000));
-        assert_eq!(parse_price("Price: $9.9K"), Some(9900));
-        assert_eq!(parse_price("Price: $10,000"), Some(10000));
-        assert_eq!(parse_price("Price: $2.5k"), Some(2500));
-        assert_eq!(parse_price("§aPrice: §6$995"), Some(995));
-        assert_eq!(parse_price("§aPrice: §6$5K"), Some(5000));
-    }
-    
+        assert_eq!(parse_price("Price: $995"), money(995));
+        assert_eq!(parse_price("Price: $5K"), money(5000));
+        assert_eq!(parse_price("Price: $9.9K"), money(9900));
+        assert_eq!(parse_price("Price: $10,000"), money(10000));
+        assert_eq!(parse_price("Price: $2.5k"), money(2500));
+        assert_eq!(parse_price("§aPrice: §6$995"), money(995));
+        assert_eq!(parse_price("§aPrice: §6$5K"), money(5000));
+    }
+
+    #[test]
+    fn test_parse_price_suffixes_and_separators() {
+        assert_eq!(parse_price("Price: $9.99K"), money(9990));
+        assert_eq!(parse_price("Price: $1.25M"), money(1_250_000));
+        assert_eq!(parse_price("Price: $3B"), money(3_000_000_000));
+        assert_eq!(parse_price("Price: $4.5T"), money(4_500_000_000_000));
+        assert_eq!(parse_price("Price: $1 000 000"), money(1_000_000));
+        assert_eq!(parse_price("Price: $12,345,678"), money(12_345_678));
+        assert_eq!(parse_price("ᴘʀɪᴄᴇ: $9.9ᴋ"), money(9900));
+        assert_eq!(parse_price("§x§f§f§a§a§0§0Price: ＄１．５Ｍ"), money(1_500_000));
+        // A word after the amount is not a suffix
+        assert_eq!(parse_price("Price: $5 total"), money(5));
+        assert_eq!(parse_price("No price here"), None);
+    }
+
+    #[test]
+    fn test_money_from_str() {
+        assert_eq!("9.9k".parse(), Ok(Money::new(9900)));
+        assert_eq!("$10,000".parse(), Ok(Money::new(10000)));
+        assert_eq!("2M".parse(), Ok(Money::new(2_000_000)));
+        assert!("abc".parse::<Money>().is_err());
+        assert!("9.9x".parse::<Money>().is_err());
+        assert!("99999999999t".parse::<Money>().is_err());
+    }
+
+    #[test]
+    fn test_money_display() {
+        assert_eq!(Money::new(0).to_string(), "$0");
+        assert_eq!(Money::new(995).to_string(), "$995");
+        assert_eq!(Money::new(9900).to_string(), "$9,900");
+        assert_eq!(Money::new(1_234_567).to_string(), "$1,234,567");
+    }
+
     #[test]
-    fn test_strip_minecraft_colors() {
-        assert_eq!(strip_minecraft_colors("§aHello §6World"), "Hello World");
-        assert_eq!(strip_minecraft_colors("Normal text"), "Normal text");
-        assert_eq!(strip_minecraft_colors("§k§l§m§n§oTest"), "Test");
+    fn test_money_serde() {
+        let from_number: Money = serde_json::from_str("2500").unwrap();
+        let from_text: Money = serde_json::from_str("\"9.9k\"").unwrap();
+        assert_eq!(from_number, Money::new(2500));
+        assert_eq!(from_text, Money::new(9900));
+        assert_eq!(serde_json::to_string(&from_text).unwrap(), "9900");
+        assert!(serde_json::from_str::<Money>("\"abc\"").is_err());
     }
-    
+
     #[test]
     fn test_format_price() {
-        assert_eq!(format_price(995), "995");
-        assert_eq!(format_price(5000), "5.0k");
-        assert_eq!(format_price(9900), "9.9k");
-        assert_eq!(format_price(316800), "316.8k");
-        assert_eq!(format_price(1000), "1.0k");
+        assert_eq!(format_price(Money::new(995)), "995");
+        assert_eq!(format_price(Money::new(5000)), "5k");
+        assert_eq!(format_price(Money::new(9900)), "9.9k");
+        assert_eq!(format_price(Money::new(316800)), "316.8k");
+        assert_eq!(format_price(Money::new(1000)), "1k");
+        assert_eq!(format_price(Money::new(14850)), "14850");
+        assert_eq!(format_price(Money::new(1_500_000)), "1.5m");
+        assert_eq!(format_price(Money::new(2_000_000_000)), "2b");
+    }
+
+    #[test]
+    fn test_encode_listing_price() {
+        // (intended, compact, expected text, expected listed amount)
+        let cases: &[(u64, bool, &str, u64)] = &[
+            (995, false, "995", 995),
+            (995, true, "995", 995),
+            (1000, false, "1k", 1000),
+            (1050, false, "1050", 1050),
+            (1050, true, "1.1k", 1100),
+            (9900, false, "9.9k", 9900),
+            (9900, true, "9.9k", 9900),
+            (14850, false, "14850", 14850),
+            (14850, true, "14.9k", 14900),
+            (14801, true, "14.9k", 14900),
+            (316800, false, "316.8k", 316800),
+            (999_950, false, "999950", 999_950),
+            (999_950, true, "1m", 1_000_000),
+            (1_234_567, false, "1234567", 1_234_567),
+            (1_234_567, true, "1.3m", 1_300_000),
+            (2_000_000_000, true, "2b", 2_000_000_000),
+            (0, true, "0", 0),
+        ];
+
+        for &(intended, compact, text, listed) in cases {
+            let price = encode_listing_price(Money::new(intended), compact);
+            assert_eq!(price.text, text, "text for {} (compact={})", intended, compact);
+            assert_eq!(price.listed, Money::new(listed), "listed for {} (compact={})", intended, compact);
+            assert_eq!(price.surplus(), Money::new(listed - intended));
+        }
+    }
+
+    #[test]
+    fn test_checked_div_ceil() {
+        // Half of an odd total rounds up instead of losing a dollar
+        assert_eq!(Money::new(9900 * 3).checked_div_ceil(2), Some(Money::new(14850)));
+        assert_eq!(Money::new(995 * 3).checked_div_ceil(2), Some(Money::new(1493)));
+        assert_eq!(Money::new(1).checked_div_ceil(0), None);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_listing_price_never_undercuts(n in 0u64..=u64::MAX / 10, compact in any::<bool>()) {
+            let price = encode_listing_price(Money::new(n), compact);
+            prop_assert!(price.listed >= price.intended);
+            prop_assert_eq!(price.text.parse::<Money>(), Ok(price.listed));
+        }
+
+        #[test]
+        fn prop_compact_round_trips(n in 0u64..=u64::MAX / 10) {
+            let m = Money::new(n);
+            prop_assert_eq!(m.to_compact().parse::<Money>(), Ok(m));
+        }
+
+        #[test]
+        fn prop_display_round_trips(n in any::<u64>()) {
+            let m = Money::new(n);
+            prop_assert_eq!(m.to_string().parse::<Money>(), Ok(m));
+        }
+
+        #[test]
+        fn prop_compact_is_never_longer_than_plain(n in any::<u64>()) {
+            prop_assert!(Money::new(n).to_compact().len() <= n.to_string().len());
+        }
+
+        #[test]
+        fn prop_one_decimal_k_is_exact(whole in 0u64..1_000_000, tenths in 0u64..10) {
+            let text = format!("${}.{}K", whole, tenths);
+            prop_assert_eq!(parse_price(&text), Some(Money::new(whole * 1000 + tenths * 100)));
+        }
     }
 }
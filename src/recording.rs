@@ -0,0 +1,420 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::Arc;
+use crate::session::{GameSession, Slots, Window};
+
+/// How a slot was clicked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ClickKind {
+    Left,
+    Right,
+    Shift,
+    Throw,
+}
+
+/// Something the bot did or saw during a session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum SessionEvent {
+    /// A chat message or command the bot sent
+    Command { command: String },
+    Click { window: i32, slot: usize, kind: ClickKind },
+    SelectHotbar { slot: u8 },
+    Close { window: i32 },
+    /// The window the bot was looking at, recorded only when it changes
+    CurrentWindow { id: i32 },
+    /// A window's full contents, lore included; `None` once it had closed
+    Contents { window: i32, slots: Option<Slots> },
+    Chat { message: String },
+    Kicked { reason: String },
+}
+
+/// One line of a recording file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    pub at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub event: SessionEvent,
+}
+
+/// Appends session events to a JSON Lines file; does nothing when `recordSession` is empty
+#[derive(Clone, Default)]
+pub struct Recorder {
+    file: Option<Arc<Mutex<File>>>,
+}
+
+impl Recorder {
+    pub fn open(path: &str) -> Result<Self> {
+        if path.is_empty() {
+            return Ok(Self::default());
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open session recording {}", path))?;
+        Ok(Self { file: Some(Arc::new(Mutex::new(file))) })
+    }
+
+    pub fn record(&self, event: SessionEvent) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        let line = RecordedEvent { at: Utc::now(), event };
+        let result = serde_json::to_string(&line)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| writeln!(file.lock(), "{}", json).map_err(anyhow::Error::from));
+        if let Err(e) = result {
+            eprintln!("[RECORD] Failed to record session event: {}", e);
+        }
+    }
+}
+
+/// A game session whose commands, clicks and observations are written to a `Recorder`
+pub struct Recorded<S> {
+    inner: S,
+    recorder: Recorder,
+    /// The window id recorded last; waiting for a container polls it every tick
+    last_window: Mutex<Option<i32>>,
+}
+
+impl<S> Recorded<S> {
+    pub fn new(inner: S, recorder: Recorder) -> Self {
+        Self { inner, recorder, last_window: Mutex::new(None) }
+    }
+}
+
+pub struct RecordedWindow<W> {
+    inner: W,
+    recorder: Recorder,
+}
+
+impl<W: Window> RecordedWindow<W> {
+    fn click(&self, slot: usize, kind: ClickKind) {
+        self.recorder.record(SessionEvent::Click { window: self.inner.id(), slot, kind });
+    }
+}
+
+impl<W: Window> Window for RecordedWindow<W> {
+    fn id(&self) -> i32 {
+        self.inner.id()
+    }
+
+    fn contents(&self) -> Option<Slots> {
+        let slots = self.inner.contents();
+        self.recorder.record(SessionEvent::Contents { window: self.inner.id(), slots: slots.clone() });
+        slots
+    }
+
+    fn left_click(&self, slot: usize) {
+        self.click(slot, ClickKind::Left);
+        self.inner.left_click(slot);
+    }
+
+    fn right_click(&self, slot: usize) {
+        self.click(slot, ClickKind::Right);
+        self.inner.right_click(slot);
+    }
+
+    fn shift_click(&self, slot: usize) {
+        self.click(slot, ClickKind::Shift);
+        self.inner.shift_click(slot);
+    }
+
+    fn throw_stack(&self, slot: usize) {
+        self.click(slot, ClickKind::Throw);
+        self.inner.throw_stack(slot);
+    }
+
+    fn close(&self) {
+        self.recorder.record(SessionEvent::Close { window: self.inner.id() });
+        self.inner.close();
+    }
+}
+
+impl<S: GameSession + Sync> GameSession for Recorded<S> {
+    type Inventory = RecordedWindow<S::Inventory>;
+
+    fn chat(&self, message: &str) {
+        self.recorder.record(SessionEvent::Command { command: message.to_string() });
+        self.inner.chat(message);
+    }
+
+    fn username(&self) -> String {
+        self.inner.username()
+    }
+
+    fn current_window(&self) -> Self::Inventory {
+        let window = self.inner.current_window();
+        let id = window.id();
+        if self.last_window.lock().replace(id) != Some(id) {
+            self.recorder.record(SessionEvent::CurrentWindow { id });
+        }
+        RecordedWindow { inner: window, recorder: self.recorder.clone() }
+    }
+
+    fn select_hotbar_slot(&self, slot: u8) {
+        self.recorder.record(SessionEvent::SelectHotbar { slot });
+        self.inner.select_hotbar_slot(slot);
+    }
+}
+
+/// Plays a recording back to the trading flows in place of the server
+///
+/// What the bot saw is served back in recorded order. What it did must match the
+/// recording action for action; the first difference is kept as the divergence.
+#[cfg(test)]
+pub mod replay {
+    use super::*;
+
+    /// Whether the bot decided to do this, as opposed to seeing it
+    fn is_action(event: &SessionEvent) -> bool {
+        matches!(
+            event,
+            SessionEvent::Command { .. } | SessionEvent::Click { .. } | SessionEvent::SelectHotbar { .. } | SessionEvent::Close { .. }
+        )
+    }
+
+    struct State {
+        events: Vec<SessionEvent>,
+        /// Index of the first event not yet replayed
+        next: usize,
+        divergence: Option<String>,
+    }
+
+    impl State {
+        /// Index of the next action the recording expects, or the end
+        fn next_action(&self) -> usize {
+            self.events[self.next..].iter()
+                .position(is_action)
+                .map_or(self.events.len(), |i| self.next + i)
+        }
+
+        fn act(&mut self, action: SessionEvent) {
+            if self.divergence.is_some() {
+                return;
+            }
+            let at = self.next_action();
+            match self.events.get(at) {
+                Some(recorded) if *recorded == action => self.next = at + 1,
+                recorded => {
+                    self.divergence = Some(format!(
+                        "event {}: the bot did {:?}, but the recording has {:?}",
+                        at + 1, action, recorded
+                    ));
+                }
+            }
+        }
+
+        /// The next observation picked out by `pick` before the next recorded action,
+        /// or failing that the latest one already replayed (nothing changed since)
+        fn observe<T>(&mut self, pick: impl Fn(&SessionEvent) -> Option<T>) -> Option<T> {
+            let until = self.next_action();
+            if let Some((i, found)) = (self.next..until).find_map(|i| pick(&self.events[i]).map(|found| (i, found))) {
+                self.next = i + 1;
+                return Some(found);
+            }
+            self.events[..self.next].iter().rev().find_map(pick)
+        }
+    }
+
+    #[derive(Clone)]
+    pub struct Replay {
+        state: Arc<Mutex<State>>,
+    }
+
+    impl Replay {
+        /// Read a recording written by `Recorder`
+        pub fn parse(jsonl: &str) -> Result<Self> {
+            let events = jsonl.lines()
+                .filter(|line| !line.trim().is_empty())
+                .enumerate()
+                .map(|(i, line)| {
+                    serde_json::from_str::<RecordedEvent>(line)
+                        .map(|recorded| recorded.event)
+                        .with_context(|| format!("Invalid recording line {}", i + 1))
+                })
+                .collect::<Result<_>>()?;
+            Ok(Self { state: Arc::new(Mutex::new(State { events, next: 0, divergence: None })) })
+        }
+
+        /// Fail if the bot did anything the recording doesn't have, or stopped short of it
+        pub fn finish(&self) -> Result<()> {
+            let state = self.state.lock();
+            if let Some(divergence) = &state.divergence {
+                anyhow::bail!("Replay diverged at {}", divergence);
+            }
+            if let Some(missing) = state.events.get(state.next_action()) {
+                anyhow::bail!("Replay ended early: the recording goes on with {:?}", missing);
+            }
+            Ok(())
+        }
+    }
+
+    pub struct ReplayWindow {
+        state: Arc<Mutex<State>>,
+        id: i32,
+    }
+
+    impl ReplayWindow {
+        fn click(&self, slot: usize, kind: ClickKind) {
+            self.state.lock().act(SessionEvent::Click { window: self.id, slot, kind });
+        }
+    }
+
+    impl Window for ReplayWindow {
+        fn id(&self) -> i32 {
+            self.id
+        }
+
+        fn contents(&self) -> Option<Slots> {
+            self.state.lock().observe(|event| match event {
+                SessionEvent::Contents { window, slots } if *window == self.id => Some(slots.clone()),
+                _ => None,
+            })?
+        }
+
+        fn left_click(&self, slot: usize) {
+            self.click(slot, ClickKind::Left);
+        }
+
+        fn right_click(&self, slot: usize) {
+            self.click(slot, ClickKind::Right);
+        }
+
+        fn shift_click(&self, slot: usize) {
+            self.click(slot, ClickKind::Shift);
+        }
+
+        fn throw_stack(&self, slot: usize) {
+            self.click(slot, ClickKind::Throw);
+        }
+
+        fn close(&self) {
+            self.state.lock().act(SessionEvent::Close { window: self.id });
+        }
+    }
+
+    impl GameSession for Replay {
+        type Inventory = ReplayWindow;
+
+        fn chat(&self, message: &str) {
+            self.state.lock().act(SessionEvent::Command { command: message.to_string() });
+        }
+
+        fn username(&self) -> String {
+            "FlipperBot".to_string()
+        }
+
+        fn current_window(&self) -> ReplayWindow {
+            let id = self.state.lock().observe(|event| match event {
+                SessionEvent::CurrentWindow { id } => Some(*id),
+                _ => None,
+            });
+            ReplayWindow { state: self.state.clone(), id: id.unwrap_or_default() }
+        }
+
+        fn select_hotbar_slot(&self, slot: u8) {
+            self.state.lock().act(SessionEvent::SelectHotbar { slot });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::replay::Replay;
+    use super::*;
+    use crate::config::Config;
+    use crate::inventory::{find_cheap_maps, get_map_slots, list_maps, open_auction_house, purchase_map};
+    use crate::price_parser::Money;
+    use crate::session::fake::{auction, chest, confirm_screen, map, FakeSession, Reaction, CONFIRM_SLOT};
+    use std::collections::HashMap;
+    use tokio::sync::Notify;
+
+    /// The trading steps of a cycle: buy the cheapest map under the limit, then list every map
+    async fn flip(bot: &impl GameSession, config: &Config) {
+        let Ok(Some(menu)) = open_auction_house(bot, config).await else {
+            return;
+        };
+        let Some(map) = find_cheap_maps(&menu, config.max_buy_price, &bot.username()) else {
+            return;
+        };
+        if !matches!(purchase_map(bot, &map, config, &Notify::new()).await, Ok(true)) {
+            return;
+        }
+        let _ = list_maps(bot, config, &get_map_slots(bot), &HashMap::new(), &Notify::new()).await;
+    }
+
+    /// An auction house with maps from alice at $3,000 (slot 0) and bob at $2,000 (slot 4)
+    fn auction_house() -> FakeSession {
+        let open = |label: &str, slots| Reaction::Open { label: label.to_string(), slots };
+        let bot = FakeSession::new(vec![]);
+        let listings = vec![(0, auction(11, "$3,000", "alice")), (4, auction(12, "$2,000", "bob"))];
+        bot.on_command("/ah map", vec![open("ah", chest(6, listings))])
+            .on_click("ah", 4, vec![open("confirm", confirm_screen(Some(auction(12, "$2,000", "bob"))))])
+            .on_click("confirm", CONFIRM_SLOT, vec![Reaction::Give(map(12, 1)), Reaction::Close])
+            .on_command("/ah sell", vec![open("sell", confirm_screen(None))])
+            .on_click("sell", CONFIRM_SLOT, vec![Reaction::TakeHeld, Reaction::Close]);
+        bot
+    }
+
+    fn temp_recording(name: &str) -> String {
+        let nanos = Utc::now().timestamp_nanos_opt().unwrap();
+        std::env::temp_dir().join(format!("{}-{}.jsonl", name, nanos)).display().to_string()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_recording_replays_to_the_same_decisions() {
+        let path = temp_recording("session-test");
+        let recorder = Recorder::open(&path).unwrap();
+        flip(&Recorded::new(auction_house(), recorder.clone()), &Config::default()).await;
+        recorder.record(SessionEvent::Chat { message: "steve bought your Map for $9.9k".to_string() });
+
+        let recording = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let replay = Replay::parse(&recording).unwrap();
+        flip(&replay, &Config::default()).await;
+        replay.finish().unwrap();
+
+        // Cheaper maps on the recorded server would have been bought instead
+        let replay = Replay::parse(&recording).unwrap();
+        let config = Config { max_buy_price: Money::new(1000), ..Config::default() };
+        flip(&replay, &config).await;
+        assert!(replay.finish().unwrap_err().to_string().contains("ended early"));
+    }
+
+    #[test]
+    fn test_recorder_is_off_without_a_path() {
+        let recorder = Recorder::open("").unwrap();
+        assert!(recorder.file.is_none());
+        recorder.record(SessionEvent::Kicked { reason: "test".to_string() });
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_replay_reports_a_different_action() {
+        let replay = Replay::parse(include_str!("../fixtures/recordings/buy-and-list.jsonl")).unwrap();
+
+        replay.chat("/ah sell 1");
+        let error = replay.finish().unwrap_err().to_string();
+        assert!(error.contains("event 2: the bot did Command"), "{}", error);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_fixture_buy_and_list() {
+        let replay = Replay::parse(include_str!("../fixtures/recordings/buy-and-list.jsonl")).unwrap();
+        flip(&replay, &Config::default()).await;
+        replay.finish().unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_fixture_sniped_auction() {
+        let replay = Replay::parse(include_str!("../fixtures/recordings/sniped-auction.jsonl")).unwrap();
+        flip(&replay, &Config::default()).await;
+        replay.finish().unwrap();
+    }
+}
@@ -0,0 +1,143 @@
+use serde_json::Value;
+use crate::config::{flatten_leaves, Config, SECRET_FIELDS};
+
+/// Fields that only take effect when the bot connects (or opens its state files)
+const RESTART_REQUIRED: &[&str] = &[
+    "$.host",
+    "$.port",
+    "$.username",
+    "$.auth",
+    "$.version",
+    "$.digest.stateFile",
+    "$.limitsStateFile",
+    "$.recordSession",
+];
+
+/// One changed config value, located by its JSON path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigChange {
+    pub path: String,
+    pub old: String,
+    pub new: String,
+}
+
+impl ConfigChange {
+    pub fn describe(&self) -> String {
+        format!("{}: {} → {}", self.path, self.old, self.new)
+    }
+}
+
+/// The result of comparing the running config with a freshly loaded one
+pub struct ReloadPlan {
+    /// Changes that will take effect at the next cycle boundary
+    pub applied: Vec<ConfigChange>,
+    /// Changes that are ignored until the bot is restarted
+    pub restart_required: Vec<ConfigChange>,
+    /// The config to swap in: the new file, with connection-level fields kept as they are
+    pub config: Config,
+}
+
+impl ReloadPlan {
+    pub fn is_empty(&self) -> bool {
+        self.applied.is_empty() && self.restart_required.is_empty()
+    }
+}
+
+/// Work out what a reload would change and build the config to swap in
+pub fn plan_reload(current: &Config, mut candidate: Config) -> ReloadPlan {
+    let (applied, restart_required) = diff(current, &candidate)
+        .into_iter()
+        .partition(|change| !RESTART_REQUIRED.contains(&change.path.as_str()));
+
+    // Keep the live connection settings; they are reported but not applied
+    candidate.host = current.host.clone();
+    candidate.port = current.port;
+    candidate.username = current.username.clone();
+    candidate.auth = current.auth.clone();
+    candidate.version = current.version.clone();
+    candidate.digest.state_file = current.digest.state_file.clone();
+    candidate.limits_state_file = current.limits_state_file.clone();
+    candidate.record_session = current.record_session.clone();
+
+    ReloadPlan {
+        applied,
+        restart_required,
+        config: candidate,
+    }
+}
+
+/// Compare two configs value by value, using the same key names as the config file
+pub fn diff(old: &Config, new: &Config) -> Vec<ConfigChange> {
+    let old_leaves = flatten_leaves(&serde_json::to_value(old).unwrap_or(Value::Null));
+    let new_leaves = flatten_leaves(&serde_json::to_value(new).unwrap_or(Value::Null));
+
+    new_leaves.iter()
+        .filter(|(path, value)| old_leaves.get(*path) != Some(*value))
+        .map(|(path, value)| {
+            let old_value = old_leaves.get(path).cloned().unwrap_or(Value::Null);
+            if SECRET_FIELDS.contains(&path.as_str()) {
+                ConfigChange {
+                    path: path.clone(),
+                    old: "<redacted>".to_string(),
+                    new: "<redacted>".to_string(),
+                }
+            } else {
+                ConfigChange {
+                    path: path.clone(),
+                    old: old_value.to_string(),
+                    new: value.to_string(),
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::price_parser::Money;
+
+    #[test]
+    fn test_trading_fields_are_applied() {
+        let current = Config::default();
+        let candidate = Config {
+            max_buy_price: Money::new(3000),
+            sell_price: Money::new(10000),
+            ..Config::default()
+        };
+
+        let plan = plan_reload(&current, candidate);
+        let paths: Vec<_> = plan.applied.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(paths, vec!["$.maxBuyPrice", "$.sellPrice"]);
+        assert!(plan.restart_required.is_empty());
+        assert_eq!(plan.config.max_buy_price, Money::new(3000));
+        assert_eq!(plan.applied[0].describe(), "$.maxBuyPrice: 2500 → 3000");
+    }
+
+    #[test]
+    fn test_connection_fields_require_restart() {
+        let current = Config::default();
+        let candidate = Config {
+            host: "example.org".to_string(),
+            port: 25566,
+            ..Config::default()
+        };
+
+        let plan = plan_reload(&current, candidate);
+        assert!(plan.applied.is_empty());
+        assert_eq!(plan.restart_required.len(), 2);
+        assert_eq!(plan.config.host, current.host);
+        assert_eq!(plan.config.port, current.port);
+    }
+
+    #[test]
+    fn test_webhook_url_is_redacted() {
+        let current = Config::default();
+        let mut candidate = Config::default();
+        candidate.webhook.url = "https://discord.com/api/webhooks/secret".to_string();
+
+        let changes = diff(&current, &candidate);
+        assert_eq!(changes.len(), 1);
+        assert!(!changes[0].describe().contains("secret"));
+    }
+}
@@ -0,0 +1,426 @@
+use serde::{Deserialize, Serialize};
+
+/// One occupied inventory or container slot, copied out of the game client
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlotItem {
+    /// Registry id, e.g. "minecraft:filled_map"
+    pub kind: String,
+    pub count: i32,
+    /// Custom display name, e.g. "§a§lConfirm" on a GUI button
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Lore lines as plain text (colour codes may remain)
+    pub lore: Vec<String>,
+    pub map_id: Option<i32>,
+}
+
+impl SlotItem {
+    /// Check if this item is a map
+    ///
+    /// Note: Matches on the item id, so empty and filled maps both count.
+    pub fn is_map(&self) -> bool {
+        self.kind.to_lowercase().contains("map")
+    }
+}
+
+/// Every slot of a window in order, `None` for empty ones
+pub type Slots = Vec<Option<SlotItem>>;
+
+/// A window the bot can look at and click in
+///
+/// Either the player inventory (46 slots: crafting 0-4, armour 5-8, main 9-35, hotbar
+/// 36-44, offhand 45) or a container, whose last 36 slots are then the player's main
+/// inventory and hotbar.
+pub trait Window {
+    fn id(&self) -> i32;
+    /// Current contents, or `None` once the window is no longer open
+    fn contents(&self) -> Option<Slots>;
+    fn left_click(&self, slot: usize);
+    /// Only `unstack_maps` right-clicks
+    #[allow(dead_code)]
+    fn right_click(&self, slot: usize);
+    fn shift_click(&self, slot: usize);
+    /// Throw the whole stack in `slot` on the ground
+    fn throw_stack(&self, slot: usize);
+    /// Ask the server to close this window
+    fn close(&self);
+}
+
+/// The game operations the trading flows use
+///
+/// Implemented for azalea's `Client`, and by a scripted fake in tests, so the flows in
+/// `inventory.rs` can run without a server. Window handles never close anything when
+/// dropped; opening and closing containers goes through `containers::ContainerManager`.
+pub trait GameSession {
+    type Inventory: Window;
+
+    fn chat(&self, message: &str);
+    fn username(&self) -> String;
+    /// The open container, or the player inventory when none is open
+    fn current_window(&self) -> Self::Inventory;
+    fn select_hotbar_slot(&self, slot: u8);
+}
+
+/// Scripted in-memory stand-in for the server, for testing flows
+#[cfg(test)]
+pub mod fake {
+    use super::*;
+    use parking_lot::Mutex;
+    use std::sync::Arc;
+
+    /// Slots in a player inventory menu
+    const PLAYER_SLOTS: usize = 46;
+    const PLAYER_WINDOW_ID: i32 = 0;
+
+    /// Something the server does in response to a command or click
+    #[derive(Debug, Clone)]
+    pub enum Reaction {
+        /// Open a container showing `slots` above the player's inventory
+        Open { label: String, slots: Slots },
+        /// Close the open container
+        Close,
+        /// Put an item in the first free main inventory or hotbar slot
+        Give(SlotItem),
+        /// Take the stack in the selected hotbar slot (a listing going up)
+        TakeHeld,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum Trigger {
+        /// A chat message starting with this
+        Command(String),
+        /// A left click on a slot of the container opened under this label
+        Click { label: String, slot: usize },
+    }
+
+    /// Everything the bot sent, in order
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum Action {
+        Chat(String),
+        LeftClick { window: i32, slot: usize },
+        RightClick { window: i32, slot: usize },
+        ShiftClick { window: i32, slot: usize },
+        Throw { window: i32, slot: usize },
+        Close { window: i32 },
+        SelectHotbar(u8),
+    }
+
+    struct OpenContainer {
+        id: i32,
+        label: String,
+        slots: Slots,
+    }
+
+    struct State {
+        inventory: Slots,
+        cursor: Option<SlotItem>,
+        selected_hotbar: u8,
+        open: Option<OpenContainer>,
+        next_id: i32,
+        rules: Vec<(Trigger, Vec<Reaction>)>,
+        actions: Vec<Action>,
+    }
+
+    #[derive(Clone)]
+    pub struct FakeSession {
+        state: Arc<Mutex<State>>,
+    }
+
+    impl FakeSession {
+        /// A session whose player inventory holds `items` at the given menu slots
+        pub fn new(items: Vec<(usize, SlotItem)>) -> Self {
+            let mut inventory = vec![None; PLAYER_SLOTS];
+            for (slot, item) in items {
+                inventory[slot] = Some(item);
+            }
+            Self {
+                state: Arc::new(Mutex::new(State {
+                    inventory,
+                    cursor: None,
+                    selected_hotbar: 0,
+                    open: None,
+                    next_id: 1,
+                    rules: Vec::new(),
+                    actions: Vec::new(),
+                })),
+            }
+        }
+
+        /// React to every chat message starting with `prefix`
+        pub fn on_command(&self, prefix: &str, reactions: Vec<Reaction>) -> &Self {
+            self.state.lock().rules.push((Trigger::Command(prefix.to_string()), reactions));
+            self
+        }
+
+        /// React to every left click on `slot` of a container opened as `label`
+        pub fn on_click(&self, label: &str, slot: usize, reactions: Vec<Reaction>) -> &Self {
+            self.state.lock().rules.push((Trigger::Click { label: label.to_string(), slot }, reactions));
+            self
+        }
+
+        pub fn inventory(&self) -> Slots {
+            self.state.lock().inventory.clone()
+        }
+
+        pub fn actions(&self) -> Vec<Action> {
+            self.state.lock().actions.clone()
+        }
+
+        pub fn commands(&self) -> Vec<String> {
+            self.actions().into_iter()
+                .filter_map(|action| match action {
+                    Action::Chat(message) => Some(message),
+                    _ => None,
+                })
+                .collect()
+        }
+    }
+
+    impl State {
+        fn fire(&mut self, trigger: &Trigger) -> bool {
+            let reactions: Vec<Reaction> = self.rules.iter()
+                .filter(|(rule, _)| match (rule, trigger) {
+                    (Trigger::Command(prefix), Trigger::Command(message)) => message.starts_with(prefix.as_str()),
+                    _ => rule == trigger,
+                })
+                .flat_map(|(_, reactions)| reactions.clone())
+                .collect();
+            let fired = !reactions.is_empty();
+            for reaction in reactions {
+                self.apply(reaction);
+            }
+            fired
+        }
+
+        fn apply(&mut self, reaction: Reaction) {
+            match reaction {
+                Reaction::Open { label, slots } => {
+                    let id = self.next_id;
+                    self.next_id += 1;
+                    self.open = Some(OpenContainer { id, label, slots });
+                }
+                Reaction::Close => self.open = None,
+                Reaction::Give(item) => {
+                    if let Some(slot) = (9..45).find(|&i| self.inventory[i].is_none()) {
+                        self.inventory[slot] = Some(item);
+                    }
+                }
+                Reaction::TakeHeld => self.inventory[36 + self.selected_hotbar as usize] = None,
+            }
+        }
+
+        /// Resolve a window slot to the container or inventory slot it shows
+        fn slot_mut(&mut self, window: i32, slot: usize) -> Option<&mut Option<SlotItem>> {
+            if window == PLAYER_WINDOW_ID {
+                return self.inventory.get_mut(slot);
+            }
+            let open = self.open.as_mut().filter(|open| open.id == window)?;
+            let container_size = open.slots.len();
+            if slot < container_size {
+                open.slots.get_mut(slot)
+            } else {
+                self.inventory.get_mut(slot - container_size + 9)
+            }
+        }
+
+        fn contents(&self, window: i32) -> Option<Slots> {
+            if window == PLAYER_WINDOW_ID {
+                return Some(self.inventory.clone());
+            }
+            let open = self.open.as_ref().filter(|open| open.id == window)?;
+            Some(open.slots.iter().chain(&self.inventory[9..45]).cloned().collect())
+        }
+
+        fn left_click(&mut self, window: i32, slot: usize) {
+            self.actions.push(Action::LeftClick { window, slot });
+            if let Some(label) = self.open.as_ref().filter(|open| open.id == window).map(|open| open.label.clone()) {
+                if self.fire(&Trigger::Click { label, slot }) {
+                    return;
+                }
+            }
+            let mut cursor = self.cursor.take();
+            let Some(target) = self.slot_mut(window, slot) else {
+                self.cursor = cursor;
+                return;
+            };
+            match (target.as_mut(), cursor.as_mut()) {
+                (Some(item), Some(held)) if stacks_with(item, held) => {
+                    item.count += held.count;
+                    cursor = None;
+                }
+                _ => std::mem::swap(target, &mut cursor),
+            }
+            self.cursor = cursor;
+        }
+
+        fn right_click(&mut self, window: i32, slot: usize) {
+            self.actions.push(Action::RightClick { window, slot });
+            let mut cursor = self.cursor.take();
+            let Some(target) = self.slot_mut(window, slot) else {
+                self.cursor = cursor;
+                return;
+            };
+            match (target.as_mut(), cursor.as_mut()) {
+                // Pick up half, rounded up
+                (Some(item), None) => {
+                    let taken = (item.count + 1) / 2;
+                    let mut half = item.clone();
+                    half.count = taken;
+                    item.count -= taken;
+                    if item.count == 0 {
+                        *target = None;
+                    }
+                    cursor = Some(half);
+                }
+                // Put down one
+                (None, Some(held)) => {
+                    let mut one = held.clone();
+                    one.count = 1;
+                    held.count -= 1;
+                    *target = Some(one);
+                    if held.count == 0 {
+                        cursor = None;
+                    }
+                }
+                _ => {}
+            }
+            self.cursor = cursor;
+        }
+
+        /// Move a stack from the player's part of a container into the first free container slot
+        fn shift_click(&mut self, window: i32, slot: usize) {
+            self.actions.push(Action::ShiftClick { window, slot });
+            let Some(open) = self.open.as_mut().filter(|open| open.id == window) else {
+                return;
+            };
+            let container_size = open.slots.len();
+            let Some(free) = open.slots.iter().position(Option::is_none) else {
+                return;
+            };
+            if slot >= container_size {
+                open.slots[free] = self.inventory[slot - container_size + 9].take();
+            }
+        }
+
+        fn throw_stack(&mut self, window: i32, slot: usize) {
+            self.actions.push(Action::Throw { window, slot });
+            if let Some(target) = self.slot_mut(window, slot) {
+                *target = None;
+            }
+        }
+
+        fn close(&mut self, window: i32) {
+            self.actions.push(Action::Close { window });
+            if self.open.as_ref().is_some_and(|open| open.id == window) {
+                self.open = None;
+            }
+        }
+    }
+
+    fn stacks_with(a: &SlotItem, b: &SlotItem) -> bool {
+        a.kind == b.kind && a.map_id == b.map_id && a.lore == b.lore
+    }
+
+    pub struct FakeWindow {
+        state: Arc<Mutex<State>>,
+        id: i32,
+    }
+
+    impl Window for FakeWindow {
+        fn id(&self) -> i32 {
+            self.id
+        }
+
+        fn contents(&self) -> Option<Slots> {
+            self.state.lock().contents(self.id)
+        }
+
+        fn left_click(&self, slot: usize) {
+            self.state.lock().left_click(self.id, slot);
+        }
+
+        fn right_click(&self, slot: usize) {
+            self.state.lock().right_click(self.id, slot);
+        }
+
+        fn shift_click(&self, slot: usize) {
+            self.state.lock().shift_click(self.id, slot);
+        }
+
+        fn throw_stack(&self, slot: usize) {
+            self.state.lock().throw_stack(self.id, slot);
+        }
+
+        fn close(&self) {
+            self.state.lock().close(self.id);
+        }
+    }
+
+    impl GameSession for FakeSession {
+        type Inventory = FakeWindow;
+
+        fn chat(&self, message: &str) {
+            let mut state = self.state.lock();
+            state.actions.push(Action::Chat(message.to_string()));
+            state.fire(&Trigger::Command(message.to_string()));
+        }
+
+        fn username(&self) -> String {
+            "FlipperBot".to_string()
+        }
+
+        fn current_window(&self) -> FakeWindow {
+            let id = self.state.lock().open.as_ref().map(|open| open.id).unwrap_or(PLAYER_WINDOW_ID);
+            FakeWindow { state: self.state.clone(), id }
+        }
+
+        fn select_hotbar_slot(&self, slot: u8) {
+            let mut state = self.state.lock();
+            state.actions.push(Action::SelectHotbar(slot));
+            state.selected_hotbar = slot;
+        }
+    }
+
+    /// Where a confirm screen has its buttons, and shows the listing being bought
+    pub const CANCEL_SLOT: usize = 11;
+    pub const LISTING_SLOT: usize = 13;
+    pub const CONFIRM_SLOT: usize = 15;
+
+    /// A stack of filled maps, as it sits in the player's inventory
+    pub fn map(id: i32, count: i32) -> SlotItem {
+        SlotItem { kind: "minecraft:filled_map".to_string(), count, name: None, lore: Vec::new(), map_id: Some(id) }
+    }
+
+    /// A single map on the auction house, with its price and seller in the lore
+    pub fn auction(id: i32, price: &str, seller: &str) -> SlotItem {
+        SlotItem {
+            lore: vec![format!("§7Price: §a{}", price), format!("§7Seller: §f{}", seller)],
+            ..map(id, 1)
+        }
+    }
+
+    /// A GUI button: an item with a display name
+    pub fn button(kind: &str, name: &str) -> SlotItem {
+        SlotItem { kind: kind.to_string(), count: 1, name: Some(name.to_string()), lore: Vec::new(), map_id: None }
+    }
+
+    /// A chest menu of `rows` rows with `items` in their slots
+    pub fn chest(rows: usize, items: Vec<(usize, SlotItem)>) -> Slots {
+        let mut slots = vec![None; rows * 9];
+        for (slot, item) in items {
+            slots[slot] = Some(item);
+        }
+        slots
+    }
+
+    /// A 3-row confirm screen: cancel and confirm buttons, with `listing`, if any, between them
+    pub fn confirm_screen(listing: Option<SlotItem>) -> Slots {
+        let mut items = vec![
+            (CANCEL_SLOT, button("minecraft:red_stained_glass_pane", "§c§lCancel")),
+            (CONFIRM_SLOT, button("minecraft:lime_stained_glass_pane", "§a§lConfirm")),
+        ];
+        items.extend(listing.map(|item| (LISTING_SLOT, item)));
+        chest(3, items)
+    }
+}
@@ -0,0 +1,204 @@
+use chrono::{DateTime, Duration, Utc};
+use std::collections::VecDeque;
+
+/// How long shard readings are kept
+const HISTORY_HOURS: i64 = 24;
+
+/// One `/shards` reading
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShardSample {
+    pub at: DateTime<Utc>,
+    pub shards: u64,
+}
+
+/// A change in whether AFK farming is earning shards
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShardAlert {
+    /// No shards earned since `since` while AFK farming
+    Stalled { since: DateTime<Utc> },
+    /// Earning again after a stall
+    Resumed,
+}
+
+/// What a new reading tells us
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShardUpdate {
+    pub shards: u64,
+    /// Change since the previous reading; negative when shards were spent
+    pub change: Option<i64>,
+    /// Shards earned per hour since AFK farming started
+    pub rate_per_hour: Option<u64>,
+    pub alert: Option<ShardAlert>,
+}
+
+/// Shard balance history, read from `/shards`, and the AFK farming rate it implies
+///
+/// Only readings taken since AFK farming last started count towards the rate, so time
+/// spent disconnected or before the teleport doesn't drag it down. Shards spent between
+/// readings aren't counted as negative earnings.
+#[derive(Debug, Default)]
+pub struct ShardTracker {
+    /// Readings from the last day, oldest first
+    history: VecDeque<ShardSample>,
+    awaiting_response: bool,
+    /// When AFK farming last started; `None` while not farming
+    farming_since: Option<DateTime<Utc>>,
+    /// Last time the balance went up, or farming started
+    last_gain: Option<DateTime<Utc>>,
+    stalled: bool,
+}
+
+impl ShardTracker {
+    pub fn is_awaiting_response(&self) -> bool {
+        self.awaiting_response
+    }
+
+    pub fn set_awaiting_response(&mut self, awaiting: bool) {
+        self.awaiting_response = awaiting;
+    }
+
+    /// The bot is in an AFK area from now on
+    pub fn start_farming(&mut self, now: DateTime<Utc>) {
+        self.farming_since = Some(now);
+        self.last_gain = Some(now);
+        self.stalled = false;
+    }
+
+    /// The bot left the AFK area, e.g. by disconnecting
+    pub fn stop_farming(&mut self) {
+        self.farming_since = None;
+        self.last_gain = None;
+        self.stalled = false;
+    }
+
+    pub fn latest(&self) -> Option<ShardSample> {
+        self.history.back().copied()
+    }
+
+    /// Shards earned per hour across the readings since AFK farming started; needs two
+    pub fn rate_per_hour(&self) -> Option<u64> {
+        let since = self.farming_since?;
+        let farming: Vec<&ShardSample> = self.history.iter().filter(|s| s.at >= since).collect();
+        let (first, last) = (farming.first()?, farming.last()?);
+        let seconds = (last.at - first.at).num_seconds();
+        if seconds <= 0 {
+            return None;
+        }
+        let earned: u64 = farming.windows(2)
+            .map(|pair| pair[1].shards.saturating_sub(pair[0].shards))
+            .sum();
+        Some(earned * 3600 / seconds as u64)
+    }
+
+    /// Record a reading; alerts once when farming has earned nothing for `stall_after`
+    /// (zero disables the alert) and once when it picks up again
+    pub fn record(&mut self, now: DateTime<Utc>, shards: u64, stall_after: Duration) -> ShardUpdate {
+        self.awaiting_response = false;
+        let change = self.latest().map(|previous| shards as i64 - previous.shards as i64);
+        self.history.push_back(ShardSample { at: now, shards });
+        let cutoff = now - Duration::hours(HISTORY_HOURS);
+        while self.history.front().is_some_and(|s| s.at <= cutoff) {
+            self.history.pop_front();
+        }
+
+        let mut alert = None;
+        if change.is_some_and(|change| change > 0) {
+            if self.farming_since.is_some() {
+                self.last_gain = Some(now);
+            }
+            if self.stalled {
+                self.stalled = false;
+                alert = Some(ShardAlert::Resumed);
+            }
+        } else if let Some(since) = self.last_gain {
+            if !self.stalled && !stall_after.is_zero() && now - since >= stall_after {
+                self.stalled = true;
+                alert = Some(ShardAlert::Stalled { since });
+            }
+        }
+
+        ShardUpdate { shards, change, rate_per_hour: self.rate_per_hour(), alert }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn start() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, 18, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_rate_while_farming() {
+        let now = start();
+        let hour = Duration::hours(1);
+        let mut tracker = ShardTracker::default();
+
+        // Readings before farming starts don't count
+        assert_eq!(tracker.record(now, 1000, hour).change, None);
+        tracker.start_farming(now + Duration::minutes(10));
+        let update = tracker.record(now + Duration::minutes(30), 1100, hour);
+        assert_eq!((update.change, update.rate_per_hour), (Some(100), None));
+
+        let update = tracker.record(now + Duration::minutes(60), 1250, hour);
+        assert_eq!((update.change, update.rate_per_hour), (Some(150), Some(300)));
+
+        // Spending shards isn't negative earnings
+        let update = tracker.record(now + Duration::minutes(90), 250, hour);
+        assert_eq!((update.change, update.rate_per_hour), (Some(-1000), Some(150)));
+        let update = tracker.record(now + Duration::minutes(120), 350, hour);
+        assert_eq!(update.rate_per_hour, Some(166));
+        assert_eq!(tracker.history.len(), 5);
+    }
+
+    #[test]
+    fn test_stall_alerts_once_and_recovers() {
+        let now = start();
+        let hour = Duration::hours(1);
+        let mut tracker = ShardTracker::default();
+        tracker.start_farming(now);
+
+        assert_eq!(tracker.record(now + Duration::minutes(30), 500, hour).alert, None);
+        assert_eq!(tracker.record(now + Duration::minutes(60), 600, hour).alert, None);
+        assert_eq!(tracker.record(now + Duration::minutes(90), 600, hour).alert, None);
+        assert_eq!(
+            tracker.record(now + Duration::minutes(120), 600, hour).alert,
+            Some(ShardAlert::Stalled { since: now + Duration::minutes(60) })
+        );
+        assert_eq!(tracker.record(now + Duration::minutes(150), 600, hour).alert, None);
+        assert_eq!(tracker.record(now + Duration::minutes(180), 650, hour).alert, Some(ShardAlert::Resumed));
+    }
+
+    #[test]
+    fn test_no_stall_unless_farming() {
+        let now = start();
+        let hour = Duration::hours(1);
+        let mut tracker = ShardTracker::default();
+        tracker.record(now, 500, hour);
+        assert_eq!(tracker.record(now + Duration::hours(3), 500, hour).alert, None);
+
+        // A disconnect stops the clock; reconnecting starts it afresh
+        tracker.start_farming(now + Duration::hours(3));
+        tracker.stop_farming();
+        assert_eq!(tracker.record(now + Duration::hours(5), 500, hour).alert, None);
+        assert_eq!(tracker.rate_per_hour(), None);
+
+        tracker.start_farming(now + Duration::hours(5));
+        assert_eq!(tracker.record(now + Duration::minutes(330), 500, Duration::zero()).alert, None);
+        assert!(tracker.record(now + Duration::hours(6), 500, hour).alert.is_some());
+    }
+
+    #[test]
+    fn test_history_keeps_a_day() {
+        let now = start();
+        let mut tracker = ShardTracker::default();
+        for i in 0..50 {
+            tracker.record(now + Duration::hours(i), i as u64, Duration::zero());
+        }
+        assert_eq!(tracker.history.len(), 24);
+        assert_eq!(tracker.history[0].shards, 26);
+        assert_eq!(tracker.latest().map(|s| s.shards), Some(49));
+    }
+}
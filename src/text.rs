@@ -0,0 +1,147 @@
+use azalea::FormattedText;
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Plain text of a chat component tree: every component's text in order, translations
+/// filled in, styling dropped
+///
+/// Unlike `to_string()` this never panics: a translation whose arguments don't fit its
+/// template is written as its key.
+pub fn flatten(text: &FormattedText) -> String {
+    text.clone()
+        .into_iter()
+        .map(|component| match component {
+            FormattedText::Text(text) => text.text,
+            FormattedText::Translatable(translatable) => match translatable.read() {
+                Ok(read) => flatten(&FormattedText::Text(read)),
+                Err(_) => translatable.key,
+            },
+        })
+        .collect()
+}
+
+/// Remove every colour and format encoding the server sends inside plain text
+///
+/// Handles legacy codes (`§a`, `§l`, `§r`), their hex form (`§x§f§f§0§0§0§0`), and the
+/// `&#RRGGBB` / `&x&f&f...` hex codes some plugins leave unconverted. Other `&`s are kept.
+pub fn strip_formatting(text: &str) -> String {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| {
+        Regex::new(concat!(
+            r"(?i)[§&]x(?:[§&][0-9a-f]){6}", // §x§R§R§G§G§B§B and &x&R&R&G&G&B&B
+            r"|&#[0-9a-f]{6}",                 // &#RRGGBB
+            r"|§[0-9a-fk-or]",                 // §a, §l, §r
+        ))
+        .unwrap()
+    });
+    re.replace_all(text, "").to_string()
+}
+
+/// Map unicode lookalikes to the ASCII they stand for, keeping case
+///
+/// Covers small capitals (ᴀꜰᴋ, including `ǫ`/`ꞯ` and `ꜱ`/`ѕ`), full-width forms (`＄１０Ｋ`),
+/// superscript, subscript and mathematical digits, and unusual spaces.
+pub fn fold_lookalikes(text: &str) -> String {
+    text.chars().map(fold_char).collect()
+}
+
+/// Both of the above: what lore and chat parsing match against
+pub fn normalize(text: &str) -> String {
+    fold_lookalikes(&strip_formatting(text))
+}
+
+fn fold_char(c: char) -> char {
+    match c {
+        // Small capitals; there is no small capital x, servers use a plain one
+        'ᴀ' => 'a', 'ʙ' => 'b', 'ᴄ' => 'c', 'ᴅ' => 'd', 'ᴇ' => 'e', 'ꜰ' => 'f',
+        'ɢ' => 'g', 'ʜ' => 'h', 'ɪ' => 'i', 'ᴊ' => 'j', 'ᴋ' => 'k', 'ʟ' => 'l',
+        'ᴍ' => 'm', 'ɴ' => 'n', 'ᴏ' => 'o', 'ᴘ' => 'p', 'ǫ' | 'ꞯ' => 'q', 'ʀ' => 'r',
+        'ꜱ' | 'ѕ' => 's', 'ᴛ' => 't', 'ᴜ' => 'u', 'ᴠ' => 'v', 'ᴡ' => 'w', 'ʏ' => 'y',
+        'ᴢ' => 'z',
+        // Digits
+        '⁰' => '0', '¹' => '1', '²' => '2', '³' => '3',
+        '⁴'..='⁹' => shift(c, '⁴', '4'),
+        '₀'..='₉' => shift(c, '₀', '0'),
+        // Bold, double-struck, sans-serif, sans-serif bold and monospace, ten of each
+        '\u{1D7CE}'..='\u{1D7FF}' => char::from(b'0' + ((c as u32 - 0x1D7CE) % 10) as u8),
+        // Full-width ASCII
+        '！'..='～' => shift(c, '！', '!'),
+        '\u{3000}' | '\u{00A0}' | '\u{2007}' | '\u{202F}' => ' ',
+        other => other,
+    }
+}
+
+/// `c`'s offset from `from`, applied to `to`
+fn shift(c: char, from: char, to: char) -> char {
+    char::from_u32(c as u32 - from as u32 + to as u32).unwrap_or(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_legacy_codes() {
+        assert_eq!(strip_formatting("§aHello §6World"), "Hello World");
+        assert_eq!(strip_formatting("Normal text"), "Normal text");
+        assert_eq!(strip_formatting("§k§l§m§n§oTest"), "Test");
+        assert_eq!(strip_formatting("§A§LUpper§R case"), "Upper case");
+        // Every legacy code
+        for code in "0123456789abcdefklmnor".chars() {
+            assert_eq!(strip_formatting(&format!("§{}x", code)), "x", "§{}", code);
+        }
+        // Not a code: left alone
+        assert_eq!(strip_formatting("§z and §"), "§z and §");
+    }
+
+    #[test]
+    fn test_strip_hex_codes() {
+        assert_eq!(strip_formatting("§x§f§f§5§5§0§0Orange§r text"), "Orange text");
+        assert_eq!(strip_formatting("§X§F§F§5§5§0§0Orange"), "Orange");
+        assert_eq!(strip_formatting("&#FF5500Orange &#00aaffBlue"), "Orange Blue");
+        assert_eq!(strip_formatting("&x&f&f&5&5&0&0Orange"), "Orange");
+        // A hex code isn't mistaken for a legacy one followed by text
+        assert_eq!(strip_formatting("§x§f§f§5§5§0§0§lBold"), "Bold");
+        // Incomplete hex codes and plain ampersands are text
+        assert_eq!(strip_formatting("&#FF55 and R&D"), "&#FF55 and R&D");
+        assert_eq!(strip_formatting("Tom & Jerry"), "Tom & Jerry");
+    }
+
+    #[test]
+    fn test_fold_small_caps() {
+        assert_eq!(fold_lookalikes("ᴀʙᴄᴅᴇꜰɢʜɪᴊᴋʟᴍɴᴏᴘǫʀꜱᴛᴜᴠᴡxʏᴢ"), "abcdefghijklmnopqrstuvwxyz");
+        assert_eq!(fold_lookalikes("ꞯ ѕ"), "q s");
+        assert_eq!(fold_lookalikes("ʏᴏᴜ ᴀʀᴇ ɴᴏᴡ ᴀꜰᴋ"), "you are now afk");
+        // Case of ordinary letters is kept
+        assert_eq!(fold_lookalikes("Steve ᴘᴜʀᴄʜᴀꜱᴇᴅ"), "Steve purchased");
+    }
+
+    #[test]
+    fn test_fold_digits() {
+        assert_eq!(fold_lookalikes("⁰¹²³⁴⁵⁶⁷⁸⁹"), "0123456789");
+        assert_eq!(fold_lookalikes("₀₁₂₃₄₅₆₇₈₉"), "0123456789");
+        assert_eq!(fold_lookalikes("０１２３４５６７８９"), "0123456789");
+        assert_eq!(fold_lookalikes("𝟎𝟗 𝟘𝟡 𝟢𝟫 𝟬𝟵 𝟶𝟿"), "09 09 09 09 09");
+    }
+
+    #[test]
+    fn test_fold_full_width() {
+        assert_eq!(fold_lookalikes("＄９．９Ｋ"), "$9.9K");
+        assert_eq!(fold_lookalikes("Ｐｒｉｃｅ：　＄１００"), "Price: $100");
+        assert_eq!(fold_lookalikes("！～"), "!~");
+        assert_eq!(fold_lookalikes("10\u{00A0}000\u{202F}$"), "10 000 $");
+    }
+
+    #[test]
+    fn test_fold_leaves_other_text() {
+        let text = "Plain ASCII 123 $!? — émigré 日本語 ✔";
+        assert_eq!(fold_lookalikes(text), text);
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("§6§lᴘʀɪᴄᴇ: §a$９.９ᴋ"), "price: $9.9k");
+        assert_eq!(normalize("&#FFAA00ꜱᴇʟʟᴇʀ: §fSteve"), "seller: Steve");
+        assert_eq!(normalize(""), "");
+    }
+}
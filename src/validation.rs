@@ -0,0 +1,425 @@
+use serde_json::Value;
+use std::fmt;
+use std::path::Path;
+use crate::chat;
+use crate::config::{self, Config, FullInventoryPolicy, JunkAction};
+use crate::price_parser::Money;
+
+/// Server version the bundled azalea build speaks
+const SUPPORTED_VERSION: &str = "1.21.11";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A single problem found in a config, located by its JSON path (e.g. `$.webhook.url`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    pub severity: Severity,
+    pub path: String,
+    pub message: String,
+}
+
+impl ConfigIssue {
+    fn error(path: &str, message: impl Into<String>) -> Self {
+        Self { severity: Severity::Error, path: path.to_string(), message: message.into() }
+    }
+
+    fn warning(path: &str, message: impl Into<String>) -> Self {
+        Self { severity: Severity::Warning, path: path.to_string(), message: message.into() }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{} at {}: {}", label, self.path, self.message)
+    }
+}
+
+/// Check a loaded config for values that parse but can't work
+///
+/// `raw` is the config file as untyped JSON; when given, keys the bot doesn't
+/// recognise are reported too (serde silently ignores them).
+pub fn validate(config: &Config, raw: Option<&Value>) -> Vec<ConfigIssue> {
+    let mut issues = Vec::new();
+
+    if config.host.trim().is_empty() {
+        issues.push(ConfigIssue::error("$.host", "host must not be empty"));
+    }
+    if config.port == 0 {
+        issues.push(ConfigIssue::error("$.port", "port must be between 1 and 65535"));
+    }
+
+    let username = config.username.trim();
+    if username.is_empty() {
+        issues.push(ConfigIssue::error("$.username", "username must not be empty"));
+    } else if username == "BOT_USERNAME" || username == "your-email@example.com" {
+        issues.push(ConfigIssue::warning(
+            "$.username",
+            format!("\"{}\" is a placeholder - set your Microsoft email or offline username", username),
+        ));
+    }
+
+    match config.auth.as_str() {
+        "microsoft" | "offline" => {}
+        other => issues.push(ConfigIssue::error(
+            "$.auth",
+            format!("unknown auth \"{}\" - expected \"microsoft\" or \"offline\"", other),
+        )),
+    }
+
+    if config.version != SUPPORTED_VERSION {
+        issues.push(ConfigIssue::warning(
+            "$.version",
+            format!("this build only speaks Minecraft {}, \"{}\" is ignored", SUPPORTED_VERSION, config.version),
+        ));
+    }
+
+    // Prices (unparseable prices are already rejected, with their path, when loading)
+    if config.sell_price == Money::ZERO {
+        issues.push(ConfigIssue::error("$.sellPrice", "sell price must be greater than zero"));
+    } else if config.max_buy_price >= config.sell_price {
+        issues.push(ConfigIssue::error(
+            "$.maxBuyPrice",
+            format!(
+                "max buy price {} is not below the sell price {} - every flip would lose money",
+                config.max_buy_price, config.sell_price
+            ),
+        ));
+    }
+    if config.max_buy_price == Money::ZERO {
+        issues.push(ConfigIssue::warning("$.maxBuyPrice", "max buy price is 0 - the bot will never buy"));
+    }
+
+    if config.min_balance_reserve > Money::ZERO && config.balance_check_interval == 0 {
+        issues.push(ConfigIssue::warning(
+            "$.minBalanceReserve",
+            "a reserve is set but balanceCheckInterval is 0 - without /bal checks it is never enforced",
+        ));
+    } else if config.balance_check_interval > 0 && config.balance_check_interval < 60_000 {
+        issues.push(ConfigIssue::warning(
+            "$.balanceCheckInterval",
+            format!("{}ms between /bal checks risks a spam kick - 60000 or more is recommended", config.balance_check_interval),
+        ));
+    }
+
+    if config.shards_check_interval > 0 && config.shards_check_interval < 60_000 {
+        issues.push(ConfigIssue::warning(
+            "$.shardsCheckInterval",
+            format!("{}ms between /shards checks risks a spam kick - 60000 or more is recommended", config.shards_check_interval),
+        ));
+    }
+    if config.enable_afk_farming && config.shard_stall_after > 0 {
+        if config.shards_check_interval == 0 {
+            issues.push(ConfigIssue::warning(
+                "$.shardStallAfter",
+                "a stall alert is set but shardsCheckInterval is 0 - without /shards checks it never fires",
+            ));
+        } else if config.shard_stall_after < config.shards_check_interval {
+            issues.push(ConfigIssue::warning(
+                "$.shardStallAfter",
+                format!("{}ms is shorter than shardsCheckInterval - a single check without shards raises the alert", config.shard_stall_after),
+            ));
+        }
+    }
+
+    // Spending limits (0 means no limit)
+    let hourly = config.max_spend_per_hour;
+    if hourly > Money::ZERO && hourly < config.max_buy_price {
+        issues.push(ConfigIssue::warning(
+            "$.maxSpendPerHour",
+            format!("{} per hour is below maxBuyPrice {} - the most expensive maps can never be bought", hourly, config.max_buy_price),
+        ));
+    }
+    if hourly > Money::ZERO && config.max_spend_per_day > Money::ZERO && hourly > config.max_spend_per_day {
+        issues.push(ConfigIssue::warning(
+            "$.maxSpendPerHour",
+            format!("hourly limit {} is above the daily limit {}", hourly, config.max_spend_per_day),
+        ));
+    }
+    if config.limit_cooldown == 0 && config.resume_file.trim().is_empty() {
+        issues.push(ConfigIssue::error(
+            "$.resumeFile",
+            "limitCooldown is 0 and no resumeFile is set - a tripped limit could never be resumed",
+        ));
+    }
+
+    if config.relist_discount_percent > 100 {
+        issues.push(ConfigIssue::error(
+            "$.relistDiscountPercent",
+            format!("{}% is not a valid discount - use 0 to 100", config.relist_discount_percent),
+        ));
+    }
+
+    if config.full_inventory_policy == FullInventoryPolicy::Store && config.ender_chest_command.trim().is_empty() {
+        issues.push(ConfigIssue::error(
+            "$.enderChestCommand",
+            "fullInventoryPolicy is \"store\" but no ender chest command is set",
+        ));
+    }
+
+    if config.junk_action == JunkAction::Store && config.ender_chest_command.trim().is_empty() {
+        issues.push(ConfigIssue::error(
+            "$.enderChestCommand",
+            "junkAction is \"store\" but no ender chest command is set",
+        ));
+    }
+    if config.junk_action == JunkAction::List && config.junk_price == Money::ZERO {
+        issues.push(ConfigIssue::error("$.junkPrice", "junk price must be greater than zero to list junk"));
+    }
+
+    if config.max_listings_per_cycle == 0 {
+        issues.push(ConfigIssue::warning(
+            "$.maxListingsPerCycle",
+            "max listings per cycle is 0 - purchased maps will never be listed",
+        ));
+    }
+
+    // Timings: too short and the server kicks us for spam or we never see windows open
+    if config.window_timeout < 1000 {
+        issues.push(ConfigIssue::error(
+            "$.windowTimeout",
+            format!("{}ms is too short for a window to open - use at least 1000", config.window_timeout),
+        ));
+    }
+    if config.delay_between_cycles < 1000 {
+        issues.push(ConfigIssue::warning(
+            "$.delayBetweenCycles",
+            format!("{}ms between cycles risks a spam kick - 1000 or more is recommended", config.delay_between_cycles),
+        ));
+    }
+    if config.delay_between_listings < 500 {
+        issues.push(ConfigIssue::warning(
+            "$.delayBetweenListings",
+            format!("{}ms between listings risks hitting the command cooldown", config.delay_between_listings),
+        ));
+    }
+
+    if config.webhook.enabled {
+        let url = config.webhook.url.trim();
+        if url.is_empty() {
+            issues.push(ConfigIssue::error("$.webhook.url", "webhooks are enabled but no URL is set"));
+        } else if !url.starts_with("https://discord.com/api/webhooks/")
+            && !url.starts_with("https://discordapp.com/api/webhooks/")
+        {
+            issues.push(ConfigIssue::warning(
+                "$.webhook.url",
+                "URL does not look like a Discord webhook (https://discord.com/api/webhooks/...)",
+            ));
+        }
+    }
+
+    if config.digest.state_file.trim().is_empty() {
+        issues.push(ConfigIssue::error("$.digest.stateFile", "digest state file path must not be empty"));
+    }
+    if config.limits_state_file.trim().is_empty() {
+        issues.push(ConfigIssue::error("$.limitsStateFile", "limits state file path must not be empty"));
+    }
+
+    // The largest GUI the server opens is a double chest
+    for (path, slot) in [("$.gui.confirmSlot", config.gui.confirm_slot), ("$.gui.afkSlot", config.gui.afk_slot)] {
+        if slot >= 54 {
+            issues.push(ConfigIssue::error(path, format!("slot {} is outside a 54-slot chest menu", slot)));
+        }
+    }
+
+    for (key, problem) in chat::pattern_problems(&config.chat_patterns) {
+        issues.push(ConfigIssue::error(&format!("$.chatPatterns.{}", key), problem));
+    }
+
+    if let Some(raw) = raw {
+        let known = serde_json::to_value(Config::default()).unwrap_or(Value::Null);
+        check_unknown_keys(raw, &known, "$", &mut issues);
+    }
+
+    issues
+}
+
+/// Walk the raw config alongside a serialized default config and flag keys that don't exist
+fn check_unknown_keys(raw: &Value, known: &Value, path: &str, issues: &mut Vec<ConfigIssue>) {
+    let (Value::Object(raw_map), Value::Object(known_map)) = (raw, known) else {
+        return;
+    };
+
+    for (key, value) in raw_map {
+        let key_path = format!("{}.{}", path, key);
+        let alias = config::KEY_ALIASES.iter().find(|(old, _)| *old == key_path);
+        match (known_map.get(key), alias) {
+            (Some(known_value), _) => check_unknown_keys(value, known_value, &key_path, issues),
+            (None, Some((_, current))) => {
+                let current_key = current.rsplit('.').next().unwrap_or(current);
+                issues.push(ConfigIssue::warning(
+                    &key_path,
+                    format!("\"{}\" is deprecated, use \"{}\"", key, current_key),
+                ));
+            }
+            (None, None) => {
+                let hint = known_map.keys()
+                    .find(|candidate| simplify_key(candidate) == simplify_key(key))
+                    .map(|candidate| format!(" - did you mean \"{}\"?", candidate))
+                    .unwrap_or_default();
+                issues.push(ConfigIssue::warning(
+                    &key_path,
+                    format!("unknown key \"{}\" is ignored{}", key, hint),
+                ));
+            }
+        }
+    }
+}
+
+/// Lowercase a key and drop separators so `displayName` and `display_name` compare equal
+fn simplify_key(key: &str) -> String {
+    key.chars()
+        .filter(|c| *c != '_' && *c != '-')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Print issues and report whether any of them is an error
+pub fn report(issues: &[ConfigIssue]) -> bool {
+    for issue in issues {
+        if issue.is_error() {
+            eprintln!("[CONFIG] ✗ {}", issue);
+        } else {
+            println!("[CONFIG] ⚠ {}", issue);
+        }
+    }
+    issues.iter().any(ConfigIssue::is_error)
+}
+
+/// `validate-config [path]`: check a config file without connecting, for use in CI
+///
+/// Environment overrides are deliberately not applied - this checks the file itself.
+/// Returns the process exit code.
+pub fn run_validate_command(path: &Path) -> i32 {
+    println!("[CONFIG] Validating {}", path.display());
+
+    let raw = match config::read_raw(path) {
+        Ok(raw) => raw,
+        Err(e) => {
+            eprintln!("[CONFIG] ✗ error at $: {:#}", e);
+            return 1;
+        }
+    };
+    let config = match Config::from_value(raw.clone()) {
+        Ok(config) => config,
+        Err(e) => {
+            // Deserialization errors already carry their path ("at $.sellPrice: ...")
+            eprintln!("[CONFIG] ✗ error {:#}", e);
+            return 1;
+        }
+    };
+
+    let issues = validate(&config, Some(&raw));
+    let has_errors = report(&issues);
+    let errors = issues.iter().filter(|i| i.is_error()).count();
+    println!("[CONFIG] {} error(s), {} warning(s)", errors, issues.len() - errors);
+
+    if has_errors { 1 } else { 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn paths(issues: &[ConfigIssue], severity: Severity) -> Vec<&str> {
+        issues.iter()
+            .filter(|i| i.severity == severity)
+            .map(|i| i.path.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_defaults_have_no_errors() {
+        let issues = validate(&Config::default(), None);
+        assert!(paths(&issues, Severity::Error).is_empty(), "{:?}", issues);
+    }
+
+    #[test]
+    fn test_bad_values_are_errors() {
+        let config = Config {
+            auth: "mojang".to_string(),
+            sell_price: Money::ZERO,
+            ..Config::default()
+        };
+        let errors = validate(&config, None);
+        assert_eq!(paths(&errors, Severity::Error), vec!["$.auth", "$.sellPrice"]);
+
+        let config = Config {
+            max_buy_price: Money::new(12000),
+            sell_price: Money::new(9900),
+            ..Config::default()
+        };
+        assert_eq!(paths(&validate(&config, None), Severity::Error), vec!["$.maxBuyPrice"]);
+
+        let config = Config {
+            limit_cooldown: 0,
+            resume_file: String::new(),
+            ..Config::default()
+        };
+        assert_eq!(paths(&validate(&config, None), Severity::Error), vec!["$.resumeFile"]);
+
+        let config = Config {
+            gui: config::GuiConfig { afk_slot: 54, ..config::GuiConfig::default() },
+            ..Config::default()
+        };
+        assert_eq!(paths(&validate(&config, None), Severity::Error), vec!["$.gui.afkSlot"]);
+
+        let config = Config {
+            chat_patterns: config::ChatPatterns { balance: "balance: (\\$\\S+)".to_string(), ..Default::default() },
+            ..Config::default()
+        };
+        assert_eq!(paths(&validate(&config, None), Severity::Error), vec!["$.chatPatterns.balance"]);
+    }
+
+    #[test]
+    fn test_shard_check_warnings() {
+        let shard_warnings = |config: &Config| -> Vec<String> {
+            paths(&validate(config, None), Severity::Warning).into_iter()
+                .filter(|path| path.starts_with("$.shard"))
+                .map(str::to_string)
+                .collect()
+        };
+        assert!(shard_warnings(&Config::default()).is_empty());
+
+        let config = Config { shards_check_interval: 0, ..Config::default() };
+        assert_eq!(shard_warnings(&config), vec!["$.shardStallAfter"]);
+
+        let config = Config { shards_check_interval: 30_000, shard_stall_after: 20_000, ..Config::default() };
+        assert_eq!(shard_warnings(&config), vec!["$.shardsCheckInterval", "$.shardStallAfter"]);
+
+        let config = Config { shards_check_interval: 0, enable_afk_farming: false, ..Config::default() };
+        assert!(shard_warnings(&config).is_empty());
+    }
+
+    #[test]
+    fn test_unknown_keys_are_warnings() {
+        let raw = json!({
+            "maxBuyPrice": 2500,
+            "mode": "normal",
+            "max_listings_per_cycle": 5,
+            "webhook": { "enabled": false, "display_name": "Bot" }
+        });
+        let issues = validate(&Config::default(), Some(&raw));
+        let warnings = paths(&issues, Severity::Warning);
+        assert!(warnings.contains(&"$.mode"));
+        assert!(warnings.contains(&"$.max_listings_per_cycle"));
+        assert!(warnings.contains(&"$.webhook.display_name"));
+
+        let hint = issues.iter().find(|i| i.path == "$.max_listings_per_cycle").unwrap();
+        assert!(hint.message.contains("did you mean \"maxListingsPerCycle\""));
+        let deprecated = issues.iter().find(|i| i.path == "$.webhook.display_name").unwrap();
+        assert!(deprecated.message.contains("use \"displayName\""));
+    }
+}
@@ -36,6 +36,9 @@ pub async fn send_webhook(
         "afk" => config.webhook.events.afk,
         "error" => config.webhook.events.error,
         "startup" => config.webhook.events.startup,
+        "digest" => config.webhook.events.digest,
+        "config" => config.webhook.events.config,
+        "limits" => config.webhook.events.limits,
         "shards" => true, // Always allow shards tracking
         _ => false,
     };